@@ -0,0 +1,169 @@
+//! Measures set, get and multi-get throughput of `Client` against a local memcached, and the cost
+//! of encoding values.
+//!
+//! The target is read from `MEMCACHED_URL` (`memcache://127.0.0.1:11211` by default), the client
+//! benchmarks are skipped when it is not reachable. With the "compress" feature, every benchmark
+//! compares plain and compressed values in the same run:
+//!
+//! ```text
+//! cargo bench --bench client --features compress
+//! ```
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::hint::black_box;
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use vmemcached::{Client, ConnectionManager, MemcacheError, Pool, Settings};
+
+const MULTI_GET_KEYS: usize = 50;
+const VALUE_SIZE: usize = 1024;
+
+fn target() -> String {
+    std::env::var("MEMCACHED_URL").unwrap_or_else(|_| "memcache://127.0.0.1:11211".into())
+}
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("failed to build runtime")
+}
+
+/// Value looking like the JSON documents usually cached, so that compression has work to do
+fn value() -> Vec<String> {
+    (0..VALUE_SIZE / 8)
+        .map(|i| format!("word{:03}", i % 100))
+        .collect()
+}
+
+/// Clients sharing given pool, named after the codec their settings select
+///
+/// Values of the plain client are never large enough to be compressed.
+fn clients(pool: Pool) -> Vec<(&'static str, Client)> {
+    let mut clients = vec![(
+        "plain",
+        Client::with_pool(pool.clone(), Settings::new().compress_min_size(usize::MAX)),
+    )];
+    if cfg!(feature = "compress") {
+        clients.push(("compressed", Client::with_pool(pool, Settings::new())));
+    }
+
+    clients
+}
+
+async fn connect(target: &str) -> Result<Pool, MemcacheError> {
+    let pool = Pool::builder()
+        .max_size(4)
+        .connection_timeout(Duration::from_millis(200))
+        .build(ConnectionManager::try_from(target)?)
+        .await?;
+
+    // Pool build does not fail on an unreachable server, a round trip does
+    let _ = Client::with_pool(pool.clone(), Settings::new())
+        .version()
+        .await?;
+
+    Ok(pool)
+}
+
+fn encode(c: &mut Criterion) {
+    // Encoding needs no server, connections are never opened
+    let runtime = runtime();
+    let _guard = runtime.enter();
+    let manager = ConnectionManager::try_from(target().as_str()).expect("invalid MEMCACHED_URL");
+    let pool = Pool::builder().build_unchecked(manager);
+    let value = value();
+
+    let mut group = c.benchmark_group("encode");
+    for (codec, client) in clients(pool) {
+        let _ = group.bench_function(codec, |b| {
+            b.iter(|| client.encode(black_box(&value)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn client(c: &mut Criterion) {
+    let target = target();
+    let runtime = runtime();
+
+    let pool = match runtime.block_on(connect(&target)) {
+        Ok(pool) => pool,
+        Err(e) => {
+            println!(
+                "skipping client benchmarks, {} is unavailable: {}",
+                target, e
+            );
+            return;
+        }
+    };
+
+    let value = value();
+    let clients = clients(pool);
+    let keys: HashMap<&str, Vec<String>> = clients
+        .iter()
+        .map(|(codec, _)| {
+            let keys = (0..MULTI_GET_KEYS)
+                .map(|i| format!("bench_{}_{}", codec, i))
+                .collect();
+            (*codec, keys)
+        })
+        .collect();
+
+    let mut group = c.benchmark_group("set");
+    let _ = group.throughput(Throughput::Elements(1));
+    for (codec, client) in &clients {
+        let key = &keys[codec][0];
+        let _ = group.bench_function(BenchmarkId::from_parameter(codec), |b| {
+            b.to_async(&runtime).iter(|| async {
+                client
+                    .set(key, &value, Duration::from_secs(60))
+                    .await
+                    .unwrap()
+            })
+        });
+    }
+    group.finish();
+
+    // Every key read below holds a value
+    runtime.block_on(async {
+        for (codec, client) in &clients {
+            for key in &keys[codec] {
+                let _ = client
+                    .set(key, &value, Duration::from_secs(60))
+                    .await
+                    .unwrap();
+            }
+        }
+    });
+
+    let mut group = c.benchmark_group("get");
+    let _ = group.throughput(Throughput::Elements(1));
+    for (codec, client) in &clients {
+        let key = &keys[codec][0];
+        let _ = group.bench_function(BenchmarkId::from_parameter(codec), |b| {
+            b.to_async(&runtime).iter(|| async {
+                let value: Option<Vec<String>> = client.get(key).await.unwrap();
+                value
+            })
+        });
+    }
+    group.finish();
+
+    let mut group = c.benchmark_group("multi-get");
+    let _ = group.throughput(Throughput::Elements(MULTI_GET_KEYS as u64));
+    for (codec, client) in &clients {
+        let keys = &keys[codec];
+        let _ = group.bench_function(BenchmarkId::from_parameter(codec), |b| {
+            b.to_async(&runtime).iter(|| async {
+                let values: Option<HashMap<String, Vec<String>>> = client.gets(keys).await.unwrap();
+                values
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, encode, client);
+criterion_main!(benches);
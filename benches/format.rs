@@ -0,0 +1,52 @@
+//! Compares formatting storage command integer fields through `to_string` against `itoa`.
+//!
+//! Run with `cargo bench --bench format`.
+use bytes::{BufMut, BytesMut};
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::hint::black_box;
+
+/// Appends flags, exptime and length separated by spaces
+type Formatter = fn(&mut BytesMut, u32, u64, usize);
+
+fn with_to_string(request: &mut BytesMut, flags: u32, exptime: u64, length: usize) {
+    request.put_slice(flags.to_string().as_bytes());
+    request.put_slice(b" ");
+    request.put_slice(exptime.to_string().as_bytes());
+    request.put_slice(b" ");
+    request.put_slice(length.to_string().as_bytes());
+}
+
+fn with_itoa(request: &mut BytesMut, flags: u32, exptime: u64, length: usize) {
+    let mut buffer = itoa::Buffer::new();
+    request.put_slice(buffer.format(flags).as_bytes());
+    request.put_slice(b" ");
+    request.put_slice(buffer.format(exptime).as_bytes());
+    request.put_slice(b" ");
+    request.put_slice(buffer.format(length).as_bytes());
+}
+
+fn format(c: &mut Criterion) {
+    let formatters: [(&str, Formatter); 2] = [("to_string", with_to_string), ("itoa", with_itoa)];
+
+    let mut group = c.benchmark_group("format");
+    for (name, f) in formatters {
+        let mut request = BytesMut::with_capacity(64);
+
+        let _ = group.bench_function(name, |b| {
+            b.iter(|| {
+                request.clear();
+                f(
+                    &mut request,
+                    black_box(42),
+                    black_box(3600),
+                    black_box(1024),
+                );
+                black_box(&request);
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, format);
+criterion_main!(benches);
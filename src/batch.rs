@@ -0,0 +1,85 @@
+use crate::parser::{Response, Status};
+use crate::MemcacheError;
+
+/// Outcome of a batch operation, with one entry per item in the order of items
+///
+/// Error replies are kept per item instead of failing the whole batch.
+#[derive(Debug)]
+pub struct BatchResult {
+    /// Status of every item, or the error the server replied with
+    pub statuses: Vec<Result<Status, MemcacheError>>,
+}
+
+impl BatchResult {
+    pub(crate) fn from_responses(responses: Vec<Response>) -> Self {
+        let statuses = responses
+            .into_iter()
+            .map(|response| match response {
+                Response::Status(s) => Ok(s),
+                Response::Error(e) => Err(e.into()),
+                _ => unreachable!(),
+            })
+            .collect();
+
+        Self { statuses }
+    }
+
+    /// Number of items in the batch
+    pub fn len(&self) -> usize {
+        self.statuses.len()
+    }
+
+    /// Check if the batch had no items
+    pub fn is_empty(&self) -> bool {
+        self.statuses.is_empty()
+    }
+
+    /// Number of items stored
+    pub fn stored(&self) -> usize {
+        self.count(&Status::Stored)
+    }
+
+    /// Number of items not stored because the condition of the command was not met
+    pub fn not_stored(&self) -> usize {
+        self.count(&Status::NotStored)
+    }
+
+    /// Errors with the index of the item they belong to
+    pub fn errors(&self) -> impl Iterator<Item = (usize, &MemcacheError)> {
+        self.statuses
+            .iter()
+            .enumerate()
+            .filter_map(|(i, status)| status.as_ref().err().map(|e| (i, e)))
+    }
+
+    fn count(&self, expected: &Status) -> usize {
+        self.statuses
+            .iter()
+            .filter(|status| status.as_ref().ok() == Some(expected))
+            .count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BatchResult;
+    use crate::parser::{Response, Status};
+    use crate::ErrorKind;
+
+    #[test]
+    fn test_batch_result() {
+        let result = BatchResult::from_responses(vec![
+            Response::Status(Status::Stored),
+            Response::Error(ErrorKind::Server("out of memory".to_string())),
+            Response::Status(Status::NotStored),
+            Response::Status(Status::Stored),
+        ]);
+
+        assert_eq!(result.len(), 4);
+        assert_eq!(result.stored(), 2);
+        assert_eq!(result.not_stored(), 1);
+
+        let errors: Vec<usize> = result.errors().map(|(i, _)| i).collect();
+        assert_eq!(errors, vec![1]);
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Mutex, PoisonError};
+
+use crate::parser::Value;
+
+/// CAS identifiers of keys last read with `Settings::always_request_cas`, keyed by cache key
+///
+/// Holds at most `capacity` keys, evicting those read longest ago.
+#[derive(Debug)]
+pub(crate) struct CasCache {
+    capacity: usize,
+    entries: Mutex<Entries>,
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    /// Identifier and recording sequence number of each key
+    by_key: HashMap<Vec<u8>, (u64, u64)>,
+    /// Keys by recording sequence number, oldest first
+    by_age: BTreeMap<u64, Vec<u8>>,
+    sequence: u64,
+}
+
+impl Entries {
+    fn remove(&mut self, key: &[u8]) {
+        if let Some((_, sequence)) = self.by_key.remove(key) {
+            let _ = self.by_age.remove(&sequence);
+        }
+    }
+
+    fn insert(&mut self, key: Vec<u8>, cas: u64) {
+        self.remove(&key);
+
+        self.sequence += 1;
+        let _ = self.by_age.insert(self.sequence, key.clone());
+        let _ = self.by_key.insert(key, (cas, self.sequence));
+    }
+
+    fn evict(&mut self, capacity: usize) {
+        while self.by_key.len() > capacity {
+            match self.by_age.pop_first() {
+                Some((_, key)) => {
+                    let _ = self.by_key.remove(&key);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+impl CasCache {
+    /// Cache holding identifiers of at most `capacity` keys
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::default(),
+        }
+    }
+
+    /// Record identifiers of values read for given keys, forgetting those of keys not found
+    pub(crate) fn update<K: AsRef<[u8]>>(&self, keys: &[K], values: &[Value]) {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+
+        for key in keys {
+            entries.remove(key.as_ref());
+        }
+
+        for value in values {
+            if let Some(cas) = value.cas {
+                entries.insert(value.key.clone(), cas);
+            }
+        }
+
+        entries.evict(self.capacity);
+    }
+
+    /// Identifier of given key as last read
+    pub(crate) fn get(&self, key: &[u8]) -> Option<u64> {
+        let entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+
+        entries.by_key.get(key).map(|&(cas, _)| cas)
+    }
+
+    /// Forget all identifiers
+    pub(crate) fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+
+        entries.by_key.clear();
+        entries.by_age.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CasCache;
+    use crate::parser::Value;
+
+    #[test]
+    fn test_update() {
+        let value = |key: &[u8], cas| Value {
+            key: key.to_vec(),
+            cas,
+            flags: 0,
+            data: Vec::new(),
+        };
+
+        let cache = CasCache::new(10);
+        cache.update(&["a", "b"], &[value(b"a", Some(1)), value(b"b", Some(2))]);
+        assert_eq!(cache.get(b"a"), Some(1));
+        assert_eq!(cache.get(b"b"), Some(2));
+
+        // Missing keys are forgotten, other keys are left alone
+        cache.update(&["a", "c"], &[value(b"c", Some(3))]);
+        assert_eq!(cache.get(b"a"), None);
+        assert_eq!(cache.get(b"b"), Some(2));
+        assert_eq!(cache.get(b"c"), Some(3));
+
+        cache.update(&["c"], &[value(b"c", None)]);
+        assert_eq!(cache.get(b"c"), None);
+
+        cache.clear();
+        assert_eq!(cache.get(b"b"), None);
+    }
+
+    #[test]
+    fn test_eviction() {
+        let value = |key: &[u8], cas| Value {
+            key: key.to_vec(),
+            cas: Some(cas),
+            flags: 0,
+            data: Vec::new(),
+        };
+
+        let cache = CasCache::new(2);
+        cache.update(&["a", "b"], &[value(b"a", 1), value(b"b", 2)]);
+
+        // Reading "a" again makes "b" the key read longest ago
+        cache.update(&["a"], &[value(b"a", 3)]);
+        cache.update(&["c"], &[value(b"c", 4)]);
+        assert_eq!(cache.get(b"a"), Some(3));
+        assert_eq!(cache.get(b"b"), None);
+        assert_eq!(cache.get(b"c"), Some(4));
+
+        // Batches larger than the capacity keep their last keys
+        cache.update(
+            &["d", "e", "f"],
+            &[value(b"d", 5), value(b"e", 6), value(b"f", 7)],
+        );
+        assert_eq!(cache.get(b"a"), None);
+        assert_eq!(cache.get(b"d"), None);
+        assert_eq!(cache.get(b"e"), Some(6));
+        assert_eq!(cache.get(b"f"), Some(7));
+
+        let cache = CasCache::new(0);
+        cache.update(&["a"], &[value(b"a", 1)]);
+        assert_eq!(cache.get(b"a"), None);
+    }
+}
@@ -1,22 +1,119 @@
 use bb8::{PooledConnection, State};
-use futures_util::TryFutureExt;
+use futures_util::{FutureExt, TryFutureExt};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::future::Future;
+use std::sync::Arc;
 use std::time::Duration;
+#[cfg(feature = "timings")]
+use std::time::Instant;
+use tokio::io::AsyncRead;
 
-use crate::driver::{RetrievalCommand, StorageCommand};
+use crate::cas::CasCache;
+use crate::driver::{ArithmeticCommand, Protocol, RetrievalCommand, StorageCommand};
+use crate::flight::{Flight, SingleFlight};
 use crate::manager::ConnectionManager;
+use crate::metrics::Counters;
 use crate::parser::{self, Response};
-use crate::{codec, driver, ClientError, MemcacheError, Pool, Settings};
+use crate::slow_log::observe;
+#[cfg(feature = "metrics")]
+use crate::ClientMetrics;
+#[cfg(feature = "timings")]
+use crate::OpTimings;
+use crate::{
+    codec, driver, BatchResult, ClientError, DryRun, EncodedValue, ErrorKind, Expiration, Flags,
+    MemcacheError, MetaSetOpts, Pool, PoolConfig, ServerStats, Settings, SingleClient, StoreOpts,
+    Value, ValueHeader, ValueReader,
+};
+
+/// Convert status replies of a batch, failing on the first error reply
+fn statuses(responses: Vec<Response>) -> Result<Vec<parser::Status>, MemcacheError> {
+    responses
+        .into_iter()
+        .map(|response| match response {
+            Response::Status(s) => Ok(s),
+            Response::Error(e) => Err(e.into()),
+            _ => unreachable!(),
+        })
+        .collect()
+}
+
+/// Total length of given keys, logged for batches by slow operation warnings
+fn keys_length<K: AsRef<[u8]>>(keys: impl IntoIterator<Item = K>) -> usize {
+    keys.into_iter().map(|key| key.as_ref().len()).sum()
+}
+
+/// Decode stored value, attaching the key and beginning of the value to failures
+pub(crate) fn decode<V: DeserializeOwned>(
+    key: &[u8],
+    data: &[u8],
+    flags: u32,
+) -> Result<V, MemcacheError> {
+    codec::decode(data, flags.into()).map_err(|e| MemcacheError::decode(key, data, e))
+}
+
+/// Check that flags given along with a value to encode leave the format bits to the codec
+pub(crate) fn check_flags(flags: u32) -> Result<(), MemcacheError> {
+    if flags & Flags::FORMAT.bits() == 0 {
+        Ok(())
+    } else {
+        Err(ClientError::ReservedFlags(flags).into())
+    }
+}
 
 /// Client wrapping r2d2 memcached connection pool
-#[derive(Clone, Debug)]
-pub struct Client(Pool, Settings);
+///
+/// Keys are taken as `K: AsRef<[u8]>`, which references implement too, so owned keys can be
+/// passed as `&key` and reused after the call. Multi-key methods borrow their key slice.
+///
+/// Pool, settings and in-flight computations are shared by all clones of a client, so clone
+/// it for every task rather than wrapping it in an `Arc`.
+#[derive(Debug)]
+pub struct Client(Arc<Inner>);
+
+/// State shared by a client and its clones
+#[derive(Debug)]
+struct Inner {
+    pool: Pool,
+    settings: Settings,
+    flights: SingleFlight,
+    pool_config: Option<PoolConfig>,
+    cas: CasCache,
+    counters: Counters,
+}
+
+impl Clone for Client {
+    /// Cheap clone sharing the pool and settings of this client, no settings are copied.
+    fn clone(&self) -> Self {
+        Self(Arc::clone(&self.0))
+    }
+}
 
-pub(crate) fn check_key_len<K: AsRef<[u8]>>(key: K) -> Result<(), MemcacheError> {
-    if key.as_ref().len() > 250 {
-        Err(ClientError::KeyTooLong.into())
+/// Validates key length and content for given protocol.
+///
+/// Keys must be at most `max_length` bytes long, see `Settings::max_key_length`. With the ascii
+/// protocol keys must not contain whitespace or control characters, otherwise the command line
+/// sent to the server would be corrupted. The binary protocol sends keys apart from the command,
+/// so any byte is allowed, but their length must fit the 16-bit key length of request headers
+/// whatever `max_length`.
+pub(crate) fn check_key_len<K: AsRef<[u8]>>(
+    key: K,
+    max_length: usize,
+    protocol: Protocol,
+) -> Result<(), MemcacheError> {
+    let key = key.as_ref();
+
+    let max_length = match protocol {
+        Protocol::Ascii => max_length,
+        Protocol::Binary => max_length.min(usize::from(u16::MAX)),
+    };
+
+    if key.len() > max_length {
+        Err(ClientError::key_too_long(key).into())
+    } else if protocol == Protocol::Ascii && key.iter().any(|&b| b <= 0x20 || b == 0x7f) {
+        Err(ClientError::InvalidKey.into())
     } else {
         Ok(())
     }
@@ -25,35 +122,329 @@ pub(crate) fn check_key_len<K: AsRef<[u8]>>(key: K) -> Result<(), MemcacheError>
 impl Client {
     /// Initialize Client with given connection pool and settings
     pub fn with_pool(pool: Pool, settings: Settings) -> Self {
-        Self(pool, settings)
+        Self(Arc::new(Inner {
+            pool,
+            cas: CasCache::new(settings.cas_cache_capacity),
+            settings,
+            flights: SingleFlight::default(),
+            pool_config: None,
+            counters: Counters::new(),
+        }))
+    }
+
+    /// Initialize Client with a pool of given configuration, kept for `Client::pool_config`
+    ///
+    /// Pools needing more options can be built from `PoolConfig::builder` and passed to
+    /// `Client::with_pool`, at the cost of their configuration being unknown to the client.
+    pub async fn with_pool_config(
+        manager: ConnectionManager,
+        config: PoolConfig,
+        settings: Settings,
+    ) -> Result<Self, MemcacheError> {
+        let pool = config.builder().build(manager).await?;
+
+        Ok(Self(Arc::new(Inner {
+            pool,
+            cas: CasCache::new(settings.cas_cache_capacity),
+            settings,
+            flights: SingleFlight::default(),
+            pool_config: Some(config),
+            counters: Counters::new(),
+        })))
+    }
+
+    /// Connect to given URL with one connection instead of a pool, for one-shot tools
+    ///
+    /// See `SingleClient`, which `SingleClient::connect` also builds from a configured manager.
+    pub async fn connect_single(url: &str) -> Result<SingleClient, MemcacheError> {
+        SingleClient::connect(ConnectionManager::try_from(url)?, Settings::default()).await
+    }
+
+    /// Get configuration the pool was built with, `None` if it was given to `Client::with_pool`
+    pub fn pool_config(&self) -> Option<PoolConfig> {
+        self.0.pool_config
     }
 
     /// Returns information about the current state of the pool.
     pub fn state(&self) -> State {
-        self.0.state()
+        self.0.pool.state()
     }
 
     /// Get pool connection
     pub async fn get_connection(
         &self,
     ) -> Result<PooledConnection<'_, ConnectionManager>, MemcacheError> {
-        Ok(self.0.get().await?)
+        Ok(self.0.pool.get().await?)
+    }
+
+    /// Get pool connection, validating given keys against its protocol with `check_key_len`
+    async fn get_connection_for<K: AsRef<[u8]>>(
+        &self,
+        keys: impl IntoIterator<Item = K>,
+    ) -> Result<PooledConnection<'_, ConnectionManager>, MemcacheError> {
+        let conn = self.get_connection().await?;
+
+        for key in keys {
+            check_key_len(key, self.0.settings.max_key_length, conn.protocol())?;
+        }
+
+        Ok(conn)
     }
 
     /// Get clone of ConnectionManager pool
     pub fn get_pool(&self) -> Pool {
-        self.0.clone()
+        self.0.pool.clone()
     }
 
     /// Get reference of settings
     pub fn get_settings(&self) -> &Settings {
-        &self.1
+        &self.0.settings
     }
 
-    /// Get the server version
+    /// Check out idle pool connections together, without waiting for connections in use
+    ///
+    /// The pool has no non-blocking checkout, so each checkout is polled once and given up if it
+    /// can not complete right away, e.g. because another task took the last idle connection in
+    /// the meantime. A checkout given up this way may still have the pool open a replacement
+    /// connection in the background, which is rare as checkouts stop once none is idle.
+    fn checkout_idle(&self) -> Vec<PooledConnection<'_, ConnectionManager>> {
+        let idle = self.state().idle_connections as usize;
+
+        // Hold all connections at once, so that the pool does not hand out the same idle
+        // connection twice
+        let mut connections = Vec::with_capacity(idle);
+        for _ in 0..idle {
+            if self.state().idle_connections == 0 {
+                break;
+            }
+
+            match self.0.pool.get().now_or_never() {
+                Some(Ok(conn)) => connections.push(conn),
+                _ => break,
+            }
+        }
+
+        connections
+    }
+
+    /// Gracefully close idle pool connections
+    ///
+    /// Checks out every currently idle connection, shuts it down and discards it from the pool.
+    /// Connections in use by other tasks are left alone, and so are connections that become idle
+    /// while shutting down. All checked out connections are discarded even if shutting one down
+    /// fails, the first failure is returned. Note that the pool opens new connections again when
+    /// `min_idle` is configured or when it is used after shutdown.
+    pub async fn shutdown(&self) -> Result<(), MemcacheError> {
+        let mut result = Ok(());
+
+        for mut conn in self.checkout_idle() {
+            if let Err(e) = conn.disconnect().await {
+                result = result.and(Err(e.into()));
+            }
+        }
+
+        result
+    }
+
+    /// Open pooled connections ahead of traffic, returning how many are established
+    ///
+    /// Checks out as many connections as `min_idle` of `Client::pool_config` together, at least
+    /// one, so that missing ones are opened and all are checked before being returned to the
+    /// pool idle. Connects are bounded by `Settings::connect_timeout` and the pool's
+    /// `connection_timeout`. Fails only if no connection could be established.
+    pub async fn warm_up(&self) -> Result<usize, MemcacheError> {
+        let count = self
+            .0
+            .pool_config
+            .and_then(|config| config.min_idle)
+            .unwrap_or_default()
+            .max(1) as usize;
+
+        let mut connections = Vec::with_capacity(count);
+        let mut error = None;
+        for _ in 0..count {
+            match self.get_connection().await {
+                Ok(conn) => connections.push(conn),
+                Err(e) => error = Some(e),
+            }
+        }
+
+        match error {
+            Some(e) if connections.is_empty() => Err(e),
+            _ => Ok(connections.len()),
+        }
+    }
+
+    /// Gracefully close pooled connections idle for at least given time, returning how many
+    /// were closed
+    ///
+    /// Shrinks the pool on demand, e.g. ahead of a known quiet period, instead of waiting for
+    /// the pool's `idle_timeout`. Idle connections are checked out together as with
+    /// [`Client::shutdown`], without waiting for connections in use, and those used more
+    /// recently are returned to the pool. Connections failing to shut down are discarded as
+    /// well, the first failure is returned. The pool opens new connections again when `min_idle`
+    /// is configured.
+    pub async fn reap_idle(&self, idle: Duration) -> Result<usize, MemcacheError> {
+        let mut result = Ok(());
+        let mut reaped = 0;
+
+        for mut conn in self.checkout_idle() {
+            if conn.idle_time() >= idle {
+                reaped += 1;
+                if let Err(e) = conn.disconnect().await {
+                    result = result.and(Err(e.into()));
+                }
+            }
+        }
+
+        result.map(|()| reaped)
+    }
+
+    /// Get a handle building the requests this client would send, without sending them.
+    ///
+    /// Meant for tests of key formatting, flags and expiration conversion. Requests are built
+    /// for the ascii protocol, whatever the protocol of the pool.
+    pub fn dry_run(&self) -> DryRun<'_> {
+        DryRun(&self.0.settings)
+    }
+
+    /// Get the server version, e.g. "1.6.21", or "38.0.0 mcrouter" for mcrouter
+    ///
+    /// Surrounding whitespace is trimmed, see `Client::version_raw` for the reply as sent.
     pub async fn version(&self) -> Result<String, MemcacheError> {
+        let version = self.version_raw().await?;
+
+        Ok(version.trim().to_string())
+    }
+
+    /// Get the server version as sent after the "VERSION " prefix, flavor markers included
+    pub async fn version_raw(&self) -> Result<String, MemcacheError> {
+        let mut conn = self.get_connection().await?;
+        let exchange = driver::version(&mut conn, &self.0.settings);
+        observe(&self.0.settings, b"version", 0, exchange).await
+    }
+
+    /// Check whether the server speaks the meta protocol of memcached 1.6 and later.
+    ///
+    /// Probes with a meta no-op, servers and proxies without meta commands reject it. The answer
+    /// is kept on the pooled connection, so each connection is only probed once. Always `false`
+    /// with the binary protocol.
+    pub async fn supports_meta(&self) -> Result<bool, MemcacheError> {
+        let conn = self.get_connection().await?;
+        let exchange = driver::supports_meta(conn, &self.0.settings);
+        observe(&self.0.settings, b"mn", 0, exchange).await
+    }
+
+    /// Get server statistics as sent by the server.
+    pub async fn stats(&self) -> Result<HashMap<String, String>, MemcacheError> {
+        let mut conn = self.get_connection().await?;
+        let exchange = driver::stats(&mut conn, None, &self.0.settings);
+        observe(&self.0.settings, b"stats", 0, exchange).await
+    }
+
+    /// Get statistics group, e.g. `stats_with_arg("slabs")` sends "stats slabs".
+    pub async fn stats_with_arg(
+        &self,
+        arg: &str,
+    ) -> Result<HashMap<String, String>, MemcacheError> {
+        if arg.bytes().any(|b| b == b'\r' || b == b'\n') {
+            return Err(ClientError::Error("stats argument must be a single line".into()).into());
+        }
+
+        let mut conn = self.get_connection().await?;
+        let exchange = driver::stats(&mut conn, Some(arg), &self.0.settings);
+        observe(&self.0.settings, b"stats", 0, exchange).await
+    }
+
+    /// Get server statistics with well-known fields parsed.
+    pub async fn stats_typed(&self) -> Result<ServerStats, MemcacheError> {
+        self.stats().await.map(ServerStats::from)
+    }
+
+    /// Set the server logging level, as with `verbosity <level>`.
+    ///
+    /// Only available with the ascii protocol.
+    pub async fn set_verbosity(&self, level: u32) -> Result<(), MemcacheError> {
+        let mut conn = self.get_connection().await?;
+        let exchange = driver::verbosity(&mut conn, level, &self.0.settings);
+        observe(&self.0.settings, b"verbosity", 0, exchange).await
+    }
+
+    /// Set the server memory limit in megabytes, as with `cache_memlimit <megabytes>`.
+    ///
+    /// Only available with the ascii protocol.
+    pub async fn set_cache_memlimit(&self, megabytes: u32) -> Result<(), MemcacheError> {
+        let mut conn = self.get_connection().await?;
+        let exchange = driver::cache_memlimit(&mut conn, megabytes, &self.0.settings);
+        observe(&self.0.settings, b"cache_memlimit", 0, exchange).await
+    }
+
+    /// Invalidate all items in `delay` seconds, right away if 0, as with `flush_all <delay>`.
+    ///
+    /// Only the server the pooled connection is connected to is flushed, see
+    /// [`Client::flush_all_nodes`]. Only available with the ascii protocol.
+    pub async fn flush_all(&self, delay: u32) -> Result<(), MemcacheError> {
+        let mut conn = self.get_connection().await?;
+        let exchange = driver::flush_all(&mut conn, delay, &self.0.settings);
+        observe(&self.0.settings, b"flush_all", 0, exchange).await
+    }
+
+    /// Flush all items through every open pool connection, returning one result per connection.
+    ///
+    /// Behind a proxy or load balancer connections may reach different backends. As many
+    /// connections as the pool has open, but at least one, are checked out together, waiting for
+    /// those in use, so that each flush goes out on a distinct connection. Without a way to list
+    /// servers this is best effort: backends none of the connections reaches are not flushed.
+    /// With a single server it is the same as [`Client::flush_all`].
+    pub async fn flush_all_nodes(&self, delay: u32) -> Vec<Result<(), MemcacheError>> {
+        let count = self.state().connections.max(1) as usize;
+
+        let mut conns = Vec::with_capacity(count);
+        for _ in 0..count {
+            conns.push(self.get_connection().await);
+        }
+
+        let mut results = Vec::with_capacity(count);
+        for conn in conns {
+            let result = match conn {
+                Ok(mut conn) => {
+                    let exchange = driver::flush_all(&mut conn, delay, &self.0.settings);
+                    observe(&self.0.settings, b"flush_all", 0, exchange).await
+                }
+                Err(e) => Err(e),
+            };
+            results.push(result);
+        }
+
+        results
+    }
+
+    /// Stream metadata of all items, as listed by "lru_crawler metadump all".
+    ///
+    /// Items are parsed as the server sends them rather than buffered, and the stream holds a
+    /// pooled connection until it ends. Only available with the ascii protocol.
+    #[cfg(feature = "metadump")]
+    pub fn key_dump(
+        &self,
+    ) -> impl futures_util::Stream<Item = Result<crate::KeyMeta, MemcacheError>> + '_ {
+        use futures_util::{stream, TryStreamExt};
+
+        stream::once(self.get_connection())
+            .map_ok(move |conn| driver::metadump(conn, &self.0.settings))
+            .try_flatten()
+    }
+
+    /// Send a raw command line and return the raw response.
+    ///
+    /// This is an advanced and unvalidated API meant for rarely used commands such as
+    /// `lru_crawler` or `slabs reassign`. The line is sent as is, followed by "\r\n", and the
+    /// response is read until a line that is "END", "OK", "ERROR", "CLIENT_ERROR ..." or
+    /// "SERVER_ERROR ...". Commands answering with anything else never complete, and a value
+    /// containing such a line ends the response early. Only available with the ascii protocol.
+    pub async fn command(&self, line: &str) -> Result<Vec<u8>, MemcacheError> {
         let mut conn = self.get_connection().await?;
-        driver::version(&mut conn, &self.1).await
+        let exchange = driver::command(&mut conn, line, &self.0.settings);
+        observe(&self.0.settings, line.as_bytes(), 0, exchange).await
     }
 
     /// Get a key from memcached server.
@@ -61,165 +452,1947 @@ impl Client {
         &self,
         key: K,
     ) -> Result<Option<V>, MemcacheError> {
-        check_key_len(&key)?;
-
         let keys = &[key];
+        let (command, name): (_, &[u8]) = if self.0.settings.always_request_cas {
+            (RetrievalCommand::Gets, b"gets")
+        } else {
+            (RetrievalCommand::Get, b"get")
+        };
 
         // <command name> <key> <flags> <exptime> <bytes> [noreply]\r\n
-        self.get_connection()
-            .and_then(|conn| driver::retrieve(conn, RetrievalCommand::Get, keys, &self.1))
+        let result = self
+            .get_connection_for(keys)
+            .and_then(|conn| {
+                let exchange = driver::retrieve(conn, command, keys, &self.0.settings);
+                observe(&self.0.settings, name, keys[0].as_ref().len(), exchange)
+            })
             .and_then(|response| async {
+                self.remember_cas(keys, response.as_deref().unwrap_or_default());
+
                 if let Some(mut values) = response {
                     let value = values.swap_remove(0);
-                    codec::decode(value.data)
+                    decode(&value.key, &value.data, value.flags)
                 } else {
                     Ok(None)
                 }
             })
-            .await
+            .await;
+
+        self.0
+            .counters
+            .get(1, &result, |value| usize::from(value.is_some()));
+
+        result
     }
 
-    /// Get keys from memcached server.
-    pub async fn gets<K: AsRef<[u8]>, V: DeserializeOwned>(
+    /// Get a key, reading its data block into the `scratch` buffer instead of a fresh allocation.
+    ///
+    /// Previous contents of `scratch` are replaced, so a buffer kept across calls saves an
+    /// allocation per call on hot read paths. Unlike [`Client::get`], CAS identifiers are never
+    /// requested.
+    pub async fn get_into<K: AsRef<[u8]>, V: DeserializeOwned>(
         &self,
-        keys: &[K],
-    ) -> Result<Option<HashMap<String, V>>, MemcacheError> {
-        for key in keys.iter() {
-            check_key_len(&key)?;
+        key: K,
+        scratch: &mut Vec<u8>,
+    ) -> Result<Option<V>, MemcacheError> {
+        let result = match self.get_connection_for([&key]).await {
+            Ok(conn) => {
+                let exchange = driver::retrieve_into(conn, &key, scratch, &self.0.settings);
+                observe(&self.0.settings, b"get", key.as_ref().len(), exchange).await
+            }
+            Err(e) => Err(e),
+        };
+
+        let result = match result {
+            Ok(Some(flags)) => decode(key.as_ref(), scratch, flags),
+            Ok(None) => Ok(None),
+            Err(e) => Err(e),
+        };
+
+        self.0
+            .counters
+            .get(1, &result, |value| usize::from(value.is_some()));
+
+        result
+    }
+
+    /// Get a key with its bytes and flags exactly as stored, without decoding.
+    ///
+    /// Together with [`Client::set_raw`] this copies items between servers unchanged.
+    pub async fn get_raw<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<Value>, MemcacheError> {
+        let keys = &[key];
+
+        let result = self
+            .get_connection_for(keys)
+            .and_then(|conn| {
+                let exchange =
+                    driver::retrieve(conn, RetrievalCommand::Get, keys, &self.0.settings);
+                observe(&self.0.settings, b"get", keys[0].as_ref().len(), exchange)
+            })
+            .map_ok(|response| response.map(|mut values| values.swap_remove(0)))
+            .await;
+
+        self.0
+            .counters
+            .get(1, &result, |value| usize::from(value.is_some()));
+
+        result
+    }
+
+    /// Get the flags, data block length and CAS identifier of a key, without its value.
+    ///
+    /// The data block is discarded as it arrives, so buffers such as an HTTP body can be sized
+    /// before fetching the value itself.
+    pub async fn get_header<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+    ) -> Result<Option<ValueHeader>, MemcacheError> {
+        let result = self
+            .get_connection_for([&key])
+            .and_then(|conn| {
+                let key_length = key.as_ref().len();
+                let exchange = driver::retrieve_header(conn, &key, &self.0.settings);
+                observe(&self.0.settings, b"gets", key_length, exchange)
+            })
+            .await;
+
+        self.0
+            .counters
+            .get(1, &result, |header| usize::from(header.is_some()));
+
+        result
+    }
+
+    /// Get a key, timing the wait for a pooled connection apart from the server round trip.
+    #[cfg(feature = "timings")]
+    pub async fn get_timed<K: AsRef<[u8]>, V: DeserializeOwned>(
+        &self,
+        key: K,
+    ) -> Result<(Option<V>, OpTimings), MemcacheError> {
+        let keys = &[key];
+
+        let result = async {
+            let started = Instant::now();
+            let conn = self.get_connection_for(keys).await?;
+            let acquired = Instant::now();
+            let exchange = driver::retrieve(conn, RetrievalCommand::Get, keys, &self.0.settings);
+            let response =
+                observe(&self.0.settings, b"get", keys[0].as_ref().len(), exchange).await?;
+            let timings = OpTimings::finish("get", started, acquired);
+
+            let value = match response {
+                Some(mut values) => {
+                    let value = values.swap_remove(0);
+                    decode(&value.key, &value.data, value.flags)?
+                }
+                None => None,
+            };
+
+            Ok((value, timings))
         }
+        .await;
 
-        // <command name> <key> <flags> <exptime> <bytes> [noreply]\r\n
-        self.get_connection()
-            .and_then(|conn| driver::retrieve(conn, RetrievalCommand::Gets, keys, &self.1))
-            .and_then(|response| async {
-                if let Some(values) = response {
-                    let mut map: HashMap<String, V> = HashMap::with_capacity(values.len());
+        self.0
+            .counters
+            .get(1, &result, |(value, _)| usize::from(value.is_some()));
+
+        result
+    }
+
+    /// Get a key with its flags and CAS identifier along with the decoded value.
+    ///
+    /// Flags hold the codec bits set by this client next to bits set with `StoreOpts::flags`.
+    pub async fn get_full<K: AsRef<[u8]>, V: DeserializeOwned>(
+        &self,
+        key: K,
+    ) -> Result<Option<(V, u32, Option<u64>)>, MemcacheError> {
+        let keys = &[key];
 
-                    for value in values.into_iter() {
-                        let decoded: V = codec::decode(value.data)?;
+        let result = self
+            .get_connection_for(keys)
+            .and_then(|conn| {
+                let exchange =
+                    driver::retrieve(conn, RetrievalCommand::Gets, keys, &self.0.settings);
+                observe(&self.0.settings, b"gets", keys[0].as_ref().len(), exchange)
+            })
+            .and_then(|response| async {
+                if let Some(mut values) = response {
+                    let value = values.swap_remove(0);
+                    let decoded = decode(&value.key, &value.data, value.flags)?;
 
-                        let _ = map.insert(String::from_utf8(value.key)?, decoded);
-                    }
-                    Ok(Some(map))
+                    Ok(Some((decoded, value.flags, value.cas)))
                 } else {
                     Ok(None)
                 }
             })
-            .await
+            .await;
+
+        self.0
+            .counters
+            .get(1, &result, |value| usize::from(value.is_some()));
+
+        result
     }
 
-    #[inline]
-    async fn store<K: AsRef<[u8]>, T: Serialize, E>(
+    /// Get a key, retrying failed attempts as set by `Settings::retry_policy`.
+    ///
+    /// Only connection failures and timeouts are retried, each attempt on a fresh pooled
+    /// connection.
+    pub async fn get_retry<K: AsRef<[u8]>, V: DeserializeOwned>(
         &self,
-        cmd: StorageCommand,
         key: K,
-        value: T,
-        expiration: E,
-    ) -> Result<parser::Status, MemcacheError>
-    where
-        E: Into<Option<Duration>>,
-    {
-        check_key_len(&key)?;
+    ) -> Result<Option<V>, MemcacheError> {
+        self.0.settings.retry_policy.run(|| self.get(&key)).await
+    }
 
-        let encoded = codec::encode(value)?;
+    /// Get a key as a reader over its raw bytes, without reading the whole value into memory.
+    ///
+    /// The bytes are returned as stored, see [`Client::set_reader`]. The reader holds a pooled
+    /// connection until it is read to the end. Only available with the ascii protocol.
+    pub async fn get_reader<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+    ) -> Result<Option<ValueReader<'_>>, MemcacheError> {
+        let result = match self.get_connection_for([&key]).await {
+            Ok(conn) => {
+                let key_length = key.as_ref().len();
+                let exchange = driver::retrieve_reader(conn, key, &self.0.settings);
+                observe(&self.0.settings, b"get", key_length, exchange).await
+            }
+            Err(e) => Err(e),
+        };
 
-        // <command name> <key> <flags> <exptime> <bytes> [noreply]\r\n
-        self.get_connection()
+        self.0
+            .counters
+            .get(1, &result, |reader| usize::from(reader.is_some()));
+
+        result
+    }
+
+    /// Get a key together with its cas unique token from memcached server.
+    ///
+    /// The token can be passed to [`Client::cas`] to store a new value only if the key was not
+    /// modified in the meantime.
+    pub async fn get_with_cas<K: AsRef<[u8]>, V: DeserializeOwned>(
+        &self,
+        key: K,
+    ) -> Result<Option<(V, u64)>, MemcacheError> {
+        let keys = &[key];
+
+        let result = self
+            .get_connection_for(keys)
             .and_then(|conn| {
-                driver::storage(conn, cmd, key, 0, expiration, encoded, false, &self.1)
+                let exchange =
+                    driver::retrieve(conn, RetrievalCommand::Gets, keys, &self.0.settings);
+                observe(&self.0.settings, b"gets", keys[0].as_ref().len(), exchange)
             })
             .and_then(|response| async {
-                match response {
-                    Response::Status(s) => Ok(s),
-                    Response::Error(e) => Err(e.into()),
-                    _ => unreachable!(),
+                if let Some(mut values) = response {
+                    let value = values.swap_remove(0);
+                    let cas = value.cas.ok_or_else(|| {
+                        ErrorKind::Protocol(Some("missing cas unique in response".to_string()))
+                    })?;
+
+                    let decoded: V = decode(&value.key, &value.data, value.flags)?;
+
+                    Ok(Some((decoded, cas)))
+                } else {
+                    Ok(None)
                 }
             })
-            .await
+            .await;
+
+        self.0
+            .counters
+            .get(1, &result, |value| usize::from(value.is_some()));
+
+        result
     }
 
-    /// Set a key with associate value into memcached server with expiration seconds.
-    pub async fn set<K: AsRef<[u8]>, T: Serialize, E>(
+    /// Set many keys with the same expiration, pipelining commands over one connection.
+    ///
+    /// Statuses are returned in the order of items, error replies to single items do not fail
+    /// the batch. Requests are flushed whenever `Settings::max_pipeline_bytes` is reached.
+    pub async fn set_many<K: AsRef<[u8]>, T: Serialize, E>(
         &self,
-        key: K,
-        value: T,
+        items: &[(K, T)],
         expiration: E,
-    ) -> Result<parser::Status, MemcacheError>
+    ) -> Result<BatchResult, MemcacheError>
     where
-        E: Into<Option<Duration>>,
+        E: Into<Expiration>,
     {
-        self.store(driver::StorageCommand::Set, key, value, expiration)
+        self.store_many(StorageCommand::Set, items, expiration)
             .await
     }
 
-    /// Add means "store this data, but only if the server *doesn't* already
-    /// hold data for this key".
-    pub async fn add<K: AsRef<[u8]>, T: Serialize, E>(
+    /// Add many keys with the same expiration, pipelining commands over one connection.
+    ///
+    /// Keys already holding a value keep it and are reported as `Status::NotStored`, so values
+    /// written in the meantime are not overwritten. Statuses are returned in the order of items,
+    /// error replies to single items do not fail the batch.
+    pub async fn add_many<K: AsRef<[u8]>, T: Serialize, E>(
         &self,
-        key: K,
-        value: T,
+        items: &[(K, T)],
         expiration: E,
-    ) -> Result<parser::Status, MemcacheError>
+    ) -> Result<BatchResult, MemcacheError>
     where
-        E: Into<Option<Duration>>,
+        E: Into<Expiration>,
     {
-        self.store(driver::StorageCommand::Add, key, value, expiration)
+        self.store_many(StorageCommand::Add, items, expiration)
             .await
     }
 
-    /// "replace" means "store this data, but only if the server *does*
-    /// already hold data for this key".
-    pub async fn replace<K: AsRef<[u8]>, T: Serialize, E>(
+    async fn store_many<K: AsRef<[u8]>, T: Serialize, E>(
         &self,
-        key: K,
-        value: T,
+        cmd: StorageCommand,
+        items: &[(K, T)],
         expiration: E,
-    ) -> Result<parser::Status, MemcacheError>
+    ) -> Result<BatchResult, MemcacheError>
     where
-        E: Into<Option<Duration>>,
+        E: Into<Expiration>,
     {
-        self.store(driver::StorageCommand::Replace, key, value, expiration)
+        let mut encoded = Vec::with_capacity(items.len());
+        for (key, value) in items {
+            let value = codec::encode(value, &self.0.settings)?;
+            encoded.push((key, value.flags.bits(), value.data));
+        }
+
+        let result = self
+            .get_connection_for(items.iter().map(|(key, _)| key))
+            .and_then(|conn| {
+                let exchange =
+                    driver::storage_many(conn, cmd, &encoded, expiration, &self.0.settings);
+                observe(
+                    &self.0.settings,
+                    cmd.into(),
+                    keys_length(items.iter().map(|item| &item.0)),
+                    exchange,
+                )
+            })
             .await
-    }
+            .map(BatchResult::from_responses);
 
-    /// Delete a key with associate value into memcached server
-    pub async fn delete<K: AsRef<[u8]>>(&self, key: K) -> Result<parser::Status, MemcacheError> {
-        check_key_len(&key)?;
+        self.0.counters.set(items.len(), &result);
 
-        // <command name> <key> <flags> <exptime> <bytes> [noreply]\r\n
-        self.get_connection()
-            .and_then(|conn| driver::delete(conn, key, false, &self.1))
-            .and_then(|response| async {
-                match response {
-                    Response::Status(s) => Ok(s),
-                    Response::Error(e) => Err(e.into()),
-                    _ => unreachable!(),
-                }
+        result
+    }
+
+    /// Delete many keys, pipelining commands over one connection.
+    ///
+    /// Statuses are returned in the order of keys. Requests are flushed whenever
+    /// `Settings::max_pipeline_bytes` is reached.
+    pub async fn delete_many<K: AsRef<[u8]>>(
+        &self,
+        keys: &[K],
+    ) -> Result<Vec<parser::Status>, MemcacheError> {
+        let result = self
+            .get_connection_for(keys)
+            .and_then(|conn| {
+                let exchange = driver::delete_many(conn, keys, &self.0.settings);
+                observe(&self.0.settings, b"delete", keys_length(keys), exchange)
             })
             .await
+            .and_then(statuses);
+
+        self.0.counters.delete(keys.len(), &result);
+
+        result
     }
 
-    /// Delete a key with associate value into memcached server
-    pub async fn touch<K: AsRef<[u8]>, E>(
+    /// Touch many keys with the same expiration, pipelining commands over one connection.
+    ///
+    /// Statuses are returned in the order of keys. Requests are flushed whenever
+    /// `Settings::max_pipeline_bytes` is reached.
+    pub async fn touch_many<K: AsRef<[u8]>, E>(
         &self,
-        key: K,
+        keys: &[K],
         expiration: E,
-    ) -> Result<parser::Status, MemcacheError>
+    ) -> Result<Vec<parser::Status>, MemcacheError>
     where
-        E: Into<Option<Duration>>,
+        E: Into<Expiration>,
     {
-        check_key_len(&key)?;
-
-        // <command name> <key> <flags> <exptime> <bytes> [noreply]\r\n
-        self.get_connection()
-            .and_then(|conn| driver::touch(conn, key, expiration, false, &self.1))
-            .and_then(|response| async {
-                match response {
-                    Response::Status(s) => Ok(s),
-                    Response::Error(e) => Err(e.into()),
-                    _ => unreachable!(),
-                }
+        let result = self
+            .get_connection_for(keys)
+            .and_then(|conn| {
+                let exchange = driver::touch_many(conn, keys, expiration, &self.0.settings);
+                observe(&self.0.settings, b"touch", keys_length(keys), exchange)
             })
             .await
+            .and_then(statuses);
+
+        self.0.counters.touch(keys.len(), &result);
+
+        result
+    }
+
+    /// Check if a key exists on memcached server, without decoding its value.
+    pub async fn exists<K: AsRef<[u8]>>(&self, key: K) -> Result<bool, MemcacheError> {
+        let result = self
+            .get_connection_for([&key])
+            .and_then(|conn| {
+                let key_length = key.as_ref().len();
+                observe(
+                    &self.0.settings,
+                    b"exists",
+                    key_length,
+                    driver::exists(conn, &key, &self.0.settings),
+                )
+            })
+            .await;
+
+        self.0.counters.get(1, &result, |&found| usize::from(found));
+
+        result
+    }
+
+    /// Get keys from memcached server.
+    ///
+    /// Keys are requested in batches of at most `Settings::max_multiget_keys`, one request after
+    /// another, and the results are merged. An empty key list returns `None`.
+    ///
+    /// Fails with `ErrorKind::Protocol` if the server answers with a value for a key that was not
+    /// requested, or repeats a key more often than it was requested.
+    pub async fn gets<K: AsRef<[u8]>, V: DeserializeOwned>(
+        &self,
+        keys: &[K],
+    ) -> Result<Option<HashMap<String, V>>, MemcacheError> {
+        let values = self.retrieve_many(keys).await?;
+
+        if values.is_empty() {
+            return Ok(None);
+        }
+
+        // Sized for all requested keys, so that chunks of hits never grow the map
+        let mut map = HashMap::with_capacity(keys.len());
+
+        for value in values.into_iter() {
+            let decoded: V = decode(&value.key, &value.data, value.flags)?;
+
+            let _ = map.insert(String::from_utf8(value.key)?, decoded);
+        }
+
+        Ok(Some(map))
+    }
+
+    /// Get keys from memcached server, keyed by their raw bytes.
+    ///
+    /// Same as [`Client::gets`], except that keys need not be valid UTF-8. Keys not found are
+    /// missing from the map, which is empty if none were found.
+    pub async fn gets_bytes<K: AsRef<[u8]>, V: DeserializeOwned>(
+        &self,
+        keys: &[K],
+    ) -> Result<HashMap<Vec<u8>, V>, MemcacheError> {
+        let values = self.retrieve_many(keys).await?;
+
+        let mut map = HashMap::with_capacity(keys.len());
+
+        for value in values.into_iter() {
+            let decoded: V = decode(&value.key, &value.data, value.flags)?;
+
+            let _ = map.insert(value.key, decoded);
+        }
+
+        Ok(map)
+    }
+
+    /// Retrieve values of many keys, in batches of at most `Settings::max_multiget_keys`
+    async fn retrieve_many<K: AsRef<[u8]>>(&self, keys: &[K]) -> Result<Vec<Value>, MemcacheError> {
+        let mut values = Vec::with_capacity(keys.len());
+
+        for chunk in keys.chunks(self.0.settings.max_multiget_keys.max(1)) {
+            // <command name> <key> <flags> <exptime> <bytes> [noreply]\r\n
+            let response = self
+                .get_connection_for(chunk)
+                .and_then(|conn| {
+                    let exchange =
+                        driver::retrieve(conn, RetrievalCommand::Gets, chunk, &self.0.settings);
+                    observe(&self.0.settings, b"gets", keys_length(chunk), exchange)
+                })
+                .await;
+
+            self.0.counters.get(chunk.len(), &response, |values| {
+                values.as_ref().map_or(0, Vec::len)
+            });
+            let response = response?;
+
+            self.remember_cas(chunk, response.as_deref().unwrap_or_default());
+
+            if let Some(chunk_values) = response {
+                values.extend(chunk_values);
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// Remember CAS identifiers of values read for given keys, with
+    /// `Settings::always_request_cas`
+    fn remember_cas<K: AsRef<[u8]>>(&self, keys: &[K], values: &[Value]) {
+        if self.0.settings.always_request_cas {
+            self.0.cas.update(keys, values);
+        }
+    }
+
+    /// Get the CAS identifier of a key as last read by this client or its clones.
+    ///
+    /// Only remembered with `Settings::always_request_cas`, `None` otherwise or if the key was
+    /// not found when last read. The identifier can be passed to [`Client::cas`].
+    pub fn last_cas<K: AsRef<[u8]>>(&self, key: K) -> Option<u64> {
+        self.0.cas.get(key.as_ref())
+    }
+
+    /// Forget CAS identifiers remembered by this client and its clones for `Client::last_cas`.
+    pub fn clear_last_cas(&self) {
+        self.0.cas.clear()
+    }
+
+    /// Get counters of operations of this client and its clones.
+    ///
+    /// Every get, set, delete and touch path is counted, as well as the methods built on them,
+    /// see [`ClientMetrics`] for the list. Counters, meta commands other than `meta_set` and
+    /// server commands such as `stats` or `flush_all` are not counted.
+    #[cfg(feature = "metrics")]
+    pub fn metrics(&self) -> ClientMetrics {
+        self.0.counters.snapshot()
+    }
+
+    #[inline]
+    async fn store<K: AsRef<[u8]>, T: Serialize>(
+        &self,
+        cmd: StorageCommand,
+        key: K,
+        value: T,
+        opts: StoreOpts,
+    ) -> Result<parser::Status, MemcacheError> {
+        check_flags(opts.flags)?;
+
+        let encoded = codec::encode(value, &self.0.settings)?;
+        let opts = opts.flags(opts.flags | encoded.flags.bits());
+
+        self.store_encoded(cmd, key, &encoded.data, opts).await
+    }
+
+    #[inline]
+    async fn store_encoded<K: AsRef<[u8]>>(
+        &self,
+        cmd: StorageCommand,
+        key: K,
+        encoded: &[u8],
+        opts: StoreOpts,
+    ) -> Result<parser::Status, MemcacheError> {
+        // <command name> <key> <flags> <exptime> <bytes> [noreply]\r\n
+        let result = self
+            .get_connection_for([&key])
+            .and_then(|conn| {
+                let key_length = key.as_ref().len();
+                let exchange = driver::storage(
+                    conn,
+                    cmd,
+                    &key,
+                    opts.flags,
+                    opts.expiration,
+                    encoded,
+                    opts.noreply,
+                    &self.0.settings,
+                );
+                observe(&self.0.settings, cmd.into(), key_length, exchange)
+            })
+            .and_then(|response| async {
+                match response {
+                    Response::Status(s) => Ok(s),
+                    Response::Error(e) => Err(e.into()),
+                    _ => unreachable!(),
+                }
+            })
+            .await;
+
+        self.0.counters.set(1, &result);
+
+        result
+    }
+
+    /// Set a key to already encoded bytes, stored as is with the flags given in `opts`.
+    ///
+    /// The bytes bypass the codec, so they are never serialized nor compressed again.
+    pub async fn set_raw<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+        data: Vec<u8>,
+        opts: StoreOpts,
+    ) -> Result<parser::Status, MemcacheError> {
+        self.store_encoded(StorageCommand::Set, key, &data, opts)
+            .await
+    }
+
+    /// Set a key to bytes streamed from a reader, without holding the whole value in memory.
+    ///
+    /// `length` bytes are read from the reader, memcached needs the length ahead of the data.
+    /// The bytes are stored as is rather than encoded, so [`Client::get`] only reads them back
+    /// if they hold an encoded value. Only available with the ascii protocol.
+    pub async fn set_reader<K: AsRef<[u8]>, R, E>(
+        &self,
+        key: K,
+        reader: R,
+        length: usize,
+        expiration: E,
+    ) -> Result<parser::Status, MemcacheError>
+    where
+        R: AsyncRead + Unpin,
+        E: Into<Expiration>,
+    {
+        let result = self
+            .get_connection_for([&key])
+            .and_then(|conn| {
+                let key_length = key.as_ref().len();
+                let exchange = driver::storage_reader(
+                    conn,
+                    StorageCommand::Set,
+                    &key,
+                    0,
+                    expiration,
+                    reader,
+                    length,
+                    &self.0.settings,
+                );
+                observe(&self.0.settings, b"set", key_length, exchange)
+            })
+            .and_then(|response| async {
+                match response {
+                    Response::Status(s) => Ok(s),
+                    Response::Error(e) => Err(e.into()),
+                    _ => unreachable!(),
+                }
+            })
+            .await;
+
+        self.0.counters.set(1, &result);
+
+        result
+    }
+
+    /// Set a key with associate value into memcached server with expiration seconds.
+    ///
+    /// Waits for the server reply unless `Settings::set_noreply` is enabled.
+    pub async fn set<K: AsRef<[u8]>, T: Serialize, E>(
+        &self,
+        key: K,
+        value: T,
+        expiration: E,
+    ) -> Result<parser::Status, MemcacheError>
+    where
+        E: Into<Expiration>,
+    {
+        let opts = StoreOpts::new()
+            .expiration(expiration)
+            .noreply(self.0.settings.set_noreply);
+
+        self.set_with_opts(key, value, opts).await
+    }
+
+    /// Set a key, timing the wait for a pooled connection apart from the server round trip.
+    ///
+    /// Encoding the value is timed by neither.
+    #[cfg(feature = "timings")]
+    pub async fn set_timed<K: AsRef<[u8]>, T: Serialize, E>(
+        &self,
+        key: K,
+        value: T,
+        expiration: E,
+    ) -> Result<(parser::Status, OpTimings), MemcacheError>
+    where
+        E: Into<Expiration>,
+    {
+        let encoded = codec::encode(value, &self.0.settings)?;
+
+        let result = async {
+            let started = Instant::now();
+            let conn = self.get_connection_for([&key]).await?;
+            let acquired = Instant::now();
+            let key_length = key.as_ref().len();
+            let exchange = driver::storage(
+                conn,
+                StorageCommand::Set,
+                key,
+                encoded.flags.bits(),
+                expiration,
+                &encoded.data,
+                self.0.settings.set_noreply,
+                &self.0.settings,
+            );
+            let response = observe(&self.0.settings, b"set", key_length, exchange).await?;
+            let timings = OpTimings::finish("set", started, acquired);
+
+            match response {
+                Response::Status(s) => Ok((s, timings)),
+                Response::Error(e) => Err(e.into()),
+                _ => unreachable!(),
+            }
+        }
+        .await;
+
+        self.0.counters.set(1, &result);
+
+        result
+    }
+
+    /// Encode a value once, to be stored under many keys with [`Client::set_encoded`].
+    pub fn encode<T: Serialize>(&self, value: T) -> Result<EncodedValue, MemcacheError> {
+        codec::encode(value, &self.0.settings)
+    }
+
+    /// Set a key to a value encoded by [`Client::encode`], without encoding it again.
+    pub async fn set_encoded<K: AsRef<[u8]>, E>(
+        &self,
+        key: K,
+        encoded: &EncodedValue,
+        expiration: E,
+    ) -> Result<parser::Status, MemcacheError>
+    where
+        E: Into<Expiration>,
+    {
+        let opts = StoreOpts::new().expiration(expiration).flags(encoded.flags);
+
+        self.store_encoded(StorageCommand::Set, key, &encoded.data, opts)
+            .await
+    }
+
+    /// Store a key with the meta protocol, returning the status and the new CAS identifier.
+    ///
+    /// `opts` select the store mode, CAS check, expiration and flags, covering what the classic
+    /// path needs separate methods for. The CAS identifier is only returned with
+    /// `Status::Stored`. Flags setting the format bits reserved by `Flags` are rejected, as by
+    /// `Client::set_with_opts`. Needs memcached 1.6 or later, only available with the ascii
+    /// protocol.
+    pub async fn meta_set<K: AsRef<[u8]>, T: Serialize>(
+        &self,
+        key: K,
+        value: T,
+        opts: MetaSetOpts,
+    ) -> Result<(parser::Status, Option<u64>), MemcacheError> {
+        check_flags(opts.flags)?;
+
+        let encoded = codec::encode(value, &self.0.settings)?;
+        let opts = MetaSetOpts {
+            flags: opts.flags | encoded.flags.bits(),
+            ..opts
+        };
+
+        let result = self
+            .get_connection_for([&key])
+            .and_then(|conn| {
+                let key_length = key.as_ref().len();
+                let exchange = driver::meta_set(conn, &key, &opts, &encoded.data, &self.0.settings);
+                observe(&self.0.settings, b"ms", key_length, exchange)
+            })
+            .await;
+
+        self.0.counters.set(1, &result);
+
+        result
+    }
+
+    /// Set a key, retrying failed attempts as set by `Settings::retry_policy`.
+    ///
+    /// The value is encoded once for all attempts. Only connection failures and timeouts are
+    /// retried, each attempt on a fresh pooled connection.
+    pub async fn set_retry<K: AsRef<[u8]>, T: Serialize, E>(
+        &self,
+        key: K,
+        value: T,
+        expiration: E,
+    ) -> Result<parser::Status, MemcacheError>
+    where
+        E: Into<Expiration>,
+    {
+        let encoded = self.encode(value)?;
+        let expiration = expiration.into();
+
+        self.0
+            .settings
+            .retry_policy
+            .run(|| self.set_encoded(&key, &encoded, expiration))
+            .await
+    }
+
+    /// Set a key and read it back on the same connection, failing if the stored bytes differ.
+    ///
+    /// Catches writes silently dropped or altered behind proxies, at the cost of a second round
+    /// trip. Reading back from the same connection ensures the same server is asked. A value that
+    /// is not stored, e.g. because it is too large, fails with `ErrorKind::Generic` as well.
+    pub async fn set_verified<K: AsRef<[u8]>, T: Serialize, E>(
+        &self,
+        key: K,
+        value: T,
+        expiration: E,
+    ) -> Result<(), MemcacheError>
+    where
+        E: Into<Expiration>,
+    {
+        let encoded = codec::encode(value, &self.0.settings)?;
+        let flags = encoded.flags.bits();
+
+        let result = match self.get_connection_for([&key]).await {
+            Ok(conn) => {
+                let exchange = driver::storage_read_back(
+                    conn,
+                    StorageCommand::Set,
+                    &key,
+                    flags,
+                    expiration,
+                    &encoded.data,
+                    &self.0.settings,
+                );
+                observe(&self.0.settings, b"set", key.as_ref().len(), exchange).await
+            }
+            Err(e) => Err(e),
+        };
+
+        self.0.counters.set(1, &result);
+        let (response, value) = result?;
+
+        let status = match response {
+            Response::Status(s) => s,
+            Response::Error(e) => return Err(e.into()),
+            _ => unreachable!(),
+        };
+
+        let failure = match value {
+            _ if status != parser::Status::Stored => format!("set failed: {}", status),
+            Some(value) if value.data == encoded.data && value.flags == flags => return Ok(()),
+            Some(_) => "read back value differs from the stored one".to_string(),
+            None => "stored value was not found when read back".to_string(),
+        };
+
+        Err(ErrorKind::Generic(format!(
+            "{} for key {}",
+            failure,
+            String::from_utf8_lossy(key.as_ref())
+        ))
+        .into())
+    }
+
+    /// Set a key with associate value into memcached server with given expiration, flags and
+    /// noreply options.
+    ///
+    /// Flags setting the format bits reserved by `Flags` fail with `ClientError::ReservedFlags`.
+    pub async fn set_with_opts<K: AsRef<[u8]>, T: Serialize>(
+        &self,
+        key: K,
+        value: T,
+        opts: StoreOpts,
+    ) -> Result<parser::Status, MemcacheError> {
+        self.store(StorageCommand::Set, key, value, opts).await
+    }
+
+    /// Add means "store this data, but only if the server *doesn't* already
+    /// hold data for this key".
+    pub async fn add<K: AsRef<[u8]>, T: Serialize, E>(
+        &self,
+        key: K,
+        value: T,
+        expiration: E,
+    ) -> Result<parser::Status, MemcacheError>
+    where
+        E: Into<Expiration>,
+    {
+        self.store(
+            StorageCommand::Add,
+            key,
+            value,
+            StoreOpts::new().expiration(expiration),
+        )
+        .await
+    }
+
+    /// "replace" means "store this data, but only if the server *does*
+    /// already hold data for this key".
+    pub async fn replace<K: AsRef<[u8]>, T: Serialize, E>(
+        &self,
+        key: K,
+        value: T,
+        expiration: E,
+    ) -> Result<parser::Status, MemcacheError>
+    where
+        E: Into<Expiration>,
+    {
+        self.store(
+            StorageCommand::Replace,
+            key,
+            value,
+            StoreOpts::new().expiration(expiration),
+        )
+        .await
+    }
+
+    /// "cas" means "store this data, but only if no one else has updated it since I last fetched
+    /// it" with [`Client::get_with_cas`].
+    ///
+    /// Returns `Status::Exists` if the key was modified and `Status::NotFound` if it is gone.
+    pub async fn cas<K: AsRef<[u8]>, T: Serialize, E>(
+        &self,
+        key: K,
+        value: T,
+        expiration: E,
+        cas: u64,
+    ) -> Result<parser::Status, MemcacheError>
+    where
+        E: Into<Expiration>,
+    {
+        self.store(
+            StorageCommand::Cas(cas),
+            key,
+            value,
+            StoreOpts::new().expiration(expiration),
+        )
+        .await
+    }
+
+    /// "add" value, or return the value already stored by a competing writer.
+    ///
+    /// If the competing value expires before it can be read, the given value is returned without
+    /// another attempt to store it.
+    pub async fn add_or_get<K, V, E>(
+        &self,
+        key: K,
+        value: V,
+        expiration: E,
+    ) -> Result<V, MemcacheError>
+    where
+        K: AsRef<[u8]>,
+        V: Serialize + DeserializeOwned,
+        E: Into<Expiration>,
+    {
+        match self
+            .store(
+                StorageCommand::Add,
+                &key,
+                &value,
+                StoreOpts::new().expiration(expiration),
+            )
+            .await?
+        {
+            parser::Status::NotStored => Ok(self.get(key).await?.unwrap_or(value)),
+            _ => Ok(value),
+        }
+    }
+
+    /// Acquire a lock by adding given owner token under key, held until it expires or is
+    /// released with [`Client::unlock`].
+    ///
+    /// Returns `true` if the lock was acquired and `false` if it is held by someone else.
+    pub async fn try_lock<K: AsRef<[u8]>, E>(
+        &self,
+        key: K,
+        token: &str,
+        expiration: E,
+    ) -> Result<bool, MemcacheError>
+    where
+        E: Into<Expiration>,
+    {
+        let status = self.add(key, token, expiration).await?;
+
+        Ok(status == parser::Status::Stored)
+    }
+
+    /// Release a lock taken with [`Client::try_lock`] if it is still held by given owner token.
+    ///
+    /// The lock is expired with "cas", so a lock that expired and was taken by another owner in
+    /// the meantime is left alone. Returns `true` if the lock was released.
+    pub async fn unlock<K: AsRef<[u8]>>(&self, key: K, token: &str) -> Result<bool, MemcacheError> {
+        match self.get_with_cas::<_, String>(&key).await? {
+            Some((owner, cas)) if owner == token => {
+                let status = self.cas(key, token, Expiration::EXPIRED, cas).await?;
+
+                Ok(status == parser::Status::Stored)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Get a key, or compute, store and return its value on a miss.
+    ///
+    /// The computed value is stored with "add", so a concurrent writer that stored the key first
+    /// is not overwritten. The computed value is returned in either case.
+    pub async fn get_or_set<K, V, E, F, Fut>(
+        &self,
+        key: K,
+        expiration: E,
+        f: F,
+    ) -> Result<V, MemcacheError>
+    where
+        K: AsRef<[u8]>,
+        V: Serialize + DeserializeOwned,
+        E: Into<Expiration>,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = V>,
+    {
+        if let Some(value) = self.get(&key).await? {
+            return Ok(value);
+        }
+
+        let value = f().await;
+
+        let _ = self
+            .store(
+                StorageCommand::Add,
+                key,
+                &value,
+                StoreOpts::new().expiration(expiration),
+            )
+            .await?;
+
+        Ok(value)
+    }
+
+    /// Same as [`Client::get_or_set`], but concurrent misses of the same key within this client
+    /// and its clones await a single computation and share its result.
+    ///
+    /// If the computing call fails or is cancelled, waiting calls fall back to
+    /// [`Client::get_or_set`].
+    pub async fn get_or_set_single_flight<K, V, E, F, Fut>(
+        &self,
+        key: K,
+        expiration: E,
+        f: F,
+    ) -> Result<V, MemcacheError>
+    where
+        K: AsRef<[u8]>,
+        V: Serialize + DeserializeOwned,
+        E: Into<Expiration>,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = V>,
+    {
+        if let Some(value) = self.get(&key).await? {
+            return Ok(value);
+        }
+
+        match self.0.flights.join(key.as_ref()) {
+            Flight::Leader(leader) => {
+                let value = f().await;
+                let encoded = Arc::new(codec::encode(&value, &self.0.settings)?);
+
+                leader.complete(encoded.clone());
+
+                let opts = StoreOpts::new().expiration(expiration).flags(encoded.flags);
+                let _ = self
+                    .store_encoded(StorageCommand::Add, key, &encoded.data, opts)
+                    .await?;
+
+                Ok(value)
+            }
+            Flight::Follower(follower) => match follower.wait().await {
+                Some(encoded) => decode(key.as_ref(), &encoded.data, encoded.flags.bits()),
+                None => self.get_or_set(key, expiration, f).await,
+            },
+        }
+    }
+
+    /// Delete a key with associate value into memcached server
+    pub async fn delete<K: AsRef<[u8]>>(&self, key: K) -> Result<parser::Status, MemcacheError> {
+        // <command name> <key> <flags> <exptime> <bytes> [noreply]\r\n
+        let result = self
+            .get_connection_for([&key])
+            .and_then(|conn| {
+                let key_length = key.as_ref().len();
+                observe(
+                    &self.0.settings,
+                    b"delete",
+                    key_length,
+                    driver::delete(conn, &key, false, &self.0.settings),
+                )
+            })
+            .and_then(|response| async {
+                match response {
+                    Response::Status(s) => Ok(s),
+                    Response::Error(e) => Err(e.into()),
+                    _ => unreachable!(),
+                }
+            })
+            .await;
+
+        self.0.counters.delete(1, &result);
+
+        result
+    }
+
+    /// Increment a counter holding a decimal number, returning its new value or `None` if the
+    /// key is not found.
+    ///
+    /// Counters are stored as plain decimal numbers, which `Client::get` only decodes without
+    /// the "compress" feature. Only available with the ascii protocol.
+    pub async fn increment<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+        amount: u64,
+    ) -> Result<Option<u64>, MemcacheError> {
+        self.arithmetic(ArithmeticCommand::Incr, key, amount).await
+    }
+
+    /// Read the current value of a counter holding a decimal number, or `None` if the key is not
+    /// found.
+    ///
+    /// Sends `incr <key> 0`, which leaves the counter unchanged and saves decoding its value
+    /// from a get. Only available with the ascii protocol.
+    pub async fn counter_value<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+    ) -> Result<Option<u64>, MemcacheError> {
+        self.increment(key, 0).await
+    }
+
+    /// Decrement a counter holding a decimal number, returning its new value or `None` if the
+    /// key is not found. Counters stop at 0.
+    pub async fn decrement<K: AsRef<[u8]>>(
+        &self,
+        key: K,
+        amount: u64,
+    ) -> Result<Option<u64>, MemcacheError> {
+        self.arithmetic(ArithmeticCommand::Decr, key, amount).await
+    }
+
+    async fn arithmetic<K: AsRef<[u8]>>(
+        &self,
+        command: ArithmeticCommand,
+        key: K,
+        amount: u64,
+    ) -> Result<Option<u64>, MemcacheError> {
+        self.get_connection_for([&key])
+            .and_then(|conn| {
+                let key_length = key.as_ref().len();
+                let exchange = driver::arithmetic(conn, command, &key, amount, &self.0.settings);
+                observe(&self.0.settings, command.into(), key_length, exchange)
+            })
+            .await
+    }
+
+    /// Increment a counter, or initialize it to given value with given expiration if it is
+    /// missing. Returns the new value.
+    ///
+    /// The counter is initialized with "add", so when concurrent callers race to create it, one
+    /// of them stores its initial value and the others increment that.
+    pub async fn increment_with_default<K: AsRef<[u8]>, E>(
+        &self,
+        key: K,
+        amount: u64,
+        initial: u64,
+        expiration: E,
+    ) -> Result<u64, MemcacheError>
+    where
+        E: Into<Expiration>,
+    {
+        const MAX_ATTEMPTS: usize = 3;
+
+        let expiration = expiration.into();
+
+        for _ in 0..MAX_ATTEMPTS {
+            if let Some(value) = self.increment(&key, amount).await? {
+                return Ok(value);
+            }
+
+            let mut buffer = itoa::Buffer::new();
+            let encoded = buffer.format(initial).as_bytes();
+            let opts = StoreOpts::new().expiration(expiration);
+
+            match self
+                .store_encoded(StorageCommand::Add, &key, encoded, opts)
+                .await?
+            {
+                parser::Status::Stored => return Ok(initial),
+                // Created by a concurrent caller, increment it on the next attempt
+                _ => continue,
+            }
+        }
+
+        Err(ErrorKind::Generic("counter vanished while being initialized".to_string()).into())
+    }
+
+    /// Get the remaining time to live of a key in seconds, -1 if it never expires.
+    ///
+    /// Returns `None` if the key was not found. Uses the meta protocol of memcached 1.6 and later,
+    /// only available with the ascii protocol.
+    pub async fn ttl<K: AsRef<[u8]>>(&self, key: K) -> Result<Option<i64>, MemcacheError> {
+        self.get_connection_for([&key])
+            .and_then(|conn| {
+                let key_length = key.as_ref().len();
+                observe(
+                    &self.0.settings,
+                    b"mg",
+                    key_length,
+                    driver::ttl(conn, &key, &self.0.settings),
+                )
+            })
+            .await
+    }
+
+    /// Delete a key with associate value into memcached server
+    pub async fn touch<K: AsRef<[u8]>, E>(
+        &self,
+        key: K,
+        expiration: E,
+    ) -> Result<parser::Status, MemcacheError>
+    where
+        E: Into<Expiration>,
+    {
+        // <command name> <key> <flags> <exptime> <bytes> [noreply]\r\n
+        let result = self
+            .get_connection_for([&key])
+            .and_then(|conn| {
+                let key_length = key.as_ref().len();
+                let exchange = driver::touch(conn, &key, expiration, false, &self.0.settings);
+                observe(&self.0.settings, b"touch", key_length, exchange)
+            })
+            .and_then(|response| async {
+                match response {
+                    Response::Status(s) => Ok(s),
+                    Response::Error(e) => Err(e.into()),
+                    _ => unreachable!(),
+                }
+            })
+            .await;
+
+        self.0.counters.touch(1, &result);
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_key_len, decode};
+    use crate::driver::Protocol;
+    use crate::{ClientError, MemcacheError};
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_gets_chunks_keys() {
+        use crate::testing::MockServer;
+        use crate::{Client, ConnectionManager, Expiration, Pool, Settings};
+
+        let server = MockServer::new();
+        let pool = Pool::builder()
+            .build(ConnectionManager::mock(server).unwrap())
+            .await
+            .unwrap();
+        let client = Client::with_pool(pool, Settings::new().max_multiget_keys(2));
+
+        let keys = ["a", "b", "c", "d", "e"];
+        for key in &keys[..4] {
+            let _ = client.set(key, key, Expiration::Never).await.unwrap();
+        }
+
+        let values = client.gets::<_, String>(&keys).await.unwrap().unwrap();
+        assert_eq!(values.len(), 4);
+        assert_eq!(values["d"], "d");
+        assert!(!values.contains_key("e"));
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_get_into() {
+        use crate::testing::MockServer;
+
+        let client = MockServer::new().client().await.unwrap();
+        let _ = client.set("long", "x".repeat(100), None).await.unwrap();
+        let _ = client.set("short", "y", None).await.unwrap();
+
+        let mut scratch = Vec::new();
+        let value: Option<String> = client.get_into("long", &mut scratch).await.unwrap();
+        assert_eq!(value.unwrap(), "x".repeat(100));
+
+        let value: Option<String> = client.get_into("short", &mut scratch).await.unwrap();
+        assert_eq!(value.as_deref(), Some("y"));
+        assert_eq!(scratch, b"\"y\"");
+
+        let value: Option<String> = client.get_into("missing", &mut scratch).await.unwrap();
+        assert!(value.is_none());
+    }
+
+    #[cfg(all(feature = "test-util", feature = "metrics"))]
+    #[tokio::test]
+    async fn test_metrics() {
+        use crate::testing::MockServer;
+
+        let client = MockServer::new().client().await.unwrap();
+        let _ = client.set("a", 1, None).await.unwrap();
+        let _ = client.set_many(&[("b", 2), ("c", 3)], None).await.unwrap();
+
+        let _ = client.get::<_, u32>("a").await.unwrap();
+        let _ = client.get::<_, u32>("missing").await.unwrap();
+        let _ = client.gets::<_, u32>(&["b", "c", "d"]).await.unwrap();
+        assert!(client.get::<_, String>("a").await.is_err());
+        let _ = client.delete("a").await.unwrap();
+        let _ = client.touch("b", None).await.unwrap();
+        let _ = client.touch_many(&["c", "d"], None).await.unwrap();
+        let _ = client.set_reader("e", &b"raw"[..], 3, None).await.unwrap();
+        assert!(client.exists("e").await.unwrap());
+
+        let metrics = client.clone().metrics();
+        assert_eq!(metrics.gets, 7);
+        assert_eq!(metrics.hits, 4);
+        assert_eq!(metrics.misses, 2);
+        assert_eq!(metrics.sets, 4);
+        assert_eq!(metrics.deletes, 1);
+        assert_eq!(metrics.touches, 3);
+        assert_eq!(metrics.errors, 1);
+        assert_eq!(metrics.hit_ratio(), Some(4.0 / 6.0));
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_supports_meta() {
+        use crate::testing::MockServer;
+
+        let client = MockServer::new().client().await.unwrap();
+        assert!(client.supports_meta().await.unwrap());
+        assert!(client.supports_meta().await.unwrap());
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_get_header() {
+        use crate::testing::MockServer;
+        use crate::Flags;
+
+        let client = MockServer::new().client().await.unwrap();
+        let _ = client.set("foo", "bar", None).await.unwrap();
+
+        let header = client.get_header("foo").await.unwrap().unwrap();
+        assert_eq!(header.flags, Flags::SERIALIZED.bits());
+        assert_eq!(header.length, 5);
+        assert!(header.cas.is_some());
+
+        // Connection is left ready for the next command
+        assert_eq!(
+            client.get::<_, String>("foo").await.unwrap().as_deref(),
+            Some("bar")
+        );
+        assert_eq!(client.get_header("missing").await.unwrap(), None);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_gets_bytes() {
+        use crate::testing::MockServer;
+
+        let client = MockServer::new().client().await.unwrap();
+
+        let binary_key = &b"\xff\xfekey"[..];
+        let _ = client.set(binary_key, "binary", None).await.unwrap();
+        let _ = client.set("text", "text", None).await.unwrap();
+
+        let keys = [binary_key, b"text", b"missing"];
+        let values = client.gets_bytes::<_, String>(&keys).await.unwrap();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[binary_key], "binary");
+        assert_eq!(values[&b"text"[..]], "text");
+
+        assert!(client.gets::<_, String>(&keys).await.is_err());
+
+        let empty: [&[u8]; 0] = [];
+        assert!(client
+            .gets_bytes::<_, String>(&empty)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_pool_config() {
+        use crate::testing::MockServer;
+        use crate::{Client, ConnectionManager, PoolConfig, Settings};
+        use std::time::Duration;
+
+        let config = PoolConfig::new()
+            .max_size(3)
+            .min_idle(Some(1))
+            .connection_timeout(Duration::from_secs(1));
+        let manager = ConnectionManager::mock(MockServer::new()).unwrap();
+        let client = Client::with_pool_config(manager, config, Settings::new())
+            .await
+            .unwrap();
+
+        assert_eq!(client.pool_config(), Some(config));
+        assert_eq!(client.clone().pool_config(), Some(config));
+        assert_eq!(client.version().await.unwrap(), "mock");
+
+        let client = MockServer::new().client().await.unwrap();
+        assert_eq!(client.pool_config(), None);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_warm_up() {
+        use crate::testing::MockServer;
+        use crate::{Client, ConnectionManager, PoolConfig, Settings};
+
+        let config = PoolConfig::new().max_size(5).min_idle(Some(3));
+        let manager = ConnectionManager::mock(MockServer::new()).unwrap();
+        let client = Client::with_pool_config(manager, config, Settings::new())
+            .await
+            .unwrap();
+
+        assert_eq!(client.warm_up().await.unwrap(), 3);
+        assert!(client.state().idle_connections >= 3);
+
+        let client = MockServer::new().client().await.unwrap();
+        assert_eq!(client.warm_up().await.unwrap(), 1);
+
+        let manager = ConnectionManager::mock(MockServer::new())
+            .unwrap()
+            .with_connector(|| async {
+                Err(std::io::Error::from(std::io::ErrorKind::ConnectionRefused).into())
+            });
+        let pool = crate::Pool::builder()
+            .connection_timeout(std::time::Duration::from_millis(100))
+            .build_unchecked(manager);
+        let client = Client::with_pool(pool, Settings::new());
+        assert!(client.warm_up().await.is_err());
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_touch_many() {
+        use crate::testing::MockServer;
+        use crate::Status;
+        use std::time::Duration;
+
+        let client = MockServer::new().client().await.unwrap();
+
+        let _ = client.set("a", "value", None).await.unwrap();
+        let _ = client.set("c", "value", None).await.unwrap();
+
+        let statuses = client
+            .touch_many(&["a", "b", "c"], Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert_eq!(
+            statuses,
+            vec![Status::Touched, Status::NotFound, Status::Touched]
+        );
+        assert!(matches!(client.ttl("c").await.unwrap(), Some(59..=60)));
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_always_request_cas() {
+        use crate::testing::MockServer;
+        use crate::{Client, ConnectionManager, Pool, Settings, Status};
+
+        let pool = Pool::builder()
+            .build(ConnectionManager::mock(MockServer::new()).unwrap())
+            .await
+            .unwrap();
+        let client = Client::with_pool(pool, Settings::new().always_request_cas(true));
+
+        let _ = client.set("foo", "bar", None).await.unwrap();
+        assert_eq!(client.last_cas("foo"), None);
+
+        let value: Option<String> = client.get("foo").await.unwrap();
+        assert_eq!(value.as_deref(), Some("bar"));
+        let cas = client.last_cas("foo").unwrap();
+        assert_eq!(client.clone().last_cas("foo"), Some(cas));
+
+        let status = client.cas("foo", "baz", None, cas).await.unwrap();
+        assert_eq!(status, Status::Stored);
+
+        let _ = client.gets::<_, String>(&["foo"]).await.unwrap();
+        assert_ne!(client.last_cas("foo"), Some(cas));
+
+        client.clear_last_cas();
+        assert_eq!(client.last_cas("foo"), None);
+
+        let _ = client.delete("foo").await.unwrap();
+        assert_eq!(client.get::<_, String>("foo").await.unwrap(), None);
+        assert_eq!(client.last_cas("foo"), None);
+
+        let client = MockServer::new().client().await.unwrap();
+        let _ = client.set("foo", "bar", None).await.unwrap();
+        let _ = client.get::<_, String>("foo").await.unwrap();
+        assert_eq!(client.last_cas("foo"), None);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_reap_idle() {
+        use crate::testing::MockServer;
+        use crate::{Client, ConnectionManager, Pool, Settings};
+        use std::time::Duration;
+
+        let pool = Pool::builder()
+            .max_size(3)
+            .build(ConnectionManager::mock(MockServer::new()).unwrap())
+            .await
+            .unwrap();
+        let client = Client::with_pool(pool, Settings::new());
+
+        let _ = client.get_connection().await.unwrap();
+        let idle = client.state().idle_connections as usize;
+        assert!(idle > 0);
+
+        assert_eq!(client.reap_idle(Duration::from_secs(60)).await.unwrap(), 0);
+        assert_eq!(client.state().idle_connections as usize, idle);
+
+        assert_eq!(client.reap_idle(Duration::ZERO).await.unwrap(), idle);
+        assert_eq!(client.state().idle_connections, 0);
+
+        assert_eq!(client.version().await.unwrap(), "mock");
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_shutdown() {
+        use crate::testing::MockServer;
+        use crate::{Client, ConnectionManager, Pool, Settings};
+        use std::time::Duration;
+
+        let pool = Pool::builder()
+            .max_size(3)
+            .connection_timeout(Duration::from_secs(5))
+            .build(ConnectionManager::mock(MockServer::new()).unwrap())
+            .await
+            .unwrap();
+        let client = Client::with_pool(pool, Settings::new());
+
+        // Connections in use are neither waited for nor closed
+        let mut conn = client.get_connection().await.unwrap();
+        drop(client.get_connection().await.unwrap());
+        assert_eq!(client.state().idle_connections, 1);
+
+        tokio::time::timeout(Duration::from_millis(100), client.shutdown())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(client.state().idle_connections, 0);
+        assert_eq!(
+            crate::driver::version(&mut conn, &Settings::new())
+                .await
+                .unwrap(),
+            "mock"
+        );
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_version() {
+        use crate::testing::MockServer;
+
+        let server = MockServer::new();
+        let client = server.client().await.unwrap();
+        assert_eq!(client.version().await.unwrap(), "mock");
+
+        // As replied by mcrouter, the flavor marker is part of the version
+        server.fail_next("VERSION 38.0.0 mcrouter ");
+        assert_eq!(client.version_raw().await.unwrap(), "38.0.0 mcrouter ");
+        server.fail_next("VERSION 38.0.0 mcrouter ");
+        assert_eq!(client.version().await.unwrap(), "38.0.0 mcrouter");
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_add_many() {
+        use crate::testing::MockServer;
+
+        let client = MockServer::new().client().await.unwrap();
+        let _ = client.set("b", "fresh", None).await.unwrap();
+
+        let items = [("a", "default"), ("b", "default"), ("c", "default")];
+        let result = client.add_many(&items, None).await.unwrap();
+        assert_eq!(result.stored(), 2);
+        assert_eq!(result.not_stored(), 1);
+        assert_eq!(
+            result.statuses[1].as_ref().ok(),
+            Some(&crate::Status::NotStored)
+        );
+
+        let value: Option<String> = client.get("b").await.unwrap();
+        assert_eq!(value.as_deref(), Some("fresh"));
+        let value: Option<String> = client.get("c").await.unwrap();
+        assert_eq!(value.as_deref(), Some("default"));
+    }
+
+    #[tokio::test]
+    async fn test_clone_shares_settings() {
+        use crate::{Client, ConnectionManager, Pool, Settings};
+        use std::convert::TryFrom;
+
+        let manager = ConnectionManager::try_from("memcache://localhost:11211").unwrap();
+        let pool = Pool::builder().build_unchecked(manager);
+        let client = Client::with_pool(pool, Settings::new());
+
+        let clone = client.clone();
+        assert!(std::ptr::eq(client.get_settings(), clone.get_settings()));
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_borrowed_keys() {
+        use crate::testing::MockServer;
+
+        let client = MockServer::new().client().await.unwrap();
+
+        let key = String::from("foo");
+        let keys = vec![key.clone(), String::from("bar")];
+
+        let _ = client.set(&key, "value", None).await.unwrap();
+        let value: Option<String> = client.get(&key).await.unwrap();
+        assert_eq!(value.as_deref(), Some("value"));
+
+        let values = client.gets::<_, String>(&keys).await.unwrap().unwrap();
+        let missing: Vec<&String> = keys.iter().filter(|k| !values.contains_key(*k)).collect();
+        assert_eq!(missing, vec!["bar"]);
+
+        let _ = client.touch(&key, None).await.unwrap();
+        let _ = client.delete(&key).await.unwrap();
+        assert_eq!(key, "foo");
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_set_verified() {
+        use crate::testing::MockServer;
+
+        let server = MockServer::new();
+        let client = server.client().await.unwrap();
+
+        client.set_verified("foo", "bar", None).await.unwrap();
+        let value: Option<String> = client.get("foo").await.unwrap();
+        assert_eq!(value.as_deref(), Some("bar"));
+
+        // Write acknowledged but dropped
+        server.fail_next("STORED");
+        server.fail_next("END");
+        assert!(client.set_verified("foo", "baz", None).await.is_err());
+
+        // Write acknowledged but altered
+        server.fail_next("STORED");
+        server.fail_next("VALUE foo 1 5\r\n\"qux\"\r\nEND");
+        assert!(client.set_verified("foo", "baz", None).await.is_err());
+
+        server.fail_next("NOT_STORED");
+        assert!(client.set_verified("foo", "baz", None).await.is_err());
+
+        client.set_verified("foo", "baz", None).await.unwrap();
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_set_encoded() {
+        use crate::testing::MockServer;
+
+        let client = MockServer::new().client().await.unwrap();
+
+        let encoded = client.encode(vec![1, 2, 3]).unwrap();
+        for key in &["a", "b"] {
+            let _ = client.set_encoded(key, &encoded, None).await.unwrap();
+        }
+
+        let value: Option<Vec<u8>> = client.get("b").await.unwrap();
+        assert_eq!(value, Some(vec![1, 2, 3]));
+
+        let raw = client.get_raw("a").await.unwrap().unwrap();
+        assert_eq!(raw.data, encoded.data());
+        assert_eq!(raw.flags, encoded.flags().bits());
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_retry() {
+        use crate::testing::MockServer;
+
+        let client = MockServer::new().client().await.unwrap();
+
+        let status = client.set_retry("a", "value", None).await.unwrap();
+        assert_eq!(status, crate::Status::Stored);
+
+        let value: Option<String> = client.get_retry("a").await.unwrap();
+        assert_eq!(value.as_deref(), Some("value"));
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_raw_round_trip() {
+        use crate::testing::MockServer;
+        use crate::StoreOpts;
+
+        let client = MockServer::new().client().await.unwrap();
+
+        // Not valid JSON nor brotli, so any encoding would change the bytes
+        let data = b"\x00\xffraw bytes".to_vec();
+        let opts = StoreOpts::new().flags(42u32);
+        let _ = client.set_raw("src", data.clone(), opts).await.unwrap();
+
+        let value = client.get_raw("src").await.unwrap().unwrap();
+        assert_eq!(value.data, data);
+        assert_eq!(value.flags, 42);
+
+        let opts = StoreOpts::new().flags(value.flags);
+        let _ = client.set_raw("dst", value.data, opts).await.unwrap();
+        let copied = client.get_raw("dst").await.unwrap().unwrap();
+        assert_eq!(copied.data, data);
+        assert_eq!(copied.flags, 42);
+
+        assert_eq!(client.get_raw("missing").await.unwrap(), None);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_empty_values() {
+        use crate::testing::MockServer;
+        use crate::StoreOpts;
+
+        let client = MockServer::new().client().await.unwrap();
+
+        // Zero-byte data blocks are present values, not misses
+        let _ = client
+            .set_raw("marker", Vec::new(), StoreOpts::new())
+            .await
+            .unwrap();
+        let value = client.get_raw("marker").await.unwrap().unwrap();
+        assert!(value.data.is_empty());
+        assert!(client.exists("marker").await.unwrap());
+
+        let mut scratch = b"stale".to_vec();
+        let result = client.get_into::<_, String>("marker", &mut scratch).await;
+        assert!(result.is_err());
+        assert!(scratch.is_empty());
+
+        let _ = client.set("string", "", None).await.unwrap();
+        let _ = client.set("bytes", Vec::<u8>::new(), None).await.unwrap();
+        assert_eq!(
+            client.get::<_, String>("string").await.unwrap().unwrap(),
+            ""
+        );
+        assert!(client
+            .get::<_, Vec<u8>>("bytes")
+            .await
+            .unwrap()
+            .unwrap()
+            .is_empty());
+    }
+
+    #[cfg(all(feature = "test-util", feature = "timings"))]
+    #[tokio::test]
+    async fn test_timed() {
+        use crate::testing::MockServer;
+
+        let client = MockServer::new().client().await.unwrap();
+
+        let (status, _) = client.set_timed("a", "value", None).await.unwrap();
+        assert_eq!(status, crate::Status::Stored);
+
+        let (value, timings) = client.get_timed::<_, String>("a").await.unwrap();
+        assert_eq!(value.as_deref(), Some("value"));
+        assert!(timings.round_trip > std::time::Duration::ZERO);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_get_full() {
+        use crate::testing::MockServer;
+        use crate::StoreOpts;
+
+        let client = MockServer::new().client().await.unwrap();
+
+        let opts = StoreOpts::new().flags(1u32 << 8);
+        let _ = client.set_with_opts("a", "value", opts).await.unwrap();
+
+        let (value, flags, cas) = client.get_full::<_, String>("a").await.unwrap().unwrap();
+        assert_eq!(value, "value");
+        assert_eq!(flags & (1 << 8), 1 << 8);
+        assert!(cas.is_some());
+
+        assert_eq!(client.get_full::<_, String>("missing").await.unwrap(), None);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_reserved_flags() {
+        use crate::testing::MockServer;
+        use crate::{MetaSetOpts, StoreOpts};
+
+        let client = MockServer::new().client().await.unwrap();
+
+        // Bit 1 would mark the plain JSON value as compressed
+        let opts = StoreOpts::new().flags(42u32);
+        match client.set_with_opts("a", "value", opts).await {
+            Err(MemcacheError::ClientError(ClientError::ReservedFlags(42))) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        let opts = MetaSetOpts::new().flags(1u32);
+        match client.meta_set("a", "value", opts).await {
+            Err(MemcacheError::ClientError(ClientError::ReservedFlags(1))) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        assert_eq!(client.get::<_, String>("a").await.unwrap(), None);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_get_reader() {
+        use crate::testing::MockServer;
+        use crate::{Client, ConnectionManager, Expiration, Pool, Settings};
+        use tokio::io::AsyncReadExt;
+
+        let pool = Pool::builder()
+            .max_size(1)
+            .build(ConnectionManager::mock(MockServer::new()).unwrap())
+            .await
+            .unwrap();
+        let client = Client::with_pool(pool, Settings::new().buffer_size(8));
+
+        let value = vec![b'x'; 1000];
+        let _ = client
+            .set_reader("foo", &value[..], value.len(), Expiration::Never)
+            .await
+            .unwrap();
+
+        assert!(client.get_reader("missing").await.unwrap().is_none());
+
+        let mut reader = client.get_reader("foo").await.unwrap().unwrap();
+        assert_eq!(reader.length(), 1000);
+
+        let mut read = Vec::new();
+        let _ = reader.read_to_end(&mut read).await.unwrap();
+        assert_eq!(read, value);
+        drop(reader);
+
+        // Connection is back in sync after the reader is done, or replaced if it is dropped early
+        let mut reader = client.get_reader("foo").await.unwrap().unwrap();
+        let _ = reader.read_u8().await.unwrap();
+        drop(reader);
+
+        let reader = client.get_reader("foo").await.unwrap().unwrap();
+        assert_eq!(reader.length(), 1000);
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_increment_with_default() {
+        use crate::testing::MockServer;
+        use crate::Expiration;
+
+        let client = MockServer::new().client().await.unwrap();
+
+        assert_eq!(client.increment("counter", 1).await.unwrap(), None);
+        assert_eq!(client.counter_value("counter").await.unwrap(), None);
+
+        let value = client
+            .increment_with_default("counter", 1, 10, Expiration::Never)
+            .await
+            .unwrap();
+        assert_eq!(value, 10);
+
+        let value = client
+            .increment_with_default("counter", 5, 10, Expiration::Never)
+            .await
+            .unwrap();
+        assert_eq!(value, 15);
+        assert_eq!(client.increment("counter", 0).await.unwrap(), Some(15));
+        assert_eq!(client.counter_value("counter").await.unwrap(), Some(15));
+
+        assert_eq!(client.decrement("counter", 20).await.unwrap(), Some(0));
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_try_lock_unlock() {
+        use crate::testing::MockServer;
+        use std::time::Duration;
+
+        let client = MockServer::new().client().await.unwrap();
+        let ttl = Duration::from_secs(10);
+
+        assert!(client.try_lock("lock", "a", ttl).await.unwrap());
+        assert!(!client.try_lock("lock", "b", ttl).await.unwrap());
+
+        // Only the owner releases the lock
+        assert!(!client.unlock("lock", "b").await.unwrap());
+        assert!(client.unlock("lock", "a").await.unwrap());
+        assert!(!client.unlock("lock", "a").await.unwrap());
+
+        assert!(client.try_lock("lock", "b", ttl).await.unwrap());
+    }
+
+    #[cfg(feature = "test-util")]
+    #[tokio::test]
+    async fn test_gets_empty_keys() {
+        use crate::testing::MockServer;
+        use crate::Expiration;
+
+        let client = MockServer::new().client().await.unwrap();
+        let _ = client.set("foo", "bar", Expiration::Never).await.unwrap();
+
+        let keys: [&str; 0] = [];
+        assert_eq!(client.gets::<_, String>(&keys).await.unwrap(), None);
+        assert_eq!(
+            client.get::<_, String>("foo").await.unwrap().unwrap(),
+            "bar"
+        );
+    }
+
+    fn client_error(key: &[u8], max_length: usize, protocol: Protocol) -> Option<ClientError> {
+        match check_key_len(key, max_length, protocol) {
+            Err(MemcacheError::ClientError(e)) => Some(e),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_check_key_len() {
+        assert!(check_key_len(b"foo", 250, Protocol::Ascii).is_ok());
+        assert!(check_key_len([b'a'; 250], 250, Protocol::Ascii).is_ok());
+        assert!(check_key_len([b'a'; 251], 1024, Protocol::Ascii).is_ok());
+        assert_eq!(
+            client_error(&[b'a'; 251], 250, Protocol::Ascii),
+            Some(ClientError::KeyTooLong {
+                len: 251,
+                prefix: "a".repeat(32),
+            })
+        );
+
+        // Binary request headers hold key lengths in 16 bits, whatever the configured limit
+        let key = vec![b'a'; usize::from(u16::MAX)];
+        assert!(check_key_len(&key, usize::MAX, Protocol::Binary).is_ok());
+        let key = vec![b'a'; usize::from(u16::MAX) + 1];
+        assert!(check_key_len(&key, usize::MAX, Protocol::Ascii).is_ok());
+        assert_eq!(
+            client_error(&key, usize::MAX, Protocol::Binary),
+            Some(ClientError::KeyTooLong {
+                len: key.len(),
+                prefix: "a".repeat(32),
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_key_invalid_chars() {
+        for key in [
+            &b"foo bar"[..],
+            b"foo\r\n",
+            b"foo\tbar",
+            b"\x00",
+            b"foo\x7f",
+        ]
+        .iter()
+        {
+            assert_eq!(
+                client_error(key, 250, Protocol::Ascii),
+                Some(ClientError::InvalidKey)
+            );
+            // Binary keys are sent apart from the command, so any byte is allowed
+            assert!(check_key_len(key, 250, Protocol::Binary).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_decode_error_context() {
+        match decode::<String>(b"foo", b"\x00not json", 0) {
+            Err(MemcacheError::Decode { key, preview, .. }) => {
+                assert_eq!(key, "foo");
+                assert_eq!(preview, "\\x00not json");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
     }
 }
@@ -1,37 +1,171 @@
+use crate::Flags;
+
+/// Number of leading bytes sampled by `looks_compressible`
+const ENTROPY_SAMPLE_LENGTH: usize = 4096;
+/// Entropy in bits per byte above which data is unlikely to compress
+const MAX_COMPRESSIBLE_ENTROPY: f64 = 7.0;
+
+/// Cheap estimate of whether data is worth compressing, for `Settings::compress_filter`
+///
+/// Computes the Shannon entropy of the first 4 KiB. Random bytes and already compressed data
+/// come close to 8 bits per byte and are rejected, while JSON is usually far below.
+pub fn looks_compressible(data: &[u8]) -> bool {
+    let sample = &data[..data.len().min(ENTROPY_SAMPLE_LENGTH)];
+    if sample.is_empty() {
+        return false;
+    }
+
+    let mut counts = [0usize; 256];
+    for &byte in sample {
+        counts[byte as usize] += 1;
+    }
+
+    let length = sample.len() as f64;
+    let entropy: f64 = counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / length;
+            -p * p.log2()
+        })
+        .sum();
+
+    entropy <= MAX_COMPRESSIBLE_ENTROPY
+}
+
+/// Value serialized, and compressed with the "compress" feature, ready to be stored
+///
+/// Built by `Client::encode` and stored by `Client::set_encoded`, so that a value set to many
+/// keys is only encoded once.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EncodedValue {
+    pub(crate) data: Vec<u8>,
+    pub(crate) flags: Flags,
+}
+
+impl EncodedValue {
+    /// Encoded bytes as stored
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Flags describing the format of the encoded bytes
+    pub fn flags(&self) -> Flags {
+        self.flags
+    }
+}
+
 #[cfg(feature = "compress")]
 mod compress {
+    use super::EncodedValue;
     use crate::error::MemcacheError;
+    use crate::{Flags, Settings};
     use serde::de::DeserializeOwned;
     use serde::Serialize;
     use std::io::{Cursor, Write};
 
-    pub(crate) fn encode<T: Serialize>(value: T) -> Result<Vec<u8>, MemcacheError> {
+    /// Values are stored compressed only if that makes them smaller, brotli output of
+    /// incompressible data such as already compressed blobs is larger than its input
+    ///
+    /// Values below `Settings::compress_min_size` or rejected by `Settings::compress_filter` are
+    /// not even tried.
+    pub(crate) fn encode<T: Serialize>(
+        value: T,
+        settings: &Settings,
+    ) -> Result<EncodedValue, MemcacheError> {
         let encoded = serde_json::to_vec(&value)?;
 
+        let worth_trying = encoded.len() >= settings.compress_min_size
+            && settings
+                .compress_filter
+                .as_ref()
+                .is_none_or(|filter| filter(&encoded));
+        if !worth_trying {
+            return Ok(EncodedValue {
+                data: encoded,
+                flags: Flags::SERIALIZED,
+            });
+        }
+
+        let compressed = compress(&encoded)?;
+
+        if compressed.len() < encoded.len() {
+            Ok(EncodedValue {
+                data: compressed,
+                flags: Flags::SERIALIZED | Flags::COMPRESSED,
+            })
+        } else {
+            Ok(EncodedValue {
+                data: encoded,
+                flags: Flags::SERIALIZED,
+            })
+        }
+    }
+
+    pub(super) fn compress(input: &[u8]) -> Result<Vec<u8>, MemcacheError> {
         let mut writer = brotli::CompressorWriter::new(Vec::new(), 2048, 11, 22);
-        let _ = writer.write_all(&encoded)?;
+        writer.write_all(input)?;
+
         Ok(writer.into_inner())
     }
 
-    pub(crate) fn decode<T: DeserializeOwned>(input: Vec<u8>) -> Result<T, MemcacheError> {
+    pub(crate) fn decode<T: DeserializeOwned>(
+        input: &[u8],
+        flags: Flags,
+    ) -> Result<T, MemcacheError> {
+        if flags.is_compressed() {
+            return decompress(input);
+        }
+
+        // Values without format bits were either stored uncompressed, or compressed before
+        // flags were set, so both are tried
+        match serde_json::from_slice(input) {
+            Ok(value) => Ok(value),
+            Err(e) if !flags.is_serialized() => decompress(input).map_err(|_| e.into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn decompress<T: DeserializeOwned>(input: &[u8]) -> Result<T, MemcacheError> {
         let mut output = Vec::new();
-        let _ = brotli::BrotliDecompress(&mut Cursor::new(input), &mut output)?;
-        Ok(serde_json::from_slice(&mut output)?)
+        brotli::BrotliDecompress(&mut Cursor::new(input), &mut output)?;
+        Ok(serde_json::from_slice(&output)?)
     }
 }
 
 #[cfg(not(feature = "compress"))]
 mod plain {
+    use super::EncodedValue;
     use crate::error::MemcacheError;
+    use crate::{ErrorKind, Flags, Settings};
     use serde::de::DeserializeOwned;
     use serde::Serialize;
 
-    pub(crate) fn encode<T: Serialize>(value: T) -> Result<Vec<u8>, MemcacheError> {
-        Ok(serde_json::to_vec(&value)?)
+    pub(crate) fn encode<T: Serialize>(
+        value: T,
+        _settings: &Settings,
+    ) -> Result<EncodedValue, MemcacheError> {
+        Ok(EncodedValue {
+            data: serde_json::to_vec(&value)?,
+            flags: Flags::SERIALIZED,
+        })
     }
 
-    pub(crate) fn decode<T: DeserializeOwned>(value: Vec<u8>) -> Result<T, MemcacheError> {
-        Ok(serde_json::from_slice(&value)?)
+    /// Values marked as compressed fail, rather than being parsed as JSON, as they can only be
+    /// decoded with the "compress" feature
+    pub(crate) fn decode<T: DeserializeOwned>(
+        value: &[u8],
+        flags: Flags,
+    ) -> Result<T, MemcacheError> {
+        if flags.is_compressed() {
+            return Err(ErrorKind::Protocol(Some(format!(
+                "unsupported value format: {}",
+                flags.bits()
+            )))
+            .into());
+        }
+
+        Ok(serde_json::from_slice(value)?)
     }
 }
 
@@ -40,3 +174,97 @@ pub(crate) use compress::*;
 
 #[cfg(not(feature = "compress"))]
 pub(crate) use plain::*;
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, looks_compressible};
+    use crate::{Flags, Settings};
+
+    #[test]
+    fn test_round_trip() {
+        let encoded = encode("value", &Settings::default()).unwrap();
+        assert!(encoded.flags.is_serialized());
+
+        let decoded: String = decode(&encoded.data, encoded.flags).unwrap();
+        assert_eq!(decoded, "value");
+    }
+
+    #[test]
+    fn test_looks_compressible() {
+        assert!(looks_compressible(
+            br#"{"id":1,"name":"value","tags":["a","b"]}"#
+        ));
+        assert!(!looks_compressible(b""));
+
+        // Every byte value equally often, as in random or compressed data
+        let uniform: Vec<u8> = (0..4096).map(|i| (i % 256) as u8).collect();
+        assert!(!looks_compressible(&uniform));
+    }
+
+    #[test]
+    fn test_decode_unflagged_plain() {
+        let decoded: String = decode(br#""legacy""#, Flags::empty()).unwrap();
+        assert_eq!(decoded, "legacy");
+    }
+
+    #[cfg(not(feature = "compress"))]
+    #[test]
+    fn test_decode_unsupported_format() {
+        use crate::{ErrorKind, MemcacheError};
+
+        let flags = Flags::SERIALIZED | Flags::COMPRESSED;
+        match decode::<String>(br#""value""#, flags) {
+            Err(MemcacheError::Memcache(ErrorKind::Protocol(Some(message)))) => {
+                assert_eq!(message, "unsupported value format: 3")
+            }
+            result => panic!("unexpected result: {:?}", result),
+        }
+    }
+
+    #[cfg(feature = "compress")]
+    #[test]
+    fn test_encode_keeps_smaller() {
+        let value = "value ".repeat(100);
+        let encoded = encode(&value, &Settings::default()).unwrap();
+        assert!(encoded.flags.is_compressed());
+        assert_eq!(
+            decode::<String>(&encoded.data, encoded.flags).unwrap(),
+            value
+        );
+
+        let encoded = encode("x", &Settings::default()).unwrap();
+        assert!(!encoded.flags.is_compressed());
+        assert_eq!(encoded.data, br#""x""#);
+        assert_eq!(decode::<String>(&encoded.data, encoded.flags).unwrap(), "x");
+    }
+
+    #[cfg(feature = "compress")]
+    #[test]
+    fn test_encode_skips_compression() {
+        let value = "value ".repeat(100);
+
+        let settings = Settings::new().compress_min_size(1024);
+        assert!(!encode(&value, &settings).unwrap().flags.is_compressed());
+
+        let settings = Settings::new().compress_filter(|_: &[u8]| false);
+        assert!(!encode(&value, &settings).unwrap().flags.is_compressed());
+
+        let settings = Settings::new().compress_filter(looks_compressible);
+        assert!(encode(&value, &settings).unwrap().flags.is_compressed());
+
+        let min_length = 1024;
+        let settings = Settings::new().compress_filter(move |data| data.len() >= min_length);
+        assert!(!encode(&value, &settings).unwrap().flags.is_compressed());
+    }
+
+    #[cfg(feature = "compress")]
+    #[test]
+    fn test_decode_unflagged_compressed() {
+        let compressed = super::compress::compress(br#""legacy""#).unwrap();
+
+        let decoded: String = decode(&compressed, Flags::empty()).unwrap();
+        assert_eq!(decoded, "legacy");
+
+        assert!(decode::<String>(br#""plain""#, Flags::SERIALIZED | Flags::COMPRESSED).is_err());
+    }
+}
@@ -1,9 +1,80 @@
 use pin_project_lite::pin_project;
+use socket2::{SockRef, TcpKeepalive};
 use std::io;
+use std::net::SocketAddr;
 use std::pin::Pin;
 use std::task::{Context, Poll};
-use tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite, BufStream, ReadBuf};
-use tokio::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+use tokio::io::{
+    AsyncBufRead, AsyncRead, AsyncWrite, AsyncWriteExt, BufStream, DuplexStream, ReadBuf,
+};
+use tokio::net::{TcpSocket, TcpStream, ToSocketAddrs};
+
+use crate::driver::Protocol;
+use crate::Settings;
+
+/// Open a TCP socket with the buffer sizes of given settings and connect it
+async fn connect_socket(address: SocketAddr, settings: &Settings) -> io::Result<TcpStream> {
+    let socket = match address {
+        SocketAddr::V4(_) => TcpSocket::new_v4()?,
+        SocketAddr::V6(_) => TcpSocket::new_v6()?,
+    };
+
+    // Set before connecting, so that the TCP window scale is negotiated accordingly
+    if let Some(size) = settings.socket_recv_buffer {
+        socket.set_recv_buffer_size(size)?;
+    }
+    if let Some(size) = settings.socket_send_buffer {
+        socket.set_send_buffer_size(size)?;
+    }
+
+    socket.connect(address).await
+}
+
+/// Byte stream a connection runs over
+#[derive(Debug)]
+pub(crate) enum Transport {
+    /// Connection to a memcached server
+    Tcp(TcpStream),
+    /// In-memory connection, e.g. to a mock server
+    Memory(DuplexStream),
+}
+
+impl AsyncRead for Transport {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Tcp(stream) => Pin::new(stream).poll_read(cx, buf),
+            Transport::Memory(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Transport {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context, buf: &[u8]) -> Poll<io::Result<usize>> {
+        match self.get_mut() {
+            Transport::Tcp(stream) => Pin::new(stream).poll_write(cx, buf),
+            Transport::Memory(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Tcp(stream) => Pin::new(stream).poll_flush(cx),
+            Transport::Memory(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<io::Result<()>> {
+        match self.get_mut() {
+            Transport::Tcp(stream) => Pin::new(stream).poll_shutdown(cx),
+            Transport::Memory(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
 
 pin_project! {
     /// Connection wrapper
@@ -11,7 +82,13 @@ pin_project! {
     #[must_use = "Connection do nothing unless polled"]
     pub struct Connection {
         #[pin]
-        stream: BufStream<TcpStream>
+        stream: BufStream<Transport>,
+        protocol: Protocol,
+        poisoned: bool,
+        in_flight: bool,
+        validated_at: Option<Instant>,
+        used_at: Instant,
+        meta_support: Option<bool>,
     }
 }
 
@@ -52,9 +129,94 @@ impl AsyncBufRead for Connection {
 impl Connection {
     /// Connect to to given socket address
     pub async fn connect<A: ToSocketAddrs>(address: A) -> Result<Connection, io::Error> {
-        TcpStream::connect(address).await.map(|c| Connection {
-            stream: BufStream::new(c),
-        })
+        TcpStream::connect(address)
+            .await
+            .map(|c| Self::new(Transport::Tcp(c)))
+    }
+
+    /// Connect to the first of given socket addresses accepting a connection, giving each
+    /// address at most `timeout` to do so
+    ///
+    /// An address timing out fails with `io::ErrorKind::TimedOut` if no later address connects.
+    pub async fn connect_timeout(
+        addresses: &[SocketAddr],
+        timeout: Duration,
+    ) -> Result<Connection, io::Error> {
+        Self::connect_with_settings(addresses, &Settings::new().connect_timeout(Some(timeout)))
+            .await
+    }
+
+    /// Connect to the first of given socket addresses accepting a connection, applying the
+    /// connect timeout, socket buffer sizes and stream buffer capacity of given settings
+    pub async fn connect_with_settings(
+        addresses: &[SocketAddr],
+        settings: &Settings,
+    ) -> Result<Connection, io::Error> {
+        let mut last_error = None;
+
+        for &address in addresses {
+            let connect = connect_socket(address, settings);
+            let result = match settings.connect_timeout {
+                Some(timeout) => tokio::time::timeout(timeout, connect)
+                    .await
+                    .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into())),
+                None => connect.await,
+            };
+
+            match result {
+                Ok(stream) => {
+                    let transport = Transport::Tcp(stream);
+
+                    return Ok(match settings.stream_buffer_capacity {
+                        Some(capacity) => Self::with_stream(BufStream::with_capacity(
+                            capacity, capacity, transport,
+                        )),
+                        None => Self::new(transport),
+                    });
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "could not resolve to any address",
+            )
+        }))
+    }
+
+    /// Wrap an in-memory stream, whose other half plays the server
+    pub fn from_duplex(stream: DuplexStream) -> Connection {
+        Self::new(Transport::Memory(stream))
+    }
+
+    fn new(transport: Transport) -> Connection {
+        Self::with_stream(BufStream::new(transport))
+    }
+
+    fn with_stream(stream: BufStream<Transport>) -> Connection {
+        Connection {
+            stream,
+            protocol: Protocol::default(),
+            poisoned: false,
+            in_flight: false,
+            validated_at: None,
+            used_at: Instant::now(),
+            meta_support: None,
+        }
+    }
+
+    /// Set wire protocol used by driver functions on this connection
+    pub fn with_protocol(mut self, protocol: Protocol) -> Self {
+        self.protocol = protocol;
+
+        self
+    }
+
+    /// Get wire protocol used on this connection
+    pub fn protocol(&self) -> Protocol {
+        self.protocol
     }
 
     /// Check if connection is broken by trying to read from it
@@ -64,16 +226,220 @@ impl Connection {
     /// number of bytes read. `Ok(0)` indicates the stream's read half is closed
     /// and will no longer yield data. If the stream is not ready to read data
     /// `Err(io::ErrorKind::WouldBlock)` is returned.
+    ///
+    /// A poisoned connection is always reported as broken, as is one whose last exchange was
+    /// cancelled between sending the request and reading the whole response. In-memory
+    /// connections are otherwise never broken.
     pub fn has_broken(&self) -> bool {
-        self.stream
-            .get_ref()
-            .try_read(&mut []) // dirty way to try to read without buffer
-            .map(|value| value == 0) // 0 indicates the stream's read half is closed
-            .unwrap_or(true) // unwrap any error as true
+        if self.poisoned || self.in_flight {
+            return true;
+        }
+
+        match self.try_get_ref() {
+            Some(stream) => stream
+                .try_read(&mut []) // dirty way to try to read without buffer
+                .map(|value| value == 0) // 0 indicates the stream's read half is closed
+                .unwrap_or(true), // unwrap any error as true
+            None => false,
+        }
+    }
+
+    /// Mark connection as broken
+    ///
+    /// Used when an exchange failed midway and the connection may hold unread response bytes,
+    /// so that the pool discards it instead of handing it out again.
+    pub fn poison(&mut self) {
+        self.poisoned = true;
+    }
+
+    /// Mark start of an exchange, the connection is reported as broken until it is finished
+    pub(crate) fn begin_exchange(&mut self) {
+        self.in_flight = true;
+    }
+
+    /// Mark exchange as finished, with the whole response read
+    pub(crate) fn finish_exchange(&mut self) {
+        self.in_flight = false;
+        self.used_at = Instant::now();
+    }
+
+    /// Get time elapsed since the last finished exchange, or since connecting if there was none
+    pub fn idle_time(&self) -> Duration {
+        self.used_at.elapsed()
+    }
+
+    /// Record a successful connection check
+    pub(crate) fn mark_validated(&mut self) {
+        self.validated_at = Some(Instant::now());
+    }
+
+    /// Check if connection passed a check within given interval
+    pub(crate) fn validated_within(&self, interval: Duration) -> bool {
+        self.validated_at
+            .is_some_and(|validated_at| validated_at.elapsed() < interval)
+    }
+
+    /// Get whether the server speaks the meta protocol, `None` until probed
+    pub(crate) fn meta_support(&self) -> Option<bool> {
+        self.meta_support
+    }
+
+    /// Record whether the server speaks the meta protocol
+    pub(crate) fn set_meta_support(&mut self, supported: bool) {
+        self.meta_support = Some(supported);
+    }
+
+    /// Gracefully close connection
+    ///
+    /// Flushes any buffered data and shuts down the write half of the socket, so the server
+    /// sees a clean close instead of having to time out a half-open connection.
+    pub async fn close(mut self) -> Result<(), io::Error> {
+        self.disconnect().await
+    }
+
+    /// Shut down the underlying stream and mark connection as broken, so that a pooled
+    /// connection is discarded instead of being returned to the pool.
+    pub(crate) async fn disconnect(&mut self) -> Result<(), io::Error> {
+        self.poison();
+        self.stream.shutdown().await
+    }
+
+    /// Enable TCP keepalive, probing the connection after given idle time
+    ///
+    /// Does nothing for in-memory connections.
+    pub fn set_tcp_keepalive(&self, time: Duration) -> io::Result<()> {
+        match self.try_get_ref() {
+            Some(stream) => {
+                let keepalive = TcpKeepalive::new().with_time(time);
+
+                SockRef::from(stream).set_tcp_keepalive(&keepalive)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Get address of the server this connection is connected to
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        match self.try_get_ref() {
+            Some(stream) => stream.peer_addr(),
+            None => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "in-memory connection has no peer address",
+            )),
+        }
     }
 
     /// Get reference to Stream
+    ///
+    /// # Panics
+    ///
+    /// Panics for in-memory connections, which `Connection::try_get_ref` reports instead.
     pub fn get_ref(&self) -> &TcpStream {
-        &self.stream.get_ref()
+        self.try_get_ref()
+            .expect("in-memory connection has no TCP stream")
+    }
+
+    /// Get reference to the TCP stream, `None` for in-memory connections
+    pub fn try_get_ref(&self) -> Option<&TcpStream> {
+        match self.stream.get_ref() {
+            Transport::Tcp(stream) => Some(stream),
+            Transport::Memory(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Connection;
+    use socket2::SockRef;
+    use std::time::Duration;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_in_memory_stream() {
+        let (client, _server) = tokio::io::duplex(64);
+        let conn = Connection::from_duplex(client);
+
+        assert!(conn.try_get_ref().is_none());
+        assert!(conn.peer_addr().is_err());
+        assert!(!conn.has_broken());
+    }
+
+    #[test]
+    fn test_validated_within() {
+        let (client, _server) = tokio::io::duplex(64);
+        let mut conn = Connection::from_duplex(client);
+        assert!(!conn.validated_within(Duration::from_secs(60)));
+
+        conn.mark_validated();
+        assert!(conn.validated_within(Duration::from_secs(60)));
+        assert!(!conn.validated_within(Duration::ZERO));
+    }
+
+    #[test]
+    fn test_idle_time() {
+        let (client, _server) = tokio::io::duplex(64);
+        let mut conn = Connection::from_duplex(client);
+
+        std::thread::sleep(Duration::from_millis(10));
+        assert!(conn.idle_time() >= Duration::from_millis(10));
+
+        conn.begin_exchange();
+        conn.finish_exchange();
+        assert!(conn.idle_time() < Duration::from_millis(10));
+    }
+
+    #[tokio::test]
+    async fn test_connect_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let closed = {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            listener.local_addr().unwrap()
+        };
+        let timeout = Duration::from_secs(5);
+
+        // Refused address is skipped
+        let addresses = [closed, listener.local_addr().unwrap()];
+        assert!(Connection::connect_timeout(&addresses, timeout)
+            .await
+            .is_ok());
+
+        let error = Connection::connect_timeout(&[], timeout).await.unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn test_connect_with_settings() {
+        use crate::Settings;
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let settings = Settings::new()
+            .socket_recv_buffer(Some(64 * 1024))
+            .socket_send_buffer(Some(64 * 1024))
+            .stream_buffer_capacity(Some(64 * 1024));
+
+        let addresses = [listener.local_addr().unwrap()];
+        let conn = Connection::connect_with_settings(&addresses, &settings)
+            .await
+            .unwrap();
+
+        // Linux doubles the requested size for bookkeeping overhead
+        let socket = SockRef::from(conn.get_ref());
+        assert!(socket.recv_buffer_size().unwrap() >= 64 * 1024);
+        assert!(socket.send_buffer_size().unwrap() >= 64 * 1024);
+    }
+
+    #[tokio::test]
+    async fn test_set_tcp_keepalive() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let conn = Connection::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+
+        let socket = SockRef::from(conn.get_ref());
+        assert!(!socket.keepalive().unwrap());
+
+        conn.set_tcp_keepalive(Duration::from_secs(30)).unwrap();
+        assert!(socket.keepalive().unwrap());
     }
 }
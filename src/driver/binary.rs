@@ -0,0 +1,557 @@
+//! Memcached binary protocol
+//!
+//! Every request and response starts with a fixed 24 byte header, followed by extras, key and
+//! value. Multi-key retrieval is pipelined as quiet `getkq` requests terminated by a `noop`,
+//! so that only hits are answered.
+use bytes::{Buf, BufMut, BytesMut};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use super::{receive, send, StorageCommand};
+use crate::parser::{Response, Status, Value};
+use crate::{ClientError, Connection, ErrorKind, Expiration, MemcacheError, Settings};
+
+const HEADER_LENGTH: usize = 24;
+
+const MAGIC_REQUEST: u8 = 0x80;
+const MAGIC_RESPONSE: u8 = 0x81;
+
+const OPCODE_SET: u8 = 0x01;
+const OPCODE_ADD: u8 = 0x02;
+const OPCODE_REPLACE: u8 = 0x03;
+const OPCODE_DELETE: u8 = 0x04;
+const OPCODE_NOOP: u8 = 0x0a;
+const OPCODE_VERSION: u8 = 0x0b;
+const OPCODE_GETKQ: u8 = 0x0d;
+const OPCODE_APPEND: u8 = 0x0e;
+const OPCODE_STAT: u8 = 0x10;
+const OPCODE_PREPEND: u8 = 0x0f;
+const OPCODE_TOUCH: u8 = 0x1c;
+const OPCODE_SASL_AUTH: u8 = 0x21;
+
+const STATUS_NO_ERROR: u16 = 0x0000;
+const STATUS_KEY_NOT_FOUND: u16 = 0x0001;
+const STATUS_KEY_EXISTS: u16 = 0x0002;
+const STATUS_VALUE_TOO_LARGE: u16 = 0x0003;
+const STATUS_ITEM_NOT_STORED: u16 = 0x0005;
+const STATUS_AUTH_ERROR: u16 = 0x0020;
+const STATUS_UNKNOWN_COMMAND: u16 = 0x0081;
+const STATUS_NOT_SUPPORTED: u16 = 0x0083;
+
+const SASL_MECHANISM_PLAIN: &[u8] = b"PLAIN";
+
+/// Binary protocol response packet
+#[derive(Debug, PartialEq)]
+struct Packet {
+    opcode: u8,
+    status: u16,
+    cas: u64,
+    extras: Vec<u8>,
+    key: Vec<u8>,
+    value: Vec<u8>,
+}
+
+impl Packet {
+    /// Error for a status that no command expects
+    fn into_error(self) -> ErrorKind {
+        match self.status {
+            STATUS_UNKNOWN_COMMAND => ErrorKind::NonexistentCommand,
+            STATUS_NOT_SUPPORTED => ErrorKind::Unsupported("not supported".to_string()),
+            STATUS_AUTH_ERROR => ErrorKind::Client("authentication failure".to_string()),
+            STATUS_VALUE_TOO_LARGE => ErrorKind::Server("object too large for cache".to_string()),
+            status => ErrorKind::Server(format!(
+                "{} (status 0x{:04x})",
+                String::from_utf8_lossy(&self.value),
+                status
+            )),
+        }
+    }
+}
+
+/// Length of a key as sent in request headers, failing for keys longer than `u16::MAX`
+fn key_length(key: &[u8]) -> Result<u16, MemcacheError> {
+    u16::try_from(key.len()).map_err(|_| ClientError::key_too_long(key).into())
+}
+
+/// Write request header for given opcode and body parts
+fn put_header(
+    buffer: &mut BytesMut,
+    opcode: u8,
+    extras_length: u8,
+    key_length: u16,
+    value_length: usize,
+    cas: u64,
+) {
+    buffer.put_u8(MAGIC_REQUEST);
+    buffer.put_u8(opcode);
+    buffer.put_u16(key_length);
+    buffer.put_u8(extras_length);
+    // data type
+    buffer.put_u8(0);
+    // vbucket id
+    buffer.put_u16(0);
+    buffer.put_u32((usize::from(extras_length) + usize::from(key_length) + value_length) as u32);
+    // opaque
+    buffer.put_u32(0);
+    buffer.put_u64(cas);
+}
+
+/// Parse one complete response packet from the start of buffer, if it has fully arrived
+fn parse_packet(buffer: &mut BytesMut) -> Result<Option<Packet>, MemcacheError> {
+    if buffer.len() < HEADER_LENGTH {
+        return Ok(None);
+    }
+
+    if buffer[0] != MAGIC_RESPONSE {
+        return Err(ErrorKind::Protocol(Some(format!(
+            "invalid binary response magic 0x{:02x}",
+            buffer[0]
+        )))
+        .into());
+    }
+
+    let body_length = u32::from_be_bytes([buffer[8], buffer[9], buffer[10], buffer[11]]) as usize;
+
+    if buffer.len() < HEADER_LENGTH + body_length {
+        return Ok(None);
+    }
+
+    let mut header = buffer.split_to(HEADER_LENGTH);
+    let mut body = buffer.split_to(body_length);
+
+    header.advance(1);
+    let opcode = header.get_u8();
+    let key_length = header.get_u16() as usize;
+    let extras_length = header.get_u8() as usize;
+    // data type
+    header.advance(1);
+    let status = header.get_u16();
+    // total body length, opaque
+    header.advance(8);
+    let cas = header.get_u64();
+
+    if extras_length + key_length > body_length {
+        return Err(ErrorKind::Protocol(Some("invalid binary response length".into())).into());
+    }
+
+    let extras = body.split_to(extras_length).to_vec();
+    let key = body.split_to(key_length).to_vec();
+
+    Ok(Some(Packet {
+        opcode,
+        status,
+        cas,
+        extras,
+        key,
+        value: body.to_vec(),
+    }))
+}
+
+/// Read next response packet, waiting for more data when needed
+async fn read_packet(
+    conn: &mut Connection,
+    buffer: &mut BytesMut,
+) -> Result<Packet, MemcacheError> {
+    loop {
+        if let Some(packet) = parse_packet(buffer)? {
+            return Ok(packet);
+        }
+
+        buffer.reserve(HEADER_LENGTH);
+
+        receive(conn, buffer).await?;
+    }
+}
+
+fn exptime(expiration: Expiration) -> u32 {
+    u32::try_from(expiration.as_exptime()).unwrap_or(u32::MAX)
+}
+
+pub(super) async fn storage_exchange(
+    conn: &mut Connection,
+    command: StorageCommand,
+    key: &[u8],
+    flags: u32,
+    expiration: Expiration,
+    bytes: &[u8],
+    settings: &Settings,
+) -> Result<Response, MemcacheError> {
+    // Binary protocol has no cas opcode, a set carrying cas in header is its equivalent
+    let (opcode, cas) = match command {
+        StorageCommand::Set => (OPCODE_SET, 0),
+        StorageCommand::Add => (OPCODE_ADD, 0),
+        StorageCommand::Replace => (OPCODE_REPLACE, 0),
+        StorageCommand::Append => (OPCODE_APPEND, 0),
+        StorageCommand::Prepend => (OPCODE_PREPEND, 0),
+        StorageCommand::Cas(unique) => (OPCODE_SET, unique),
+    };
+
+    // Append and prepend carry no flags and exptime extras
+    let extras_length = match opcode {
+        OPCODE_APPEND | OPCODE_PREPEND => 0,
+        _ => 8,
+    };
+
+    let mut request = BytesMut::with_capacity(
+        HEADER_LENGTH + usize::from(extras_length) + key.len() + bytes.len(),
+    );
+    put_header(
+        &mut request,
+        opcode,
+        extras_length,
+        key_length(key)?,
+        bytes.len(),
+        cas,
+    );
+    if extras_length > 0 {
+        request.put_u32(flags);
+        request.put_u32(exptime(expiration));
+    }
+    request.put_slice(key);
+    request.put_slice(bytes);
+
+    send(conn, &request).await?;
+
+    let mut buffer = BytesMut::with_capacity(settings.buffer_size);
+    let packet = read_packet(conn, &mut buffer).await?;
+
+    // Mirror ascii replies: "add" on existing and "replace", "append" or "prepend" on missing
+    // keys are NOT_STORED, while "cas" reports EXISTS and NOT_FOUND
+    Ok(match packet.status {
+        STATUS_NO_ERROR => Response::Status(Status::Stored),
+        STATUS_KEY_EXISTS if cas != 0 => Response::Status(Status::Exists),
+        STATUS_KEY_NOT_FOUND if cas != 0 => Response::Status(Status::NotFound),
+        STATUS_KEY_EXISTS | STATUS_KEY_NOT_FOUND | STATUS_ITEM_NOT_STORED => {
+            Response::Status(Status::NotStored)
+        }
+        _ => Response::Error(packet.into_error()),
+    })
+}
+
+pub(super) async fn storage_many_exchange<K>(
+    conn: &mut Connection,
+    command: StorageCommand,
+    items: &[(K, u32, Vec<u8>)],
+    expiration: Expiration,
+    settings: &Settings,
+) -> Result<Vec<Response>, MemcacheError>
+where
+    K: AsRef<[u8]>,
+{
+    let mut responses = Vec::with_capacity(items.len());
+
+    for (key, flags, bytes) in items {
+        let response = storage_exchange(
+            conn,
+            command,
+            key.as_ref(),
+            *flags,
+            expiration,
+            bytes,
+            settings,
+        )
+        .await?;
+
+        responses.push(response);
+    }
+
+    Ok(responses)
+}
+
+pub(super) async fn retrieve_exchange<K>(
+    conn: &mut Connection,
+    keys: &[K],
+    settings: &Settings,
+) -> Result<Option<Vec<Value>>, MemcacheError>
+where
+    K: AsRef<[u8]>,
+{
+    let mut request = BytesMut::with_capacity(HEADER_LENGTH * (keys.len() + 1));
+    for key in keys {
+        let key = key.as_ref();
+        put_header(&mut request, OPCODE_GETKQ, 0, key_length(key)?, 0, 0);
+        request.put_slice(key);
+    }
+    put_header(&mut request, OPCODE_NOOP, 0, 0, 0, 0);
+
+    send(conn, &request).await?;
+
+    let mut buffer = BytesMut::with_capacity(settings.buffer_size);
+    let mut values = Vec::with_capacity(keys.len());
+
+    loop {
+        let packet = read_packet(conn, &mut buffer).await?;
+
+        match (packet.opcode, packet.status) {
+            (OPCODE_NOOP, _) => break,
+            (OPCODE_GETKQ, STATUS_NO_ERROR) => {
+                if packet.extras.len() < 4 {
+                    return Err(ErrorKind::Protocol(Some("missing flags".into())).into());
+                }
+
+                values.push(Value {
+                    flags: (&packet.extras[..4]).get_u32(),
+                    cas: Some(packet.cas),
+                    key: packet.key,
+                    data: packet.value,
+                });
+            }
+            _ => return Err(MemcacheError::Memcache(packet.into_error())),
+        }
+    }
+
+    if values.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(values))
+    }
+}
+
+pub(super) async fn delete_exchange(
+    conn: &mut Connection,
+    key: &[u8],
+    settings: &Settings,
+) -> Result<Response, MemcacheError> {
+    let mut request = BytesMut::with_capacity(HEADER_LENGTH + key.len());
+    put_header(&mut request, OPCODE_DELETE, 0, key_length(key)?, 0, 0);
+    request.put_slice(key);
+
+    send(conn, &request).await?;
+
+    let mut buffer = BytesMut::with_capacity(settings.buffer_size);
+    let packet = read_packet(conn, &mut buffer).await?;
+
+    Ok(match packet.status {
+        STATUS_NO_ERROR => Response::Status(Status::Deleted),
+        STATUS_KEY_NOT_FOUND => Response::Status(Status::NotFound),
+        _ => Response::Error(packet.into_error()),
+    })
+}
+
+pub(super) async fn delete_many_exchange<K>(
+    conn: &mut Connection,
+    keys: &[K],
+    settings: &Settings,
+) -> Result<Vec<Response>, MemcacheError>
+where
+    K: AsRef<[u8]>,
+{
+    let mut responses = Vec::with_capacity(keys.len());
+
+    for key in keys {
+        responses.push(delete_exchange(conn, key.as_ref(), settings).await?);
+    }
+
+    Ok(responses)
+}
+
+pub(super) async fn touch_exchange(
+    conn: &mut Connection,
+    key: &[u8],
+    expiration: Expiration,
+    settings: &Settings,
+) -> Result<Response, MemcacheError> {
+    let mut request = BytesMut::with_capacity(HEADER_LENGTH + 4 + key.len());
+    put_header(&mut request, OPCODE_TOUCH, 4, key_length(key)?, 0, 0);
+    request.put_u32(exptime(expiration));
+    request.put_slice(key);
+
+    send(conn, &request).await?;
+
+    let mut buffer = BytesMut::with_capacity(settings.buffer_size);
+    let packet = read_packet(conn, &mut buffer).await?;
+
+    Ok(match packet.status {
+        STATUS_NO_ERROR => Response::Status(Status::Touched),
+        STATUS_KEY_NOT_FOUND => Response::Status(Status::NotFound),
+        _ => Response::Error(packet.into_error()),
+    })
+}
+
+pub(super) async fn touch_many_exchange<K>(
+    conn: &mut Connection,
+    keys: &[K],
+    expiration: Expiration,
+    settings: &Settings,
+) -> Result<Vec<Response>, MemcacheError>
+where
+    K: AsRef<[u8]>,
+{
+    let mut responses = Vec::with_capacity(keys.len());
+
+    for key in keys {
+        responses.push(touch_exchange(conn, key.as_ref(), expiration, settings).await?);
+    }
+
+    Ok(responses)
+}
+
+pub(super) async fn version_exchange(
+    conn: &mut Connection,
+    settings: &Settings,
+) -> Result<String, MemcacheError> {
+    let mut request = BytesMut::with_capacity(HEADER_LENGTH);
+    put_header(&mut request, OPCODE_VERSION, 0, 0, 0, 0);
+
+    send(conn, &request).await?;
+
+    let mut buffer = BytesMut::with_capacity(settings.buffer_size);
+    let packet = read_packet(conn, &mut buffer).await?;
+
+    match packet.status {
+        STATUS_NO_ERROR => Ok(String::from_utf8(packet.value)?),
+        _ => Err(MemcacheError::Memcache(packet.into_error())),
+    }
+}
+
+/// Server replies with one packet per statistic, terminated by a packet with an empty key
+///
+/// Stats group argument, e.g. "slabs", is sent as key
+pub(super) async fn stats_exchange(
+    conn: &mut Connection,
+    arg: Option<&str>,
+    settings: &Settings,
+) -> Result<HashMap<String, String>, MemcacheError> {
+    let key = arg.unwrap_or_default().as_bytes();
+
+    let mut request = BytesMut::with_capacity(HEADER_LENGTH + key.len());
+    put_header(&mut request, OPCODE_STAT, 0, key_length(key)?, 0, 0);
+    request.put_slice(key);
+
+    send(conn, &request).await?;
+
+    let mut buffer = BytesMut::with_capacity(settings.buffer_size);
+    let mut stats = HashMap::new();
+
+    loop {
+        let packet = read_packet(conn, &mut buffer).await?;
+
+        if packet.status != STATUS_NO_ERROR {
+            return Err(MemcacheError::Memcache(packet.into_error()));
+        }
+
+        if packet.key.is_empty() {
+            return Ok(stats);
+        }
+
+        let _ = stats.insert(
+            String::from_utf8(packet.key)?,
+            String::from_utf8(packet.value)?,
+        );
+    }
+}
+
+/// SASL PLAIN authentication, value is `\0<username>\0<password>`
+pub(super) async fn auth_exchange(
+    conn: &mut Connection,
+    username: &str,
+    password: &str,
+    settings: &Settings,
+) -> Result<(), MemcacheError> {
+    let value_length = username.len() + password.len() + 2;
+
+    let mut request =
+        BytesMut::with_capacity(HEADER_LENGTH + SASL_MECHANISM_PLAIN.len() + value_length);
+    put_header(
+        &mut request,
+        OPCODE_SASL_AUTH,
+        0,
+        key_length(SASL_MECHANISM_PLAIN)?,
+        value_length,
+        0,
+    );
+    request.put_slice(SASL_MECHANISM_PLAIN);
+    request.put_u8(0);
+    request.put_slice(username.as_bytes());
+    request.put_u8(0);
+    request.put_slice(password.as_bytes());
+
+    send(conn, &request).await?;
+
+    let mut buffer = BytesMut::with_capacity(settings.buffer_size);
+    let packet = read_packet(conn, &mut buffer).await?;
+
+    match packet.status {
+        STATUS_NO_ERROR => Ok(()),
+        _ => Err(MemcacheError::Memcache(packet.into_error())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        key_length, parse_packet, put_header, Packet, MAGIC_RESPONSE, OPCODE_GETKQ, OPCODE_SET,
+    };
+    use crate::{ClientError, MemcacheError};
+    use bytes::{BufMut, BytesMut};
+
+    #[test]
+    fn test_put_header() {
+        let mut buffer = BytesMut::new();
+        put_header(&mut buffer, OPCODE_SET, 8, 3, 5, 42);
+
+        assert_eq!(
+            &buffer[..],
+            &[
+                0x80, 0x01, 0x00, 0x03, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x2a
+            ][..]
+        );
+    }
+
+    #[test]
+    fn test_key_length() {
+        assert_eq!(key_length(b"foo").unwrap(), 3);
+        assert_eq!(key_length(&[b'a'; 65535]).unwrap(), u16::MAX);
+        match key_length(&[b'a'; 65536]) {
+            Err(MemcacheError::ClientError(ClientError::KeyTooLong { len, .. })) => {
+                assert_eq!(len, 65536)
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_packet() {
+        let mut buffer = BytesMut::new();
+        buffer.put_u8(MAGIC_RESPONSE);
+        buffer.put_u8(OPCODE_GETKQ);
+        buffer.put_u16(3);
+        buffer.put_u8(4);
+        buffer.put_u8(0);
+        buffer.put_u16(0);
+        buffer.put_u32(4 + 3 + 11);
+        buffer.put_u32(0);
+        buffer.put_u64(15);
+        buffer.put_u32(42);
+        buffer.put_slice(b"foo");
+        buffer.put_slice(b"hello world");
+
+        // Incomplete packets wait for more data
+        for i in 0..buffer.len() {
+            let mut partial = BytesMut::from(&buffer[..i]);
+            assert_eq!(parse_packet(&mut partial).unwrap(), None);
+        }
+
+        buffer.put_slice(b"next");
+
+        assert_eq!(
+            parse_packet(&mut buffer).unwrap(),
+            Some(Packet {
+                opcode: OPCODE_GETKQ,
+                status: 0,
+                cas: 15,
+                extras: vec![0, 0, 0, 42],
+                key: b"foo".to_vec(),
+                value: b"hello world".to_vec(),
+            })
+        );
+        assert_eq!(&buffer[..], b"next");
+    }
+
+    #[test]
+    fn test_parse_packet_invalid_magic() {
+        let mut buffer = BytesMut::from(&[0u8; 24][..]);
+        assert!(parse_packet(&mut buffer).is_err());
+    }
+}
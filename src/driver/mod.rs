@@ -1,20 +1,215 @@
-use bytes::BytesMut;
+use bytes::{Buf, BufMut, BytesMut};
+use std::collections::HashMap;
+use std::fmt;
 use std::io;
-use std::time::Duration;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::ops::DerefMut;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
 
-use crate::parser::{Response, Value};
-use crate::{parser, MemcacheError, PoolConnection, Settings};
+use crate::parser::{Response, Status, Value, ValueHeader};
+use crate::{
+    parser, Connection, ErrorKind, Expiration, MemcacheError, MetaSetMode, MetaSetOpts,
+    PoolConnection, Settings, ValueReader,
+};
+
+mod binary;
 
 const EMPTY_SPACE_BYTES: &[u8] = b" ";
 const NEW_LINE_BYTES: &[u8] = b"\r\n";
 const NO_REPLY_BYTES: &[u8] = b" noreply\r\n";
 const COMMAND_DELETE: &[u8] = b"delete ";
 const COMMAND_TOUCH: &[u8] = b"touch ";
+const COMMAND_INCR: &[u8] = b"incr ";
+const COMMAND_DECR: &[u8] = b"decr ";
 const COMMAND_VERSION: &[u8] = b"version\r\n";
+const COMMAND_STATS: &[u8] = b"stats";
+const COMMAND_VERBOSITY: &[u8] = b"verbosity ";
+const COMMAND_META_GET: &[u8] = b"mg ";
+const COMMAND_META_SET: &[u8] = b"ms ";
+#[cfg(feature = "metadump")]
+const COMMAND_METADUMP: &[u8] = b"lru_crawler metadump all\r\n";
+const META_FLAG_TTL: &[u8] = b" t\r\n";
+const COMMAND_META_NOOP: &[u8] = b"mn\r\n";
+const COMMAND_CACHE_MEMLIMIT: &[u8] = b"cache_memlimit ";
+const COMMAND_FLUSH_ALL: &[u8] = b"flush_all ";
+const COMMAND_AUTH: &[u8] = b"set auth 0 -1 ";
+/// End of a data block followed by the END line closing a single value response
+const VALUE_TRAILER: &[u8] = b"\r\nEND\r\n";
+
+/// Room for command name, numeric fields and separators of a request line, on top of its keys
+const REQUEST_LINE_CAPACITY: usize = 64;
+
+/// Wire protocol spoken on a connection
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Protocol {
+    /// Memcached ascii (text) protocol
+    #[default]
+    Ascii,
+    /// Memcached binary protocol
+    Binary,
+}
+
+/// Marks the connection as broken when an exchange failed midway, so that the pool discards
+/// it instead of handing it out with unread response bytes.
+///
+/// Exchanges dropped before getting here, e.g. by a timeout, stay in flight and the connection
+/// is discarded as well.
+fn poison_on_error<T>(
+    conn: &mut Connection,
+    result: Result<T, MemcacheError>,
+) -> Result<T, MemcacheError> {
+    match result {
+        Ok(_) => conn.finish_exchange(),
+        Err(_) => conn.poison(),
+    }
+
+    result
+}
+
+/// Marks the connection as broken when a storage command was answered with "ERROR".
+///
+/// The server did not recognize the command line, so it reads the data block that follows as
+/// further commands, whose replies would be taken for those of the next exchanges.
+fn poison_on_unknown_command<'r, I>(conn: &mut Connection, responses: I)
+where
+    I: IntoIterator<Item = &'r Response>,
+{
+    let unknown = Response::Error(ErrorKind::NonexistentCommand);
+
+    if responses.into_iter().any(|response| *response == unknown) {
+        conn.poison();
+    }
+}
+
+/// Write and flush request in one go
+async fn send(conn: &mut Connection, request: &[u8]) -> Result<(), MemcacheError> {
+    conn.begin_exchange();
+
+    #[cfg(feature = "protocol-debug")]
+    log_bytes(conn, ">", request);
+
+    conn.write_all(request).await?;
+    conn.flush().await?;
+
+    Ok(())
+}
+
+/// Read available response bytes into buffer, failing if the server closed the connection
+async fn receive(conn: &mut Connection, buffer: &mut BytesMut) -> Result<(), MemcacheError> {
+    #[cfg(feature = "protocol-debug")]
+    let start = buffer.len();
+
+    if conn.read_buf(buffer).await? == 0 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    #[cfg(feature = "protocol-debug")]
+    log_bytes(conn, "<", &buffer[start..]);
+
+    Ok(())
+}
+
+/// Send requests as a pipeline, in chunks of whole commands bounded by
+/// `settings.max_pipeline_bytes`, reading the status reply of every chunk before sending the next
+async fn pipeline_exchange<I>(
+    conn: &mut Connection,
+    requests: I,
+    settings: &Settings,
+) -> Result<Vec<Response>, MemcacheError>
+where
+    I: Iterator<Item = BytesMut>,
+{
+    let mut responses = Vec::new();
+    let mut pipeline = BytesMut::new();
+    let mut pending = 0;
+
+    let mut requests = requests.peekable();
+    while let Some(request) = requests.next() {
+        pipeline.extend_from_slice(&request);
+        pending += 1;
+
+        if pipeline.len() >= settings.max_pipeline_bytes || requests.peek().is_none() {
+            send(conn, &pipeline).await?;
+            pipeline.clear();
+
+            receive_statuses(conn, pending, &mut responses, settings).await?;
+            pending = 0;
+        }
+    }
+
+    Ok(responses)
+}
+
+/// Read given number of status replies
+async fn receive_statuses(
+    conn: &mut Connection,
+    count: usize,
+    responses: &mut Vec<Response>,
+    settings: &Settings,
+) -> Result<(), MemcacheError> {
+    let mut buffer: BytesMut = BytesMut::with_capacity(settings.buffer_size);
+    let mut left = count;
+
+    while left > 0 {
+        match parser::parse_ascii_status(&buffer) {
+            Ok((rest, response)) => {
+                let n = buffer.len() - rest.len();
+                buffer.advance(n);
+
+                responses.push(response);
+                left -= 1;
+            }
+            Err(nom::Err::Incomplete(_)) => receive(conn, &mut buffer).await?,
+            Err(e) => return Err(protocol_error(e)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Log raw protocol bytes, truncated and with non-printable bytes escaped, along with the server
+/// address
+#[cfg(feature = "protocol-debug")]
+fn log_bytes(conn: &Connection, direction: &str, bytes: &[u8]) {
+    const LOG_LENGTH: usize = 256;
+
+    let logged = &bytes[..bytes.len().min(LOG_LENGTH)];
+
+    tracing::debug!(
+        target: "vmemcached::protocol",
+        peer = ?conn.peer_addr().ok(),
+        "{} {}{} ({} bytes)",
+        direction,
+        logged.escape_ascii(),
+        if logged.len() < bytes.len() { "..." } else { "" },
+        bytes.len()
+    );
+}
+
+/// Parser failures mean the server replied with something this client does not understand
+fn protocol_error<E: fmt::Debug>(error: nom::Err<E>) -> MemcacheError {
+    ErrorKind::Protocol(Some(error.to_string())).into()
+}
+
+/// Format integer field directly into request, without allocating a `String`
+fn put_integer<I: itoa::Integer>(request: &mut BytesMut, value: I) {
+    let mut buffer = itoa::Buffer::new();
+    request.put_slice(buffer.format(value).as_bytes());
+}
+
+/// Fails on connections that speak a protocol other than ascii, for commands that have no
+/// binary protocol counterpart.
+fn ascii_only(conn: &Connection) -> Result<(), MemcacheError> {
+    match conn.protocol() {
+        Protocol::Ascii => Ok(()),
+        Protocol::Binary => Err(ErrorKind::Generic(
+            "command is only supported by the ascii protocol".to_string(),
+        )
+        .into()),
+    }
+}
 
 /// Storage command
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 pub enum StorageCommand {
     /// "set" means "store this data".
     Set,
@@ -24,6 +219,19 @@ pub enum StorageCommand {
     /// "replace" means "store this data, but only if the server *does*
     /// already hold data for this key".
     Replace,
+    /// "append" means "add this data to an existing key after existing data".
+    ///
+    /// The server ignores flags and exptime, but they are still sent as required by protocol.
+    Append,
+    /// "prepend" means "add this data to an existing key before existing data".
+    ///
+    /// The server ignores flags and exptime, but they are still sent as required by protocol.
+    Prepend,
+    /// "cas" is a check and set operation which means "store this data but
+    /// only if no one else has updated since I last fetched it".
+    ///
+    /// Holds the cas unique token returned by "gets".
+    Cas(u64),
 }
 
 impl From<StorageCommand> for &'static [u8] {
@@ -32,81 +240,455 @@ impl From<StorageCommand> for &'static [u8] {
             StorageCommand::Set => b"set ",
             StorageCommand::Add => b"add ",
             StorageCommand::Replace => b"replace ",
+            StorageCommand::Append => b"append ",
+            StorageCommand::Prepend => b"prepend ",
+            StorageCommand::Cas(_) => b"cas ",
         }
     }
 }
 
 /// <command name> <key> <flags> <exptime> <bytes> [noreply]\r\n
+/// cas <key> <flags> <exptime> <bytes> <cas unique> [noreply]\r\n
 ///
 ///
 /// - "STORED\r\n", to indicate success.
 ///
 /// - "NOT_STORED\r\n" to indicate the data was not stored, but not
-/// because of an error. This normally means that the
-/// condition for an "add" or a "replace" command wasn't met.
+///   because of an error. This normally means that the
+///   condition for an "add" or a "replace" command wasn't met.
 ///
 /// - "EXISTS\r\n" to indicate that the item you are trying to store with
-/// a "cas" command has been modified since you last fetched it.
+///   a "cas" command has been modified since you last fetched it.
 ///
 /// - "NOT_FOUND\r\n" to indicate that the item you are trying to store
-/// with a "cas" command did not exist.
+///   with a "cas" command did not exist.
+///
+/// With `noreply` the server does not reply and `Status::NoReply` is returned right after
+/// sending. Binary connections ignore `noreply` and wait for the reply.
 #[allow(clippy::too_many_arguments)]
 pub async fn storage<K, E>(
-    mut conn: PoolConnection<'_>,
+    mut conn: impl DerefMut<Target = Connection>,
     command: StorageCommand,
     key: K,
     flags: u32,
     expiration: E,
-    bytes: Vec<u8>,
+    bytes: &[u8],
     noreply: bool,
     settings: &Settings,
 ) -> Result<Response, MemcacheError>
 where
     K: AsRef<[u8]>,
-    E: Into<Option<Duration>>,
+    E: Into<Expiration>,
+{
+    let result = storage_any(
+        &mut conn,
+        command,
+        key.as_ref(),
+        flags,
+        expiration.into(),
+        bytes,
+        noreply,
+        settings,
+    )
+    .await;
+
+    let result = poison_on_error(&mut conn, result);
+    poison_on_unknown_command(&mut conn, result.iter());
+
+    result
+}
+
+/// Storage followed by retrieval of the stored key, both on the same connection
+///
+/// Returns the storage reply together with the value read back, which is only retrieved if the
+/// item was stored.
+pub async fn storage_read_back<K, E>(
+    mut conn: impl DerefMut<Target = Connection>,
+    command: StorageCommand,
+    key: K,
+    flags: u32,
+    expiration: E,
+    bytes: &[u8],
+    settings: &Settings,
+) -> Result<(Response, Option<Value>), MemcacheError>
+where
+    K: AsRef<[u8]>,
+    E: Into<Expiration>,
+{
+    let key = key.as_ref();
+
+    let result = async {
+        let response = storage_any(
+            &mut conn,
+            command,
+            key,
+            flags,
+            expiration.into(),
+            bytes,
+            false,
+            settings,
+        )
+        .await?;
+
+        if response != Response::Status(Status::Stored) {
+            return Ok((response, None));
+        }
+
+        let value = retrieve_any(&mut conn, RetrievalCommand::Get, &[key], settings)
+            .await?
+            .map(|mut values| values.swap_remove(0));
+
+        Ok((response, value))
+    }
+    .await;
+
+    let result = poison_on_error(&mut conn, result);
+    poison_on_unknown_command(&mut conn, result.iter().map(|(response, _)| response));
+
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn storage_any(
+    conn: &mut Connection,
+    command: StorageCommand,
+    key: &[u8],
+    flags: u32,
+    expiration: Expiration,
+    bytes: &[u8],
+    noreply: bool,
+    settings: &Settings,
+) -> Result<Response, MemcacheError> {
+    match conn.protocol() {
+        Protocol::Ascii => {
+            storage_exchange(
+                conn, command, key, flags, expiration, bytes, noreply, settings,
+            )
+            .await
+        }
+        Protocol::Binary => {
+            binary::storage_exchange(conn, command, key, flags, expiration, bytes, settings).await
+        }
+    }
+}
+
+/// Pipelined storage of many `(key, flags, data)` items with the same command and expiration
+///
+/// Replies are returned in the order of items. Binary connections store items one at a time.
+pub async fn storage_many<K, E>(
+    mut conn: impl DerefMut<Target = Connection>,
+    command: StorageCommand,
+    items: &[(K, u32, Vec<u8>)],
+    expiration: E,
+    settings: &Settings,
+) -> Result<Vec<Response>, MemcacheError>
+where
+    K: AsRef<[u8]>,
+    E: Into<Expiration>,
 {
+    let expiration = expiration.into();
+
+    let result = match conn.protocol() {
+        Protocol::Ascii => {
+            let requests = items.iter().map(|(key, flags, bytes)| {
+                storage_request(command, key.as_ref(), *flags, expiration, bytes, false)
+            });
+
+            pipeline_exchange(&mut conn, requests, settings).await
+        }
+        Protocol::Binary => {
+            binary::storage_many_exchange(&mut conn, command, items, expiration, settings).await
+        }
+    };
+
+    let result = poison_on_error(&mut conn, result);
+    poison_on_unknown_command(&mut conn, result.iter().flatten());
+
+    result
+}
+
+pub(crate) fn storage_request(
+    command: StorageCommand,
+    key: &[u8],
+    flags: u32,
+    expiration: Expiration,
+    bytes: &[u8],
+    noreply: bool,
+) -> BytesMut {
+    let mut request = BytesMut::with_capacity(REQUEST_LINE_CAPACITY + key.len() + bytes.len());
+
+    put_storage_line(
+        &mut request,
+        command,
+        key,
+        flags,
+        expiration,
+        bytes.len(),
+        noreply,
+    );
+
+    // <data block>
+    request.put_slice(bytes);
+    request.put_slice(NEW_LINE_BYTES);
+
+    request
+}
+
+fn put_storage_line(
+    request: &mut BytesMut,
+    command: StorageCommand,
+    key: &[u8],
+    flags: u32,
+    expiration: Expiration,
+    length: usize,
+    noreply: bool,
+) {
+    let cas_unique = match command {
+        StorageCommand::Cas(unique) => Some(unique),
+        _ => None,
+    };
+
     // <command name>
-    let _ = conn.write(command.into()).await?;
+    request.put_slice(command.into());
     // <key>
-    let _ = conn.write_all(key.as_ref()).await?;
-    let _ = conn.write(EMPTY_SPACE_BYTES).await?;
+    request.put_slice(key);
+    request.put_slice(EMPTY_SPACE_BYTES);
 
     // <flags>
-    let _ = conn.write(flags.to_string().as_ref()).await?;
-    let _ = conn.write(EMPTY_SPACE_BYTES).await?;
+    put_integer(request, flags);
+    request.put_slice(EMPTY_SPACE_BYTES);
 
     // <exptime>
-    let exptime = expiration.into().map(|d| d.as_secs()).unwrap_or(0);
-    let _ = conn.write(exptime.to_string().as_ref()).await?;
-    let _ = conn.write(EMPTY_SPACE_BYTES).await?;
+    put_integer(request, expiration.as_exptime());
+    request.put_slice(EMPTY_SPACE_BYTES);
 
     // <bytes>
-    let _ = conn.write(bytes.len().to_string().as_bytes()).await?;
+    put_integer(request, length);
+
+    // <cas unique>
+    if let Some(unique) = cas_unique {
+        request.put_slice(EMPTY_SPACE_BYTES);
+        put_integer(request, unique);
+    }
 
     // [noreply]
     if noreply {
         // FYI: NO_REPLY_BYTES contains space before and new line after
-        let _ = conn.write(NO_REPLY_BYTES).await?;
+        request.put_slice(NO_REPLY_BYTES);
     } else {
-        let _ = conn.write(NEW_LINE_BYTES).await?;
+        request.put_slice(NEW_LINE_BYTES);
     }
+}
+
+/// <command name> <key> <flags> <exptime> <bytes>\r\n
+///
+///
+/// Like `storage`, but streams the data block of given length from a reader straight to the
+/// connection instead of holding it in memory. Fails with `io::ErrorKind::UnexpectedEof` if the
+/// reader ends early. Only available with the ascii protocol.
+#[allow(clippy::too_many_arguments)]
+pub async fn storage_reader<K, E, R>(
+    mut conn: impl DerefMut<Target = Connection>,
+    command: StorageCommand,
+    key: K,
+    flags: u32,
+    expiration: E,
+    reader: R,
+    length: usize,
+    settings: &Settings,
+) -> Result<Response, MemcacheError>
+where
+    K: AsRef<[u8]>,
+    E: Into<Expiration>,
+    R: AsyncRead + Unpin,
+{
+    ascii_only(&conn)?;
+
+    let result = storage_reader_exchange(
+        &mut conn,
+        command,
+        key.as_ref(),
+        flags,
+        expiration.into(),
+        reader,
+        length,
+        settings,
+    )
+    .await;
+
+    let result = poison_on_error(&mut conn, result);
+    poison_on_unknown_command(&mut conn, result.iter());
+
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn storage_reader_exchange<R>(
+    conn: &mut Connection,
+    command: StorageCommand,
+    key: &[u8],
+    flags: u32,
+    expiration: Expiration,
+    reader: R,
+    length: usize,
+    settings: &Settings,
+) -> Result<Response, MemcacheError>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut request = BytesMut::with_capacity(REQUEST_LINE_CAPACITY + key.len());
+    put_storage_line(&mut request, command, key, flags, expiration, length, false);
+
+    conn.begin_exchange();
+
+    #[cfg(feature = "protocol-debug")]
+    log_bytes(conn, ">", &request);
+
+    conn.write_all(&request).await?;
 
     // <data block>
-    let _ = conn.write_all(&bytes).await?;
-    let _ = conn.write(NEW_LINE_BYTES).await?;
+    let copied = tokio::io::copy(&mut reader.take(length as u64), conn).await?;
+    if copied < length as u64 {
+        return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    send(conn, NEW_LINE_BYTES).await?;
+
+    let mut responses = Vec::with_capacity(1);
+    receive_statuses(conn, 1, &mut responses, settings).await?;
+
+    Ok(responses.swap_remove(0))
+}
 
-    // Flush command
-    let _ = conn.flush().await?;
+#[allow(clippy::too_many_arguments)]
+async fn storage_exchange(
+    conn: &mut Connection,
+    command: StorageCommand,
+    key: &[u8],
+    flags: u32,
+    expiration: Expiration,
+    bytes: &[u8],
+    noreply: bool,
+    settings: &Settings,
+) -> Result<Response, MemcacheError> {
+    let request = storage_request(command, key, flags, expiration, bytes, noreply);
+
+    send(conn, &request).await?;
+
+    // Server sends nothing back, not even errors
+    if noreply {
+        return Ok(Response::Status(Status::NoReply));
+    }
 
     let mut buffer: BytesMut = BytesMut::with_capacity(settings.buffer_size);
 
-    if conn.read_buf(&mut buffer).await? == 0 {
-        return Err(io::ErrorKind::UnexpectedEof.into());
+    receive(conn, &mut buffer).await?;
+
+    match parser::parse_ascii_status(&buffer) {
+        Ok((_left, result)) => Ok(result),
+        Err(e) => Err(protocol_error(e)),
+    }
+}
+
+/// version\r\n
+///
+///
+/// Tells whether the server requires authentication: memcached started with ascii authentication
+/// enabled (`-Y <authfile>`) answers any other command than the authentication "set" of an
+/// unauthenticated connection with an error. Binary protocol servers answer "version" before SASL
+/// authentication too, so binary connections are never reported as requiring it.
+///
+/// - "VERSION <version>\r\n" if authentication is not required
+///
+/// - "CLIENT_ERROR unauthenticated\r\n" if it is
+pub async fn requires_auth(
+    conn: &mut Connection,
+    settings: &Settings,
+) -> Result<bool, MemcacheError> {
+    let result = match conn.protocol() {
+        Protocol::Ascii => version_exchange(conn, settings).await,
+        Protocol::Binary => binary::version_exchange(conn, settings).await,
+    };
+
+    // The error reply was read whole, so the connection can still authenticate
+    let result = match result {
+        Ok(_) => Ok(false),
+        Err(MemcacheError::Memcache(ErrorKind::Unauthenticated)) => Ok(true),
+        Err(e) => Err(e),
+    };
+
+    poison_on_error(conn, result)
+}
+
+/// set auth <flags> <exptime> <bytes>\r\n
+/// <username> <password>\r\n
+///
+///
+/// Authenticates a freshly opened connection against memcached started with ascii
+/// authentication enabled (`-Y <authfile>`).
+///
+/// A server without authentication would store the credentials as an item under key "auth", so
+/// it must only be sent once `requires_auth` reported that the server requires it. The negative
+/// exptime is ignored by an authenticating server.
+///
+/// - "STORED\r\n" to indicate success
+///
+/// - "CLIENT_ERROR authentication failure\r\n" to indicate invalid credentials
+pub async fn auth(
+    conn: &mut Connection,
+    username: &str,
+    password: &str,
+    settings: &Settings,
+) -> Result<(), MemcacheError> {
+    if conn.protocol() == Protocol::Binary {
+        let result = binary::auth_exchange(conn, username, password, settings).await;
+
+        return poison_on_error(conn, result);
+    }
+
+    let result = auth_exchange(conn, username, password, settings).await;
+
+    match poison_on_error(conn, result)? {
+        Response::Status(Status::Stored) => Ok(()),
+        Response::Error(e) => Err(e.into()),
+        response => Err(ErrorKind::Protocol(Some(format!(
+            "unexpected authentication response: {:?}",
+            response
+        )))
+        .into()),
     }
+}
+
+async fn auth_exchange(
+    conn: &mut Connection,
+    username: &str,
+    password: &str,
+    settings: &Settings,
+) -> Result<Response, MemcacheError> {
+    let credentials = format!("{} {}", username, password);
+
+    let mut request = BytesMut::with_capacity(REQUEST_LINE_CAPACITY + credentials.len());
+
+    // <command name> <key> <flags> <exptime>
+    request.put_slice(COMMAND_AUTH);
+
+    // <bytes>
+    put_integer(&mut request, credentials.len());
+    request.put_slice(NEW_LINE_BYTES);
+
+    // <data block>
+    request.put_slice(credentials.as_bytes());
+    request.put_slice(NEW_LINE_BYTES);
+
+    send(conn, &request).await?;
+
+    let mut buffer: BytesMut = BytesMut::with_capacity(settings.buffer_size);
+
+    receive(conn, &mut buffer).await?;
 
     match parser::parse_ascii_status(&buffer) {
         Ok((_left, result)) => Ok(result),
-        Err(e) => Err(MemcacheError::Nom(format!("{}", e))),
+        Err(e) => Err(protocol_error(e)),
     }
 }
 
@@ -139,8 +721,31 @@ impl From<RetrievalCommand> for &'static [u8] {
 /// VALUE <key> <flags> <bytes> [<cas unique>]\r\n
 /// <data block>\r\n
 /// "END\r\n"
+///
+/// An empty key list returns `None` without sending anything. A response holding a value for a
+/// key that was not requested, or more values for a key than it was requested, fails with
+/// `ErrorKind::Protocol` instead of letting one value silently replace another.
 pub async fn retrieve<K>(
-    mut conn: PoolConnection<'_>,
+    mut conn: impl DerefMut<Target = Connection>,
+    command: RetrievalCommand,
+    keys: &[K],
+    settings: &Settings,
+) -> Result<Option<Vec<Value>>, MemcacheError>
+where
+    K: AsRef<[u8]>,
+{
+    // A bare "get\r\n" is answered with ERROR
+    if keys.is_empty() {
+        return Ok(None);
+    }
+
+    let result = retrieve_any(&mut conn, command, keys, settings).await;
+
+    poison_on_error(&mut conn, result)
+}
+
+async fn retrieve_any<K>(
+    conn: &mut Connection,
     command: RetrievalCommand,
     keys: &[K],
     settings: &Settings,
@@ -148,26 +753,80 @@ pub async fn retrieve<K>(
 where
     K: AsRef<[u8]>,
 {
-    debug_assert!(!keys.is_empty());
+    let values = match conn.protocol() {
+        Protocol::Ascii => retrieve_exchange(conn, command, keys, settings).await?,
+        Protocol::Binary => binary::retrieve_exchange(conn, keys, settings).await?,
+    };
+
+    match values {
+        Some(values) => check_values(keys, &values).map(|_| Some(values)),
+        None => Ok(None),
+    }
+}
+
+/// Check that every value answers a requested key, at most as many times as it was requested
+fn check_values<K>(keys: &[K], values: &[Value]) -> Result<(), MemcacheError>
+where
+    K: AsRef<[u8]>,
+{
+    let mut requested: HashMap<&[u8], usize> = HashMap::with_capacity(keys.len());
+    for key in keys {
+        *requested.entry(key.as_ref()).or_default() += 1;
+    }
+
+    for value in values {
+        match requested.get_mut(value.key.as_slice()) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => {
+                return Err(ErrorKind::Protocol(Some(format!(
+                    "unexpected value for key {}",
+                    String::from_utf8_lossy(&value.key)
+                )))
+                .into())
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn retrieve_request<K>(command: RetrievalCommand, keys: &[K]) -> BytesMut
+where
+    K: AsRef<[u8]>,
+{
+    let keys_length: usize = keys.iter().map(|key| key.as_ref().len() + 1).sum();
+    let mut request = BytesMut::with_capacity(REQUEST_LINE_CAPACITY + keys_length);
+
     // <command name>
-    let _ = conn.write(command.into()).await?;
+    request.put_slice(command.into());
 
     // <key>
-    for key in &*keys {
-        let _ = conn.write(EMPTY_SPACE_BYTES).await?; // ends key without empty space
-        let _ = conn.write_all(key.as_ref()).await?;
+    for key in keys {
+        request.put_slice(EMPTY_SPACE_BYTES); // ends key without empty space
+        request.put_slice(key.as_ref());
     }
-    let _ = conn.write(NEW_LINE_BYTES).await?;
+    request.put_slice(NEW_LINE_BYTES);
+
+    request
+}
+
+async fn retrieve_exchange<K>(
+    conn: &mut Connection,
+    command: RetrievalCommand,
+    keys: &[K],
+    settings: &Settings,
+) -> Result<Option<Vec<Value>>, MemcacheError>
+where
+    K: AsRef<[u8]>,
+{
+    let request = retrieve_request(command, keys);
 
-    // Flush command
-    let _ = conn.flush().await?;
+    send(conn, &request).await?;
 
     let mut buffer: BytesMut = BytesMut::with_capacity(settings.buffer_size);
 
     loop {
-        if conn.read_buf(&mut buffer).await? == 0 {
-            return Err(io::ErrorKind::UnexpectedEof.into());
-        }
+        receive(conn, &mut buffer).await?;
 
         match parser::parse_ascii_response(&buffer) {
             Ok(Some((_n, response))) => match response {
@@ -182,23 +841,264 @@ where
                 _ => return Ok(None),
             },
             Ok(None) => {
-                buffer.reserve(1024);
+                buffer.reserve(settings.reserve_size);
                 continue;
             }
-            Err(e) => return Err(MemcacheError::Nom(format!("{}", e))),
+            Err(e) => return Err(e.into()),
         }
     }
 }
 
-/// delete <key> [noreply]\r\n
+/// get <key>\r\n
 ///
 ///
-/// - "DELETED\r\n" to indicate success
+/// Checks whether key exists, discarding the data block as it arrives instead of keeping it.
 ///
-/// - "NOT_FOUND\r\n" to indicate that the item with this key was not
-///   found.
+/// - "VALUE <key> <flags> <bytes>\r\n<data block>\r\nEND\r\n" if the key exists
+///
+/// - "END\r\n" otherwise
+pub async fn exists<K>(
+    mut conn: impl DerefMut<Target = Connection>,
+    key: K,
+    settings: &Settings,
+) -> Result<bool, MemcacheError>
+where
+    K: AsRef<[u8]>,
+{
+    let keys = &[key];
+
+    let result = match conn.protocol() {
+        Protocol::Ascii => header_exchange(&mut conn, RetrievalCommand::Get, keys, settings)
+            .await
+            .map(|header| header.is_some()),
+        Protocol::Binary => binary::retrieve_exchange(&mut conn, keys, settings)
+            .await
+            .map(|values| values.is_some()),
+    };
+
+    poison_on_error(&mut conn, result)
+}
+
+/// gets <key>\r\n
+///
+///
+/// Reads the header of a value, discarding the data block as it arrives instead of keeping it.
+/// Binary connections read the whole value and drop its data.
+///
+/// - "VALUE <key> <flags> <bytes> <cas unique>\r\n<data block>\r\nEND\r\n" if the key exists
+///
+/// - "END\r\n" otherwise
+pub async fn retrieve_header<K>(
+    mut conn: impl DerefMut<Target = Connection>,
+    key: K,
+    settings: &Settings,
+) -> Result<Option<ValueHeader>, MemcacheError>
+where
+    K: AsRef<[u8]>,
+{
+    let keys = &[key];
+
+    let result = match conn.protocol() {
+        Protocol::Ascii => header_exchange(&mut conn, RetrievalCommand::Gets, keys, settings).await,
+        Protocol::Binary => binary::retrieve_exchange(&mut conn, keys, settings)
+            .await
+            .map(|values| {
+                values.map(|mut values| {
+                    let value = values.swap_remove(0);
+
+                    ValueHeader {
+                        flags: value.flags,
+                        length: value.data.len(),
+                        cas: value.cas,
+                    }
+                })
+            }),
+    };
+
+    poison_on_error(&mut conn, result)
+}
+
+async fn header_exchange<K>(
+    conn: &mut Connection,
+    command: RetrievalCommand,
+    keys: &[K],
+    settings: &Settings,
+) -> Result<Option<ValueHeader>, MemcacheError>
+where
+    K: AsRef<[u8]>,
+{
+    let request = retrieve_request(command, keys);
+
+    send(conn, &request).await?;
+
+    let mut buffer: BytesMut = BytesMut::with_capacity(settings.buffer_size);
+
+    // Drop data block bytes as soon as they are read, then expect the closing END line
+    let header = match receive_value_header(conn, &mut buffer).await? {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+    let mut data_left = header.length;
+
+    loop {
+        let discarded = data_left.min(buffer.len());
+        buffer.advance(discarded);
+        data_left -= discarded;
+
+        if data_left == 0 && buffer.len() >= VALUE_TRAILER.len() {
+            break;
+        }
+
+        receive(conn, &mut buffer).await?;
+    }
+
+    if buffer[..VALUE_TRAILER.len()] != *VALUE_TRAILER {
+        return Err(ErrorKind::Protocol(Some("expected END after value".to_string())).into());
+    }
+
+    Ok(Some(header))
+}
+
+/// get <key>\r\n
+///
+///
+/// Reads the data block of a value into given buffer, replacing its contents, so one buffer can
+/// be reused for many values. Returns flags of the value, or `None` if the key was not found.
+/// Binary connections read the value as `retrieve` does and copy it into the buffer.
+pub async fn retrieve_into<K>(
+    mut conn: impl DerefMut<Target = Connection>,
+    key: K,
+    data: &mut Vec<u8>,
+    settings: &Settings,
+) -> Result<Option<u32>, MemcacheError>
+where
+    K: AsRef<[u8]>,
+{
+    let keys = &[key];
+
+    let result = match conn.protocol() {
+        Protocol::Ascii => retrieve_into_exchange(&mut conn, keys, data, settings).await,
+        Protocol::Binary => binary::retrieve_exchange(&mut conn, keys, settings)
+            .await
+            .map(|values| {
+                values.map(|mut values| {
+                    let value = values.swap_remove(0);
+                    data.clear();
+                    data.extend_from_slice(&value.data);
+                    value.flags
+                })
+            }),
+    };
+
+    poison_on_error(&mut conn, result)
+}
+
+async fn retrieve_into_exchange<K>(
+    conn: &mut Connection,
+    keys: &[K],
+    data: &mut Vec<u8>,
+    settings: &Settings,
+) -> Result<Option<u32>, MemcacheError>
+where
+    K: AsRef<[u8]>,
+{
+    let request = retrieve_request(RetrievalCommand::Get, keys);
+
+    send(conn, &request).await?;
+
+    let mut buffer: BytesMut = BytesMut::with_capacity(settings.buffer_size);
+
+    let ValueHeader { flags, length, .. } = match receive_value_header(conn, &mut buffer).await? {
+        Some(header) => header,
+        None => return Ok(None),
+    };
+
+    data.clear();
+    data.reserve(length);
+
+    loop {
+        let copied = (length - data.len()).min(buffer.len());
+        data.extend_from_slice(&buffer[..copied]);
+        buffer.advance(copied);
+
+        if data.len() == length && buffer.len() >= VALUE_TRAILER.len() {
+            break;
+        }
+
+        receive(conn, &mut buffer).await?;
+    }
+
+    if buffer[..VALUE_TRAILER.len()] != *VALUE_TRAILER {
+        return Err(ErrorKind::Protocol(Some("expected END after value".to_string())).into());
+    }
+
+    Ok(Some(flags))
+}
+
+/// Read the first line of a retrieval response into buffer, leaving it positioned at the data
+/// block. Returns the value header, or `None` if the response is an empty "END".
+async fn receive_value_header(
+    conn: &mut Connection,
+    buffer: &mut BytesMut,
+) -> Result<Option<ValueHeader>, MemcacheError> {
+    loop {
+        receive(conn, buffer).await?;
+
+        match parser::parse_ascii_value_header(buffer)? {
+            Some((_n, None)) => return Ok(None),
+            Some((n, Some(header))) => {
+                buffer.advance(n);
+
+                return Ok(Some(header));
+            }
+            None => continue,
+        }
+    }
+}
+
+/// get <key>\r\n
+///
+///
+/// Streams the data block of a value instead of reading it into memory. The returned reader
+/// holds the connection until it is read to the end. Only available with the ascii protocol.
+pub async fn retrieve_reader<'a, K>(
+    mut conn: PoolConnection<'a>,
+    key: K,
+    settings: &Settings,
+) -> Result<Option<ValueReader<'a>>, MemcacheError>
+where
+    K: AsRef<[u8]>,
+{
+    ascii_only(&conn)?;
+
+    let request = retrieve_request(RetrievalCommand::Get, &[key]);
+    let mut buffer: BytesMut = BytesMut::with_capacity(settings.buffer_size);
+
+    let result = match send(&mut conn, &request).await {
+        Ok(()) => receive_value_header(&mut conn, &mut buffer).await,
+        Err(e) => Err(e),
+    };
+
+    // Exchange stays in flight while the reader holds the connection
+    match poison_on_error(&mut conn, result)? {
+        Some(header) => {
+            conn.begin_exchange();
+
+            Ok(Some(ValueReader::new(conn, buffer, header.length)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// delete <key> [noreply]\r\n
+///
+///
+/// - "DELETED\r\n" to indicate success
+///
+/// - "NOT_FOUND\r\n" to indicate that the item with this key was not
+///   found.
 pub async fn delete<K>(
-    mut conn: PoolConnection<'_>,
+    mut conn: impl DerefMut<Target = Connection>,
     key: K,
     noreply: bool,
     settings: &Settings,
@@ -206,31 +1106,78 @@ pub async fn delete<K>(
 where
     K: AsRef<[u8]>,
 {
+    let result = match conn.protocol() {
+        Protocol::Ascii => delete_exchange(&mut conn, key.as_ref(), noreply, settings).await,
+        Protocol::Binary => binary::delete_exchange(&mut conn, key.as_ref(), settings).await,
+    };
+
+    poison_on_error(&mut conn, result)
+}
+
+/// Pipelined deletion of many keys
+///
+/// Replies are returned in the order of keys. Binary connections delete keys one at a time.
+pub async fn delete_many<K>(
+    mut conn: impl DerefMut<Target = Connection>,
+    keys: &[K],
+    settings: &Settings,
+) -> Result<Vec<Response>, MemcacheError>
+where
+    K: AsRef<[u8]>,
+{
+    let result = match conn.protocol() {
+        Protocol::Ascii => {
+            let requests = keys.iter().map(|key| delete_request(key.as_ref(), false));
+
+            pipeline_exchange(&mut conn, requests, settings).await
+        }
+        Protocol::Binary => binary::delete_many_exchange(&mut conn, keys, settings).await,
+    };
+
+    poison_on_error(&mut conn, result)
+}
+
+pub(crate) fn delete_request(key: &[u8], noreply: bool) -> BytesMut {
+    let mut request = BytesMut::with_capacity(REQUEST_LINE_CAPACITY + key.len());
+
     // <command name>
-    let _ = conn.write(COMMAND_DELETE).await?;
+    request.put_slice(COMMAND_DELETE);
     // <key>
-    let _ = conn.write_all(key.as_ref()).await?;
+    request.put_slice(key);
 
     // [noreply]
     if noreply {
         // FYI: NO_REPLY_BYTES contains space before and new line after
-        let _ = conn.write(NO_REPLY_BYTES).await?;
+        request.put_slice(NO_REPLY_BYTES);
     } else {
-        let _ = conn.write(NEW_LINE_BYTES).await?;
+        request.put_slice(NEW_LINE_BYTES);
     }
 
-    // Flush command
-    let _ = conn.flush().await?;
+    request
+}
 
-    let mut buffer: BytesMut = BytesMut::with_capacity(settings.buffer_size);
+async fn delete_exchange(
+    conn: &mut Connection,
+    key: &[u8],
+    noreply: bool,
+    settings: &Settings,
+) -> Result<Response, MemcacheError> {
+    let request = delete_request(key, noreply);
 
-    if conn.read_buf(&mut buffer).await? == 0 {
-        return Err(io::ErrorKind::UnexpectedEof.into());
+    send(conn, &request).await?;
+
+    // Server sends nothing back, not even errors
+    if noreply {
+        return Ok(Response::Status(Status::NoReply));
     }
 
+    let mut buffer: BytesMut = BytesMut::with_capacity(settings.buffer_size);
+
+    receive(conn, &mut buffer).await?;
+
     match parser::parse_ascii_status(&buffer) {
         Ok((_left, result)) => Ok(result),
-        Err(e) => Err(MemcacheError::Nom(format!("{}", e))),
+        Err(e) => Err(protocol_error(e)),
     }
 }
 
@@ -244,7 +1191,7 @@ where
 /// - "NOT_FOUND\r\n" to indicate that the item with this key was not
 ///   found.
 pub async fn touch<K, E>(
-    mut conn: PoolConnection<'_>,
+    mut conn: impl DerefMut<Target = Connection>,
     key: K,
     expiration: E,
     noreply: bool,
@@ -253,39 +1200,373 @@ pub async fn touch<K, E>(
 where
     K: AsRef<[u8]>,
 
-    E: Into<Option<Duration>>,
+    E: Into<Expiration>,
+{
+    let expiration = expiration.into();
+
+    let result = match conn.protocol() {
+        Protocol::Ascii => {
+            touch_exchange(&mut conn, key.as_ref(), expiration, noreply, settings).await
+        }
+        Protocol::Binary => {
+            binary::touch_exchange(&mut conn, key.as_ref(), expiration, settings).await
+        }
+    };
+
+    poison_on_error(&mut conn, result)
+}
+
+/// Pipelined touch of many keys with the same expiration
+///
+/// Replies are returned in the order of keys. Binary connections touch keys one at a time.
+pub async fn touch_many<K, E>(
+    mut conn: impl DerefMut<Target = Connection>,
+    keys: &[K],
+    expiration: E,
+    settings: &Settings,
+) -> Result<Vec<Response>, MemcacheError>
+where
+    K: AsRef<[u8]>,
+    E: Into<Expiration>,
 {
+    let expiration = expiration.into();
+
+    let result = match conn.protocol() {
+        Protocol::Ascii => {
+            let requests = keys
+                .iter()
+                .map(|key| touch_request(key.as_ref(), expiration, false));
+
+            pipeline_exchange(&mut conn, requests, settings).await
+        }
+        Protocol::Binary => {
+            binary::touch_many_exchange(&mut conn, keys, expiration, settings).await
+        }
+    };
+
+    poison_on_error(&mut conn, result)
+}
+
+pub(crate) fn touch_request(key: &[u8], expiration: Expiration, noreply: bool) -> BytesMut {
+    let mut request = BytesMut::with_capacity(REQUEST_LINE_CAPACITY + key.len());
+
     // <command name>
-    let _ = conn.write(COMMAND_TOUCH).await?;
+    request.put_slice(COMMAND_TOUCH);
     // <key>
-    let _ = conn.write_all(key.as_ref()).await?;
-    let _ = conn.write(EMPTY_SPACE_BYTES).await?;
+    request.put_slice(key);
+    request.put_slice(EMPTY_SPACE_BYTES);
 
     // <exptime>
-    let exptime = expiration.into().map(|d| d.as_secs()).unwrap_or(0);
-    let _ = conn.write(exptime.to_string().as_ref()).await?;
-    let _ = conn.write(EMPTY_SPACE_BYTES).await?;
+    put_integer(&mut request, expiration.as_exptime());
 
     // [noreply]
     if noreply {
         // FYI: NO_REPLY_BYTES contains space before and new line after
-        let _ = conn.write(NO_REPLY_BYTES).await?;
+        request.put_slice(NO_REPLY_BYTES);
     } else {
-        let _ = conn.write(NEW_LINE_BYTES).await?;
+        request.put_slice(NEW_LINE_BYTES);
     }
 
-    // Flush command
-    let _ = conn.flush().await?;
+    request
+}
 
-    let mut buffer: BytesMut = BytesMut::with_capacity(settings.buffer_size);
+async fn touch_exchange(
+    conn: &mut Connection,
+    key: &[u8],
+    expiration: Expiration,
+    noreply: bool,
+    settings: &Settings,
+) -> Result<Response, MemcacheError> {
+    let request = touch_request(key, expiration, noreply);
 
-    if conn.read_buf(&mut buffer).await? == 0 {
-        return Err(io::ErrorKind::UnexpectedEof.into());
+    send(conn, &request).await?;
+
+    // Server sends nothing back, not even errors
+    if noreply {
+        return Ok(Response::Status(Status::NoReply));
     }
 
+    let mut buffer: BytesMut = BytesMut::with_capacity(settings.buffer_size);
+
+    receive(conn, &mut buffer).await?;
+
     match parser::parse_ascii_status(&buffer) {
         Ok((_left, result)) => Ok(result),
-        Err(e) => Err(MemcacheError::Nom(format!("{}", e))),
+        Err(e) => Err(protocol_error(e)),
+    }
+}
+
+/// Arithmetic command
+#[derive(Clone, Copy, Debug)]
+pub enum ArithmeticCommand {
+    /// "incr" adds to a decimal number, wrapping around at 64 bits
+    Incr,
+    /// "decr" subtracts from a decimal number, stopping at 0
+    Decr,
+}
+
+impl From<ArithmeticCommand> for &'static [u8] {
+    fn from(c: ArithmeticCommand) -> &'static [u8] {
+        match c {
+            ArithmeticCommand::Incr => COMMAND_INCR,
+            ArithmeticCommand::Decr => COMMAND_DECR,
+        }
+    }
+}
+
+/// incr <key> <value>\r\n
+/// decr <key> <value>\r\n
+///
+///
+/// - "NOT_FOUND\r\n" to indicate the item with this value was not found, returned as `None`
+///
+/// - "<value>\r\n", where <value> is the new value of the item's data
+///
+/// The item must hold a decimal number. Only available with the ascii protocol.
+pub async fn arithmetic<K>(
+    mut conn: impl DerefMut<Target = Connection>,
+    command: ArithmeticCommand,
+    key: K,
+    amount: u64,
+    settings: &Settings,
+) -> Result<Option<u64>, MemcacheError>
+where
+    K: AsRef<[u8]>,
+{
+    ascii_only(&conn)?;
+
+    let result = arithmetic_exchange(&mut conn, command, key.as_ref(), amount, settings).await;
+
+    poison_on_error(&mut conn, result)
+}
+
+pub(crate) fn arithmetic_request(command: ArithmeticCommand, key: &[u8], amount: u64) -> BytesMut {
+    let mut request = BytesMut::with_capacity(REQUEST_LINE_CAPACITY + key.len());
+
+    // <command name>
+    request.put_slice(command.into());
+    // <key>
+    request.put_slice(key);
+    request.put_slice(EMPTY_SPACE_BYTES);
+
+    // <value>
+    put_integer(&mut request, amount);
+    request.put_slice(NEW_LINE_BYTES);
+
+    request
+}
+
+async fn arithmetic_exchange(
+    conn: &mut Connection,
+    command: ArithmeticCommand,
+    key: &[u8],
+    amount: u64,
+    settings: &Settings,
+) -> Result<Option<u64>, MemcacheError> {
+    let request = arithmetic_request(command, key, amount);
+
+    send(conn, &request).await?;
+
+    let mut buffer: BytesMut = BytesMut::with_capacity(settings.buffer_size);
+
+    loop {
+        receive(conn, &mut buffer).await?;
+
+        if let Some((_n, value)) = parser::parse_ascii_arithmetic(&buffer)? {
+            return Ok(value);
+        }
+    }
+}
+
+/// mg <key> t\r\n
+///
+///
+/// - "EN\r\n" to indicate the key was not found, returned as `None`
+///
+/// - "HD t<ttl>\r\n", where <ttl> is the number of seconds left, or -1 if the item never expires
+///
+/// Needs the meta protocol of memcached 1.6 and later. Only available with the ascii protocol.
+pub async fn ttl<K>(
+    mut conn: impl DerefMut<Target = Connection>,
+    key: K,
+    settings: &Settings,
+) -> Result<Option<i64>, MemcacheError>
+where
+    K: AsRef<[u8]>,
+{
+    ascii_only(&conn)?;
+
+    let result = ttl_exchange(&mut conn, key.as_ref(), settings).await;
+
+    poison_on_error(&mut conn, result)
+}
+
+fn ttl_request(key: &[u8]) -> BytesMut {
+    let mut request = BytesMut::with_capacity(REQUEST_LINE_CAPACITY + key.len());
+
+    // <command name>
+    request.put_slice(COMMAND_META_GET);
+    // <key>
+    request.put_slice(key);
+    // <flags>
+    request.put_slice(META_FLAG_TTL);
+
+    request
+}
+
+async fn ttl_exchange(
+    conn: &mut Connection,
+    key: &[u8],
+    settings: &Settings,
+) -> Result<Option<i64>, MemcacheError> {
+    let request = ttl_request(key);
+
+    send(conn, &request).await?;
+
+    let mut buffer: BytesMut = BytesMut::with_capacity(settings.buffer_size);
+
+    loop {
+        receive(conn, &mut buffer).await?;
+
+        if let Some((_n, ttl)) = parser::parse_ascii_meta_ttl(&buffer)? {
+            return Ok(ttl);
+        }
+    }
+}
+
+/// mn\r\n
+///
+///
+/// - "MN\r\n" if the server speaks the meta protocol
+///
+/// - "ERROR\r\n" otherwise, or "SERVER_ERROR" from proxies that do not forward meta commands
+///
+/// The answer is kept on the connection, which is only probed once. Binary connections do not
+/// take meta commands, so they are not probed at all.
+pub async fn supports_meta(
+    mut conn: impl DerefMut<Target = Connection>,
+    settings: &Settings,
+) -> Result<bool, MemcacheError> {
+    if conn.protocol() == Protocol::Binary {
+        return Ok(false);
+    }
+
+    if let Some(supported) = conn.meta_support() {
+        return Ok(supported);
+    }
+
+    let result = meta_noop_exchange(&mut conn, settings).await;
+    let supported = poison_on_error(&mut conn, result)?;
+    conn.set_meta_support(supported);
+
+    Ok(supported)
+}
+
+async fn meta_noop_exchange(
+    conn: &mut Connection,
+    settings: &Settings,
+) -> Result<bool, MemcacheError> {
+    send(conn, COMMAND_META_NOOP).await?;
+
+    let mut buffer: BytesMut = BytesMut::with_capacity(settings.buffer_size);
+
+    loop {
+        receive(conn, &mut buffer).await?;
+
+        if let Some((_n, supported)) = parser::parse_ascii_meta_noop(&buffer)? {
+            return Ok(supported);
+        }
+    }
+}
+
+/// ms <key> <datalen> c F<flags> T<ttl> M<mode>[ C<cas>][ I]\r\n
+/// <data block>\r\n
+///
+///
+/// - "HD c<cas>\r\n", to indicate success, where <cas> is the new CAS identifier of the item
+///
+/// - "NS\r\n" to indicate the data was not stored, because the mode condition was not met
+///
+/// - "EX\r\n" to indicate that the item was modified since the given CAS identifier
+///
+/// - "NF\r\n" to indicate that the item to compare the CAS identifier with was not found
+///
+/// Needs the meta protocol of memcached 1.6 and later. Only available with the ascii protocol.
+pub async fn meta_set<K>(
+    mut conn: impl DerefMut<Target = Connection>,
+    key: K,
+    opts: &MetaSetOpts,
+    bytes: &[u8],
+    settings: &Settings,
+) -> Result<(Status, Option<u64>), MemcacheError>
+where
+    K: AsRef<[u8]>,
+{
+    ascii_only(&conn)?;
+
+    let result = meta_set_exchange(&mut conn, key.as_ref(), opts, bytes, settings).await;
+
+    poison_on_error(&mut conn, result)
+}
+
+fn meta_set_request(key: &[u8], opts: &MetaSetOpts, bytes: &[u8]) -> BytesMut {
+    let mut request = BytesMut::with_capacity(REQUEST_LINE_CAPACITY + key.len() + bytes.len());
+
+    // <command name>
+    request.put_slice(COMMAND_META_SET);
+    // <key>
+    request.put_slice(key);
+    request.put_slice(EMPTY_SPACE_BYTES);
+    // <datalen>
+    put_integer(&mut request, bytes.len());
+
+    // <flags>
+    request.put_slice(b" c F");
+    put_integer(&mut request, opts.flags);
+    request.put_slice(b" T");
+    put_integer(&mut request, opts.expiration.as_exptime());
+    request.put_slice(match opts.mode {
+        MetaSetMode::Set => b" MS",
+        MetaSetMode::Add => b" ME",
+        MetaSetMode::Append => b" MA",
+        MetaSetMode::Prepend => b" MP",
+        MetaSetMode::Replace => b" MR",
+    });
+    if let Some(cas) = opts.cas {
+        request.put_slice(b" C");
+        put_integer(&mut request, cas);
+    }
+    if opts.invalidate {
+        request.put_slice(b" I");
+    }
+    request.put_slice(NEW_LINE_BYTES);
+
+    // <data block>
+    request.put_slice(bytes);
+    request.put_slice(NEW_LINE_BYTES);
+
+    request
+}
+
+async fn meta_set_exchange(
+    conn: &mut Connection,
+    key: &[u8],
+    opts: &MetaSetOpts,
+    bytes: &[u8],
+    settings: &Settings,
+) -> Result<(Status, Option<u64>), MemcacheError> {
+    let request = meta_set_request(key, opts, bytes);
+
+    send(conn, &request).await?;
+
+    let mut buffer: BytesMut = BytesMut::with_capacity(settings.buffer_size);
+
+    loop {
+        receive(conn, &mut buffer).await?;
+
+        if let Some((_n, reply)) = parser::parse_ascii_meta_set(&buffer)? {
+            return Ok(reply);
+        }
     }
 }
 
@@ -293,24 +1574,748 @@ where
 ///
 ///
 /// "VERSION <version>\r\n", where <version> is the version string for the
-pub async fn version(
-    conn: &mut PoolConnection<'_>,
+pub async fn version(conn: &mut Connection, settings: &Settings) -> Result<String, MemcacheError> {
+    let result = match conn.protocol() {
+        Protocol::Ascii => version_exchange(conn, settings).await,
+        Protocol::Binary => binary::version_exchange(conn, settings).await,
+    };
+
+    poison_on_error(conn, result)
+}
+
+async fn version_exchange(
+    conn: &mut Connection,
     settings: &Settings,
 ) -> Result<String, MemcacheError> {
     // <command name>
-    let _ = conn.write(COMMAND_VERSION).await?;
+    send(conn, COMMAND_VERSION).await?;
+
+    let mut buffer: BytesMut = BytesMut::with_capacity(settings.buffer_size);
+
+    // The version line may arrive split over several reads
+    loop {
+        match parser::parse_version(&buffer) {
+            Ok((_left, result)) => return result.map_err(MemcacheError::from),
+            Err(nom::Err::Incomplete(_)) => receive(conn, &mut buffer).await?,
+            Err(e) => return Err(protocol_error(e)),
+        }
+    }
+}
+
+/// stats [<args>]\r\n
+///
+///
+/// "STAT <name> <value>\r\n" for each statistic, followed by "END\r\n"
+pub async fn stats(
+    conn: &mut Connection,
+    arg: Option<&str>,
+    settings: &Settings,
+) -> Result<HashMap<String, String>, MemcacheError> {
+    let result = match conn.protocol() {
+        Protocol::Ascii => stats_exchange(conn, arg, settings).await,
+        Protocol::Binary => binary::stats_exchange(conn, arg, settings).await,
+    };
+
+    poison_on_error(conn, result)
+}
+
+fn stats_request(arg: Option<&str>) -> BytesMut {
+    let arg_length = arg.map(|arg| arg.len() + 1).unwrap_or(0);
+    let mut request = BytesMut::with_capacity(COMMAND_STATS.len() + arg_length + 2);
+
+    // <command name>
+    request.put_slice(COMMAND_STATS);
+
+    // [<args>]
+    if let Some(arg) = arg {
+        request.put_slice(EMPTY_SPACE_BYTES);
+        request.put_slice(arg.as_bytes());
+    }
+    request.put_slice(NEW_LINE_BYTES);
+
+    request
+}
+
+async fn stats_exchange(
+    conn: &mut Connection,
+    arg: Option<&str>,
+    settings: &Settings,
+) -> Result<HashMap<String, String>, MemcacheError> {
+    let request = stats_request(arg);
 
-    // Flush command
-    let _ = conn.flush().await?;
+    send(conn, &request).await?;
 
     let mut buffer: BytesMut = BytesMut::with_capacity(settings.buffer_size);
 
-    if conn.read_buf(&mut buffer).await? == 0 {
-        return Err(io::ErrorKind::UnexpectedEof.into());
+    loop {
+        receive(conn, &mut buffer).await?;
+
+        if parser::is_raw_response_complete(&buffer) {
+            return Ok(parser::parse_ascii_stats(&buffer)?);
+        }
+
+        buffer.reserve(settings.reserve_size);
     }
+}
 
-    match parser::parse_version(&buffer) {
-        Ok((_left, result)) => Ok(result),
-        Err(e) => Err(MemcacheError::Nom(format!("{}", e))),
+/// verbosity <level>\r\n
+///
+///
+/// "OK\r\n" once the logging level is set. Only available with the ascii protocol.
+pub async fn verbosity(
+    conn: &mut Connection,
+    level: u32,
+    settings: &Settings,
+) -> Result<(), MemcacheError> {
+    ascii_only(conn)?;
+
+    let request = admin_request(COMMAND_VERBOSITY, level);
+    let result = ok_exchange(conn, &request, settings).await;
+
+    poison_on_error(conn, result)
+}
+
+/// cache_memlimit <megabytes>\r\n
+///
+///
+/// "OK\r\n" once the memory limit is set. Only available with the ascii protocol.
+pub async fn cache_memlimit(
+    conn: &mut Connection,
+    megabytes: u32,
+    settings: &Settings,
+) -> Result<(), MemcacheError> {
+    ascii_only(conn)?;
+
+    let request = admin_request(COMMAND_CACHE_MEMLIMIT, megabytes);
+    let result = ok_exchange(conn, &request, settings).await;
+
+    poison_on_error(conn, result)
+}
+
+/// flush_all <delay>\r\n
+///
+///
+/// "OK\r\n" once all items are set to expire in <delay> seconds, right away if 0. Only available
+/// with the ascii protocol.
+pub async fn flush_all(
+    conn: &mut Connection,
+    delay: u32,
+    settings: &Settings,
+) -> Result<(), MemcacheError> {
+    ascii_only(conn)?;
+
+    let request = admin_request(COMMAND_FLUSH_ALL, delay);
+    let result = ok_exchange(conn, &request, settings).await;
+
+    poison_on_error(conn, result)
+}
+
+fn admin_request(command: &[u8], value: u32) -> BytesMut {
+    let mut request = BytesMut::with_capacity(REQUEST_LINE_CAPACITY);
+
+    // <command name>
+    request.put_slice(command);
+
+    // <value>
+    put_integer(&mut request, value);
+    request.put_slice(NEW_LINE_BYTES);
+
+    request
+}
+
+async fn ok_exchange(
+    conn: &mut Connection,
+    request: &[u8],
+    settings: &Settings,
+) -> Result<(), MemcacheError> {
+    send(conn, request).await?;
+
+    let mut buffer: BytesMut = BytesMut::with_capacity(settings.buffer_size);
+
+    loop {
+        receive(conn, &mut buffer).await?;
+
+        if parser::parse_ascii_ok(&buffer)?.is_some() {
+            return Ok(());
+        }
+    }
+}
+
+/// lru_crawler metadump all\r\n
+///
+///
+/// "key=<key> exp=<exptime> la=<last access> cas=<cas> fetch=<yes|no> cls=<class> size=<size>\n"
+/// for each item, followed by "END\r\n"
+///
+/// Items are streamed as lines arrive, reading at most `settings.buffer_size` bytes at a time.
+/// Dropping the stream early discards the connection. Only available with the ascii protocol.
+#[cfg(feature = "metadump")]
+pub fn metadump<'a>(
+    conn: PoolConnection<'a>,
+    settings: &Settings,
+) -> impl futures_util::Stream<Item = Result<crate::KeyMeta, MemcacheError>> + 'a {
+    let buffer = BytesMut::with_capacity(settings.buffer_size);
+
+    futures_util::stream::try_unfold(
+        (conn, buffer, false),
+        |(mut conn, mut buffer, sent)| async move {
+            if !sent {
+                ascii_only(&conn)?;
+            }
+
+            let result = metadump_next(&mut conn, &mut buffer, sent).await;
+
+            match result {
+                Ok(Some(meta)) => Ok(Some((meta, (conn, buffer, true)))),
+                result => poison_on_error(&mut conn, result).map(|_| None),
+            }
+        },
+    )
+}
+
+/// Read next item of a metadump, sending the command first if not `sent` yet
+#[cfg(feature = "metadump")]
+async fn metadump_next(
+    conn: &mut Connection,
+    buffer: &mut BytesMut,
+    sent: bool,
+) -> Result<Option<crate::KeyMeta>, MemcacheError> {
+    if !sent {
+        send(conn, COMMAND_METADUMP).await?;
+    }
+
+    loop {
+        if let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+            let line = buffer.split_to(end + 1);
+
+            return Ok(crate::metadump::parse_metadump_line(&line)?);
+        }
+
+        receive(conn, buffer).await?;
+    }
+}
+
+/// <command line>\r\n
+///
+///
+/// Sends a raw, unvalidated command line and reads the response until a line that is
+/// "END", "OK", "ERROR", "CLIENT_ERROR ..." or "SERVER_ERROR ...". Returns raw response bytes.
+pub async fn command(
+    conn: &mut Connection,
+    line: &str,
+    settings: &Settings,
+) -> Result<Vec<u8>, MemcacheError> {
+    ascii_only(conn)?;
+
+    let result = command_exchange(conn, line, settings).await;
+
+    poison_on_error(conn, result)
+}
+
+async fn command_exchange(
+    conn: &mut Connection,
+    line: &str,
+    settings: &Settings,
+) -> Result<Vec<u8>, MemcacheError> {
+    let mut request = BytesMut::with_capacity(line.len() + NEW_LINE_BYTES.len());
+
+    // <command line>
+    request.put_slice(line.as_bytes());
+    request.put_slice(NEW_LINE_BYTES);
+
+    send(conn, &request).await?;
+
+    let mut buffer: BytesMut = BytesMut::with_capacity(settings.buffer_size);
+
+    loop {
+        receive(conn, &mut buffer).await?;
+
+        if parser::is_raw_response_complete(&buffer) {
+            return Ok(buffer.to_vec());
+        }
+
+        buffer.reserve(settings.reserve_size);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        arithmetic_request, check_values, delete_request, header_exchange, meta_set_request,
+        pipeline_exchange, poison_on_error, retrieve_request, send, stats_exchange, stats_request,
+        storage_exchange, storage_reader_exchange, storage_request, supports_meta, touch_request,
+        ttl_request, version_exchange, ArithmeticCommand, RetrievalCommand, StorageCommand,
+        COMMAND_VERSION,
+    };
+    use crate::parser::{Response, Status, Value};
+    use crate::{
+        Connection, ErrorKind, Expiration, MemcacheError, MetaSetMode, MetaSetOpts, Settings,
+    };
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Run storage command against a local socket, returning bytes received by the server
+    async fn storage_roundtrip(command: StorageCommand, key: &[u8], bytes: &[u8]) -> Vec<u8> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut received = Vec::new();
+
+            // Wait for both command line and data block
+            while received.windows(2).filter(|w| w == b"\r\n").count() < 2 {
+                let mut chunk = [0u8; 256];
+                let n = socket.read(&mut chunk).await.unwrap();
+                received.extend_from_slice(&chunk[..n]);
+            }
+
+            socket.write_all(b"STORED\r\n").await.unwrap();
+            received
+        });
+
+        let mut conn = Connection::connect(address).await.unwrap();
+        let response = storage_exchange(
+            &mut conn,
+            command,
+            key,
+            0,
+            Expiration::Never,
+            bytes,
+            false,
+            &Settings::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response, Response::Status(Status::Stored));
+
+        server.await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_storage_reader() {
+        let (client, mut server) = tokio::io::duplex(1024);
+
+        let server = tokio::spawn(async move {
+            let mut received = Vec::new();
+
+            while received.windows(2).filter(|w| w == b"\r\n").count() < 2 {
+                let mut chunk = [0u8; 256];
+                let n = server.read(&mut chunk).await.unwrap();
+                received.extend_from_slice(&chunk[..n]);
+            }
+
+            server.write_all(b"STORED\r\n").await.unwrap();
+            received
+        });
+
+        let mut conn = Connection::from_duplex(client);
+        let response = storage_reader_exchange(
+            &mut conn,
+            StorageCommand::Set,
+            b"foo",
+            0,
+            Expiration::Never,
+            &b"hello world, and more"[..],
+            11,
+            &Settings::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response, Response::Status(Status::Stored));
+        assert_eq!(server.await.unwrap(), b"set foo 0 0 11\r\nhello world\r\n");
+
+        // Reader shorter than announced length
+        let (client, _server) = tokio::io::duplex(1024);
+        let mut conn = Connection::from_duplex(client);
+        let result = storage_reader_exchange(
+            &mut conn,
+            StorageCommand::Set,
+            b"foo",
+            0,
+            Expiration::Never,
+            &b"short"[..],
+            11,
+            &Settings::default(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(MemcacheError::Io(_))));
+    }
+
+    /// Run exists against a local socket replying with given chunks, one write per chunk
+    async fn exists_roundtrip(chunks: &'static [&'static [u8]]) -> bool {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+
+            let mut request = [0u8; 256];
+            let n = socket.read(&mut request).await.unwrap();
+            assert_eq!(&request[..n], b"get foo\r\n");
+
+            for chunk in chunks {
+                socket.write_all(chunk).await.unwrap();
+                socket.flush().await.unwrap();
+                tokio::task::yield_now().await;
+            }
+        });
+
+        let mut conn = Connection::connect(address).await.unwrap();
+        let header = header_exchange(
+            &mut conn,
+            RetrievalCommand::Get,
+            &["foo"],
+            &Settings::default(),
+        )
+        .await
+        .unwrap();
+
+        server.await.unwrap();
+        header.is_some()
+    }
+
+    #[tokio::test]
+    async fn test_exists() {
+        assert!(!exists_roundtrip(&[b"END\r\n"]).await);
+        assert!(exists_roundtrip(&[b"VALUE foo 0 11\r\nhello world\r\nEND\r\n"]).await);
+        assert!(
+            exists_roundtrip(&[b"VALUE foo 0 11\r", b"\nhello", b" world\r\nEN", b"D\r\n"]).await
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pipeline_flushes_whole_commands() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut received = Vec::new();
+            let mut replied = 0;
+
+            // Reply to every command as soon as its line arrives
+            while replied < 3 {
+                let mut chunk = [0u8; 256];
+                let n = socket.read(&mut chunk).await.unwrap();
+                received.extend_from_slice(&chunk[..n]);
+
+                let lines = received.windows(2).filter(|w| w == b"\r\n").count();
+                for _ in replied..lines {
+                    socket.write_all(b"DELETED\r\n").await.unwrap();
+                }
+                replied = lines;
+            }
+
+            received
+        });
+
+        let mut conn = Connection::connect(address).await.unwrap();
+        let requests = ["foo", "bar", "baz"]
+            .iter()
+            .map(|key| delete_request(key.as_bytes(), false));
+        let settings = Settings::default().max_pipeline_bytes(1);
+
+        let responses = pipeline_exchange(&mut conn, requests, &settings)
+            .await
+            .unwrap();
+
+        assert_eq!(responses, vec![Response::Status(Status::Deleted); 3]);
+        assert_eq!(
+            server.await.unwrap(),
+            b"delete foo\r\ndelete bar\r\ndelete baz\r\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_storage_noreply() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        // Server never replies, so waiting for a reply would hang
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut received = Vec::new();
+            let _ = socket.read_to_end(&mut received).await.unwrap();
+            received
+        });
+
+        let mut conn = Connection::connect(address).await.unwrap();
+        let response = storage_exchange(
+            &mut conn,
+            StorageCommand::Set,
+            b"foo",
+            42,
+            Expiration::Never,
+            b"bar",
+            true,
+            &Settings::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response, Response::Status(Status::NoReply));
+
+        drop(conn);
+        assert_eq!(server.await.unwrap(), b"set foo 42 0 3 noreply\r\nbar\r\n");
+    }
+
+    #[tokio::test]
+    async fn test_supports_meta_probes_once() {
+        let (client, mut server) = tokio::io::duplex(1024);
+
+        let server = tokio::spawn(async move {
+            let mut request = [0u8; 256];
+            let n = server.read(&mut request).await.unwrap();
+
+            server.write_all(b"ERROR\r\n").await.unwrap();
+            (server, request[..n].to_vec())
+        });
+
+        let mut conn = Connection::from_duplex(client);
+        let settings = Settings::default();
+        assert!(!supports_meta(&mut conn, &settings).await.unwrap());
+        let (_server, request) = server.await.unwrap();
+        assert_eq!(request, b"mn\r\n");
+
+        // Answered from the connection, the server would not reply again
+        assert!(!supports_meta(&mut conn, &settings).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_unparsable_reply_is_protocol_error() {
+        let (client, mut server) = tokio::io::duplex(1024);
+
+        let server = tokio::spawn(async move {
+            let mut request = [0u8; 256];
+            let _ = server.read(&mut request).await.unwrap();
+
+            server.write_all(b"GARBAGE\r\n").await.unwrap();
+            server
+        });
+
+        let mut conn = Connection::from_duplex(client);
+        let result = storage_exchange(
+            &mut conn,
+            StorageCommand::Set,
+            b"foo",
+            0,
+            Expiration::Never,
+            b"bar",
+            false,
+            &Settings::default(),
+        )
+        .await;
+        drop(server.await.unwrap());
+
+        match result {
+            Err(MemcacheError::Memcache(ErrorKind::Protocol(Some(_)))) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stats_accumulates_until_end() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+
+            let mut request = [0u8; 256];
+            let n = socket.read(&mut request).await.unwrap();
+            assert_eq!(&request[..n], b"stats\r\n");
+
+            for chunk in &[
+                &b"STAT pid 1\r\nSTAT upt"[..],
+                b"ime 60\r\n",
+                b"STAT curr_items 3\r\nEN",
+                b"D\r\n",
+            ] {
+                socket.write_all(chunk).await.unwrap();
+                socket.flush().await.unwrap();
+                tokio::task::yield_now().await;
+            }
+        });
+
+        let mut conn = Connection::connect(address).await.unwrap();
+        let stats = stats_exchange(&mut conn, None, &Settings::default())
+            .await
+            .unwrap();
+        server.await.unwrap();
+
+        assert_eq!(stats.len(), 3);
+        assert_eq!(stats["pid"], "1");
+        assert_eq!(stats["uptime"], "60");
+        assert_eq!(stats["curr_items"], "3");
+    }
+
+    #[test]
+    fn test_check_values() {
+        let value = |key: &[u8]| Value {
+            key: key.to_vec(),
+            cas: None,
+            flags: 0,
+            data: Vec::new(),
+        };
+
+        assert!(check_values(&["a", "b"], &[value(b"b"), value(b"a")]).is_ok());
+        assert!(check_values(&["a", "a"], &[value(b"a"), value(b"a")]).is_ok());
+
+        for values in &[vec![value(b"a"), value(b"a")], vec![value(b"c")]] {
+            match check_values(&["a", "b"], values) {
+                Err(MemcacheError::Memcache(ErrorKind::Protocol(_))) => {}
+                other => panic!("unexpected result: {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unfinished_exchange_breaks_connection() {
+        let (client, _server) = tokio::io::duplex(1024);
+        let mut conn = Connection::from_duplex(client);
+        assert!(!conn.has_broken());
+
+        // A request sent without reading the reply, as when its future is dropped
+        send(&mut conn, COMMAND_VERSION).await.unwrap();
+        assert!(conn.has_broken());
+
+        poison_on_error(&mut conn, Ok(())).unwrap();
+        assert!(!conn.has_broken());
+    }
+
+    #[tokio::test]
+    async fn test_version_split_reply() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+
+            let mut request = [0u8; 256];
+            let n = socket.read(&mut request).await.unwrap();
+            assert_eq!(&request[..n], b"version\r\n");
+
+            for chunk in &[&b"VERSION 1.6"[..], b".21\r\n"] {
+                socket.write_all(chunk).await.unwrap();
+                socket.flush().await.unwrap();
+                tokio::task::yield_now().await;
+            }
+        });
+
+        let mut conn = Connection::connect(address).await.unwrap();
+        let version = version_exchange(&mut conn, &Settings::default())
+            .await
+            .unwrap();
+        server.await.unwrap();
+
+        assert_eq!(version, "1.6.21");
+    }
+
+    #[tokio::test]
+    async fn test_storage_append_prepend() {
+        assert_eq!(
+            storage_roundtrip(StorageCommand::Append, b"foo", b"bar").await,
+            b"append foo 0 0 3\r\nbar\r\n"
+        );
+        assert_eq!(
+            storage_roundtrip(StorageCommand::Prepend, b"foo", b"bar").await,
+            b"prepend foo 0 0 3\r\nbar\r\n"
+        );
+    }
+
+    #[test]
+    fn test_storage_request() {
+        let request = storage_request(
+            StorageCommand::Set,
+            b"foo",
+            42,
+            Duration::from_secs(100).into(),
+            b"hello world",
+            false,
+        );
+        assert_eq!(&request[..], b"set foo 42 100 11\r\nhello world\r\n");
+
+        let request = storage_request(StorageCommand::Add, b"foo", 0, Expiration::Never, b"", true);
+        assert_eq!(&request[..], b"add foo 0 0 0 noreply\r\n\r\n");
+
+        let request = storage_request(
+            StorageCommand::Cas(15),
+            b"foo",
+            0,
+            Expiration::Never,
+            b"bar",
+            false,
+        );
+        assert_eq!(&request[..], b"cas foo 0 0 3 15\r\nbar\r\n");
+    }
+
+    #[test]
+    fn test_retrieve_request() {
+        let request = retrieve_request(RetrievalCommand::Get, &["foo"]);
+        assert_eq!(&request[..], b"get foo\r\n");
+
+        let request = retrieve_request(RetrievalCommand::Gets, &["foo", "bar"]);
+        assert_eq!(&request[..], b"gets foo bar\r\n");
+    }
+
+    #[test]
+    fn test_delete_request() {
+        assert_eq!(&delete_request(b"foo", false)[..], b"delete foo\r\n");
+        assert_eq!(&delete_request(b"foo", true)[..], b"delete foo noreply\r\n");
+    }
+
+    #[test]
+    fn test_stats_request() {
+        assert_eq!(&stats_request(None)[..], b"stats\r\n");
+        assert_eq!(&stats_request(Some("slabs"))[..], b"stats slabs\r\n");
+    }
+
+    #[test]
+    fn test_arithmetic_request() {
+        let request = arithmetic_request(ArithmeticCommand::Incr, b"foo", 1);
+        assert_eq!(&request[..], b"incr foo 1\r\n");
+
+        let request = arithmetic_request(ArithmeticCommand::Decr, b"foo", u64::MAX);
+        assert_eq!(&request[..], b"decr foo 18446744073709551615\r\n");
+    }
+
+    #[test]
+    fn test_ttl_request() {
+        assert_eq!(&ttl_request(b"foo")[..], b"mg foo t\r\n");
+    }
+
+    #[test]
+    fn test_meta_set_request() {
+        let opts = MetaSetOpts::new().flags(1u32);
+        assert_eq!(
+            &meta_set_request(b"foo", &opts, b"bar")[..],
+            b"ms foo 3 c F1 T0 MS\r\nbar\r\n"
+        );
+
+        let opts = MetaSetOpts::new()
+            .mode(MetaSetMode::Add)
+            .cas(42)
+            .expiration(Duration::from_secs(10))
+            .invalidate(true);
+        assert_eq!(
+            &meta_set_request(b"foo", &opts, b"")[..],
+            b"ms foo 0 c F0 T10 ME C42 I\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_touch_request() {
+        let request = touch_request(b"foo", Duration::from_secs(10).into(), false);
+        assert_eq!(&request[..], b"touch foo 10\r\n");
+
+        let request = touch_request(b"foo", Expiration::AbsoluteUnix(1_700_000_000), false);
+        assert_eq!(&request[..], b"touch foo 1700000000\r\n");
+
+        let request = touch_request(b"foo", Expiration::Never, true);
+        assert_eq!(&request[..], b"touch foo 0 noreply\r\n");
     }
 }
@@ -0,0 +1,258 @@
+use serde::Serialize;
+
+use crate::client::{check_flags, check_key_len};
+use crate::driver::{
+    arithmetic_request, delete_request, retrieve_request, storage_request, touch_request,
+    ArithmeticCommand, Protocol, RetrievalCommand, StorageCommand,
+};
+use crate::{codec, Expiration, MemcacheError, Settings, StoreOpts};
+
+/// Builds the ascii protocol requests a `Client` would send, without opening a connection
+///
+/// Returned by `Client::dry_run`. Keys are validated and values encoded exactly as by the
+/// client methods of the same name, so key formatting, flags and expiration conversion can be
+/// checked deterministically. Relative expirations longer than 30 days depend on the clock, see
+/// `Expiration`.
+#[derive(Debug)]
+pub struct DryRun<'a>(pub(crate) &'a Settings);
+
+impl DryRun<'_> {
+    /// Request sent by `Client::get`
+    pub fn get<K: AsRef<[u8]>>(&self, key: K) -> Result<Vec<u8>, MemcacheError> {
+        check_key_len(&key, self.0.max_key_length, Protocol::Ascii)?;
+
+        let command = if self.0.always_request_cas {
+            RetrievalCommand::Gets
+        } else {
+            RetrievalCommand::Get
+        };
+
+        Ok(retrieve_request(command, &[key]).to_vec())
+    }
+
+    /// Requests sent by `Client::gets`, one after another, in batches of at most
+    /// `Settings::max_multiget_keys`
+    pub fn gets<K: AsRef<[u8]>>(&self, keys: &[K]) -> Result<Vec<u8>, MemcacheError> {
+        for key in keys {
+            check_key_len(key, self.0.max_key_length, Protocol::Ascii)?;
+        }
+
+        Ok(keys
+            .chunks(self.0.max_multiget_keys.max(1))
+            .flat_map(|chunk| retrieve_request(RetrievalCommand::Gets, chunk))
+            .collect())
+    }
+
+    /// Request sent by `Client::set`
+    pub fn set<K: AsRef<[u8]>, T: Serialize, E>(
+        &self,
+        key: K,
+        value: T,
+        expiration: E,
+    ) -> Result<Vec<u8>, MemcacheError>
+    where
+        E: Into<Expiration>,
+    {
+        let opts = StoreOpts::new()
+            .expiration(expiration)
+            .noreply(self.0.set_noreply);
+
+        self.set_with_opts(key, value, opts)
+    }
+
+    /// Request sent by `Client::set_with_opts`
+    pub fn set_with_opts<K: AsRef<[u8]>, T: Serialize>(
+        &self,
+        key: K,
+        value: T,
+        opts: StoreOpts,
+    ) -> Result<Vec<u8>, MemcacheError> {
+        self.store(StorageCommand::Set, key, value, opts)
+    }
+
+    /// Request sent by `Client::add`
+    pub fn add<K: AsRef<[u8]>, T: Serialize, E>(
+        &self,
+        key: K,
+        value: T,
+        expiration: E,
+    ) -> Result<Vec<u8>, MemcacheError>
+    where
+        E: Into<Expiration>,
+    {
+        let opts = StoreOpts::new().expiration(expiration);
+
+        self.store(StorageCommand::Add, key, value, opts)
+    }
+
+    /// Request sent by `Client::replace`
+    pub fn replace<K: AsRef<[u8]>, T: Serialize, E>(
+        &self,
+        key: K,
+        value: T,
+        expiration: E,
+    ) -> Result<Vec<u8>, MemcacheError>
+    where
+        E: Into<Expiration>,
+    {
+        let opts = StoreOpts::new().expiration(expiration);
+
+        self.store(StorageCommand::Replace, key, value, opts)
+    }
+
+    /// Request sent by `Client::cas`
+    pub fn cas<K: AsRef<[u8]>, T: Serialize, E>(
+        &self,
+        key: K,
+        value: T,
+        expiration: E,
+        cas: u64,
+    ) -> Result<Vec<u8>, MemcacheError>
+    where
+        E: Into<Expiration>,
+    {
+        let opts = StoreOpts::new().expiration(expiration);
+
+        self.store(StorageCommand::Cas(cas), key, value, opts)
+    }
+
+    /// Request sent by `Client::delete`
+    pub fn delete<K: AsRef<[u8]>>(&self, key: K) -> Result<Vec<u8>, MemcacheError> {
+        check_key_len(&key, self.0.max_key_length, Protocol::Ascii)?;
+
+        Ok(delete_request(key.as_ref(), false).to_vec())
+    }
+
+    /// Request sent by `Client::touch`
+    pub fn touch<K: AsRef<[u8]>, E>(&self, key: K, expiration: E) -> Result<Vec<u8>, MemcacheError>
+    where
+        E: Into<Expiration>,
+    {
+        check_key_len(&key, self.0.max_key_length, Protocol::Ascii)?;
+
+        Ok(touch_request(key.as_ref(), expiration.into(), false).to_vec())
+    }
+
+    /// Request sent by `Client::increment`
+    pub fn increment<K: AsRef<[u8]>>(&self, key: K, amount: u64) -> Result<Vec<u8>, MemcacheError> {
+        self.arithmetic(ArithmeticCommand::Incr, key, amount)
+    }
+
+    /// Request sent by `Client::decrement`
+    pub fn decrement<K: AsRef<[u8]>>(&self, key: K, amount: u64) -> Result<Vec<u8>, MemcacheError> {
+        self.arithmetic(ArithmeticCommand::Decr, key, amount)
+    }
+
+    fn store<K: AsRef<[u8]>, T: Serialize>(
+        &self,
+        cmd: StorageCommand,
+        key: K,
+        value: T,
+        opts: StoreOpts,
+    ) -> Result<Vec<u8>, MemcacheError> {
+        check_key_len(&key, self.0.max_key_length, Protocol::Ascii)?;
+        check_flags(opts.flags)?;
+
+        let encoded = codec::encode(value, self.0)?;
+        let flags = opts.flags | encoded.flags.bits();
+
+        let request = storage_request(
+            cmd,
+            key.as_ref(),
+            flags,
+            opts.expiration,
+            &encoded.data,
+            opts.noreply,
+        );
+
+        Ok(request.to_vec())
+    }
+
+    fn arithmetic<K: AsRef<[u8]>>(
+        &self,
+        command: ArithmeticCommand,
+        key: K,
+        amount: u64,
+    ) -> Result<Vec<u8>, MemcacheError> {
+        check_key_len(&key, self.0.max_key_length, Protocol::Ascii)?;
+
+        Ok(arithmetic_request(command, key.as_ref(), amount).to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DryRun;
+    use crate::{ClientError, MemcacheError, Settings, StoreOpts};
+    use std::time::Duration;
+
+    #[test]
+    fn test_requests() {
+        let settings = Settings::new().max_multiget_keys(2);
+        let dry_run = DryRun(&settings);
+
+        assert_eq!(dry_run.get("foo").unwrap(), b"get foo\r\n");
+        assert_eq!(
+            dry_run.gets(&["a", "b", "c"]).unwrap(),
+            b"gets a b\r\ngets c\r\n"
+        );
+        assert_eq!(
+            dry_run.set("foo", "bar", Duration::from_secs(10)).unwrap(),
+            b"set foo 1 10 5\r\n\"bar\"\r\n"
+        );
+
+        let opts = StoreOpts::new().flags(1u32 << 8).noreply(true);
+        assert_eq!(
+            dry_run.set_with_opts("foo", 42, opts).unwrap(),
+            b"set foo 257 0 2 noreply\r\n42\r\n"
+        );
+        assert_eq!(
+            dry_run.cas("foo", 42, None, 7).unwrap(),
+            b"cas foo 1 0 2 7\r\n42\r\n"
+        );
+        assert_eq!(dry_run.delete("foo").unwrap(), b"delete foo\r\n");
+        assert_eq!(
+            dry_run.touch("foo", Duration::from_millis(1500)).unwrap(),
+            b"touch foo 2\r\n"
+        );
+        assert_eq!(dry_run.increment("foo", 3).unwrap(), b"incr foo 3\r\n");
+    }
+
+    #[test]
+    fn test_set_noreply() {
+        let settings = Settings::new().set_noreply(true);
+        let dry_run = DryRun(&settings);
+
+        assert_eq!(
+            dry_run.set("foo", 42, None).unwrap(),
+            b"set foo 1 0 2 noreply\r\n42\r\n"
+        );
+        assert_eq!(
+            dry_run.add("foo", 42, None).unwrap(),
+            b"add foo 1 0 2\r\n42\r\n"
+        );
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        let settings = Settings::new();
+        let dry_run = DryRun(&settings);
+
+        match dry_run.set("foo bar", "bar", None) {
+            Err(MemcacheError::ClientError(ClientError::InvalidKey)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_reserved_flags() {
+        let settings = Settings::new();
+        let dry_run = DryRun(&settings);
+
+        let opts = StoreOpts::new().flags(1u32);
+        match dry_run.set_with_opts("foo", "bar", opts) {
+            Err(MemcacheError::ClientError(ClientError::ReservedFlags(1))) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Largest exptime memcached treats as relative, in seconds (30 days)
+pub(crate) const MAX_RELATIVE_EXPTIME: u64 = 30 * 24 * 60 * 60;
+
+/// Current unix time in seconds
+pub(crate) fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default()
+}
+
+/// Item expiration time
+///
+/// memcached interprets exptime values up to 30 days as relative to now and anything larger as
+/// an absolute unix timestamp. `Relative` durations are sent as seconds, `AbsoluteUnix` pins
+/// expiry to a wall-clock moment and `Never` keeps the item until it is evicted.
+///
+/// `Relative` durations longer than 30 days are converted to an absolute timestamp using the
+/// local clock, rather than sent as seconds that the server would take for a moment in 1970.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Expiration {
+    /// Expire after given duration
+    ///
+    /// Sub-second durations are rounded up to whole seconds, so a short expiration never turns
+    /// into `Never`. A zero duration is sent as is and, like in memcached, means never expire.
+    Relative(Duration),
+    /// Expire at given unix timestamp, in seconds
+    AbsoluteUnix(u64),
+    /// Never expire
+    #[default]
+    Never,
+}
+
+impl Expiration {
+    /// Absolute time in the past, expiring an item as soon as it is stored
+    pub(crate) const EXPIRED: Expiration = Expiration::AbsoluteUnix(MAX_RELATIVE_EXPTIME + 1);
+
+    /// Exptime field value as sent to the server
+    pub(crate) fn as_exptime(&self) -> u64 {
+        match self {
+            Expiration::Relative(duration) => {
+                let seconds = duration
+                    .as_secs()
+                    .saturating_add(u64::from(duration.subsec_nanos() > 0));
+
+                if seconds > MAX_RELATIVE_EXPTIME {
+                    unix_time().saturating_add(seconds)
+                } else {
+                    seconds
+                }
+            }
+            Expiration::AbsoluteUnix(timestamp) => *timestamp,
+            Expiration::Never => 0,
+        }
+    }
+}
+
+impl From<Duration> for Expiration {
+    fn from(duration: Duration) -> Self {
+        Expiration::Relative(duration)
+    }
+}
+
+impl From<Option<Duration>> for Expiration {
+    fn from(duration: Option<Duration>) -> Self {
+        duration.map(Expiration::Relative).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{unix_time, Expiration, MAX_RELATIVE_EXPTIME};
+    use std::time::Duration;
+
+    #[test]
+    fn test_as_exptime() {
+        assert_eq!(Expiration::from(Duration::from_secs(10)).as_exptime(), 10);
+        assert_eq!(
+            Expiration::AbsoluteUnix(1_700_000_000).as_exptime(),
+            1_700_000_000
+        );
+        assert_eq!(Expiration::Never.as_exptime(), 0);
+        assert_eq!(Expiration::from(None).as_exptime(), 0);
+    }
+
+    #[test]
+    fn test_as_exptime_rounds_up() {
+        assert_eq!(Expiration::from(Duration::from_millis(500)).as_exptime(), 1);
+        assert_eq!(
+            Expiration::from(Duration::from_millis(1001)).as_exptime(),
+            2
+        );
+        assert_eq!(Expiration::from(Duration::from_secs(0)).as_exptime(), 0);
+    }
+
+    #[test]
+    fn test_as_exptime_beyond_30_days() {
+        let thirty_days = Duration::from_secs(MAX_RELATIVE_EXPTIME);
+        assert_eq!(
+            Expiration::from(thirty_days).as_exptime(),
+            MAX_RELATIVE_EXPTIME
+        );
+
+        let sixty_days = thirty_days * 2;
+        let before = unix_time();
+        let exptime = Expiration::from(sixty_days).as_exptime();
+        assert!(exptime >= before + sixty_days.as_secs());
+        assert!(exptime <= unix_time() + sixty_days.as_secs());
+
+        assert_eq!(Expiration::from(Duration::MAX).as_exptime(), u64::MAX);
+    }
+}
@@ -0,0 +1,125 @@
+use std::ops::BitOr;
+
+/// Item flags, an opaque 32-bit value stored along with every item
+///
+/// Bit 0 marks values serialized as JSON and bit 1 values compressed with Brotli. These format
+/// bits are reserved: they are set when values are encoded, and methods encoding values reject
+/// flags setting them. The remaining bits are left to applications.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Flags(u32);
+
+impl Flags {
+    /// Value is serialized as JSON
+    pub const SERIALIZED: Flags = Flags(1 << 0);
+    /// Value is compressed with Brotli
+    pub const COMPRESSED: Flags = Flags(1 << 1);
+    /// Bits describing the value format, reserved for encoding
+    pub(crate) const FORMAT: Flags = Flags(Self::SERIALIZED.0 | Self::COMPRESSED.0);
+
+    /// Flags with no bit set
+    pub const fn empty() -> Self {
+        Flags(0)
+    }
+
+    /// Raw flags value
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Check if all bits of other flags are set
+    pub const fn contains(self, other: Flags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Check if value is marked as serialized
+    pub const fn is_serialized(self) -> bool {
+        self.contains(Flags::SERIALIZED)
+    }
+
+    /// Check if value is marked as compressed
+    pub const fn is_compressed(self) -> bool {
+        self.contains(Flags::COMPRESSED)
+    }
+
+    /// Check if bit `n` is set
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is not below 32.
+    pub fn bit(self, n: u32) -> bool {
+        self.contains(Flags(single_bit(n)))
+    }
+
+    /// Set bit `n`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is not below 32.
+    pub fn set_bit(mut self, n: u32) -> Self {
+        self.0 |= single_bit(n);
+
+        self
+    }
+
+    /// Clear bit `n`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is not below 32.
+    pub fn clear_bit(mut self, n: u32) -> Self {
+        self.0 &= !single_bit(n);
+
+        self
+    }
+}
+
+fn single_bit(n: u32) -> u32 {
+    assert!(n < u32::BITS, "flag bit {} out of range", n);
+
+    1 << n
+}
+
+impl BitOr for Flags {
+    type Output = Flags;
+
+    fn bitor(self, other: Flags) -> Flags {
+        Flags(self.0 | other.0)
+    }
+}
+
+impl From<u32> for Flags {
+    fn from(bits: u32) -> Self {
+        Flags(bits)
+    }
+}
+
+impl From<Flags> for u32 {
+    fn from(flags: Flags) -> Self {
+        flags.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Flags;
+
+    #[test]
+    fn test_flags() {
+        let flags = Flags::SERIALIZED | Flags::COMPRESSED;
+        assert!(flags.is_serialized());
+        assert!(flags.is_compressed());
+        assert_eq!(u32::from(flags), 0b11);
+
+        let flags = Flags::from(0).set_bit(8);
+        assert!(flags.bit(8));
+        assert!(!flags.is_serialized());
+        assert_eq!(flags.bits(), 256);
+        assert_eq!(flags.clear_bit(8), Flags::empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_set_bit_out_of_range() {
+        let _ = Flags::empty().set_bit(32);
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::sync::watch;
+
+use crate::codec::EncodedValue;
+
+/// Encoded value shared by a flight leader with its followers
+type Shared = Option<Arc<EncodedValue>>;
+
+/// In-flight computations of `Client::get_or_set_single_flight`, keyed by cache key
+#[derive(Debug, Default)]
+pub(crate) struct SingleFlight(Mutex<HashMap<Vec<u8>, watch::Receiver<Shared>>>);
+
+/// Role of a caller joining a flight
+pub(crate) enum Flight<'a> {
+    /// First caller for the key, computes the value
+    Leader(Leader<'a>),
+    /// Concurrent caller for the key, waits for the leader
+    Follower(Follower),
+}
+
+/// Computes the value and shares it with followers through `Leader::complete`
+///
+/// Dropping the leader without completing ends the flight, followers are then left to compute the
+/// value on their own.
+pub(crate) struct Leader<'a> {
+    flights: &'a SingleFlight,
+    key: Vec<u8>,
+    sender: watch::Sender<Shared>,
+}
+
+/// Waits for the value computed by the leader
+pub(crate) struct Follower(watch::Receiver<Shared>);
+
+impl SingleFlight {
+    /// Join flight for given key, becoming its leader if nobody is computing the value yet
+    pub(crate) fn join(&self, key: &[u8]) -> Flight<'_> {
+        let mut flights = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(receiver) = flights.get(key) {
+            return Flight::Follower(Follower(receiver.clone()));
+        }
+
+        let (sender, receiver) = watch::channel(None);
+        let _ = flights.insert(key.to_vec(), receiver);
+
+        Flight::Leader(Leader {
+            flights: self,
+            key: key.to_vec(),
+            sender,
+        })
+    }
+}
+
+impl Leader<'_> {
+    /// Share encoded value with followers and end the flight
+    pub(crate) fn complete(self, encoded: Arc<EncodedValue>) {
+        // Flight map holds a receiver until drop, so sending can not fail
+        let _ = self.sender.send(Some(encoded));
+    }
+}
+
+impl Drop for Leader<'_> {
+    fn drop(&mut self) {
+        let mut flights = self
+            .flights
+            .0
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+
+        let _ = flights.remove(&self.key);
+    }
+}
+
+impl Follower {
+    /// Wait for the encoded value, `None` if the leader failed or was cancelled
+    pub(crate) async fn wait(mut self) -> Option<Arc<EncodedValue>> {
+        // A value sent before the end of flight is seen even if the sender is already gone
+        match self.0.changed().await {
+            Ok(()) => self.0.borrow().clone(),
+            Err(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Flight, SingleFlight};
+    use crate::codec::EncodedValue;
+    use crate::Flags;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_followers_share_leader_value() {
+        let flights = SingleFlight::default();
+
+        let leader = match flights.join(b"foo") {
+            Flight::Leader(leader) => leader,
+            Flight::Follower(_) => panic!("first caller must lead"),
+        };
+        let follower = match flights.join(b"foo") {
+            Flight::Follower(follower) => follower,
+            Flight::Leader(_) => panic!("concurrent caller must follow"),
+        };
+        assert!(matches!(flights.join(b"bar"), Flight::Leader(_)));
+
+        let encoded = EncodedValue {
+            data: b"value".to_vec(),
+            flags: Flags::SERIALIZED,
+        };
+        leader.complete(Arc::new(encoded.clone()));
+
+        assert_eq!(follower.wait().await.as_deref(), Some(&encoded));
+        assert!(matches!(flights.join(b"foo"), Flight::Leader(_)));
+    }
+
+    #[tokio::test]
+    async fn test_dropped_leader_releases_followers() {
+        let flights = SingleFlight::default();
+
+        let leader = flights.join(b"foo");
+        let follower = match flights.join(b"foo") {
+            Flight::Follower(follower) => follower,
+            Flight::Leader(_) => panic!("concurrent caller must follow"),
+        };
+        drop(leader);
+
+        assert_eq!(follower.wait().await, None);
+        assert!(matches!(flights.join(b"foo"), Flight::Leader(_)));
+    }
+}
@@ -1,7 +1,6 @@
 //! Vinted Rust memcache
 #![deny(
     bad_style,
-    const_err,
     dead_code,
     deprecated,
     improper_ctypes,
@@ -12,7 +11,6 @@
     overflowing_literals,
     path_statements,
     patterns_in_fns_without_body,
-    private_in_public,
     trivial_casts,
     trivial_numeric_casts,
     unconditional_recursion,
@@ -32,24 +30,61 @@
     while_true
 )]
 
+mod batch;
+mod cas;
 mod client;
 mod codec;
 mod connection;
+mod dry_run;
 mod error;
+mod expiration;
+mod flags;
+mod flight;
 mod manager;
+#[cfg(feature = "metadump")]
+mod metadump;
+mod metrics;
+mod options;
 mod parser;
+mod pool;
+mod reader;
+mod retry;
 mod settings;
+mod single;
+mod slow_log;
+mod stats;
+#[cfg(feature = "timings")]
+mod timings;
 
 /// Driver access
 pub mod driver;
+#[cfg(feature = "test-util")]
+pub mod testing;
 
+pub use crate::batch::BatchResult;
 pub use crate::client::Client;
+pub use crate::codec::{looks_compressible, EncodedValue};
+pub use crate::dry_run::DryRun;
 pub use crate::error::{ClientError, ErrorKind, MemcacheError};
-pub use crate::manager::ConnectionManager;
-pub use crate::settings::Settings;
+pub use crate::expiration::Expiration;
+pub use crate::flags::Flags;
+pub use crate::manager::{Connect, ConnectionManager};
+#[cfg(feature = "metadump")]
+pub use crate::metadump::KeyMeta;
+#[cfg(feature = "metrics")]
+pub use crate::metrics::ClientMetrics;
+pub use crate::options::{MetaSetMode, MetaSetOpts, StoreOpts};
+pub use crate::pool::PoolConfig;
+pub use crate::reader::ValueReader;
+pub use crate::retry::RetryPolicy;
+pub use crate::settings::{CompressFilter, ResolverSettings, Settings};
+pub use crate::single::SingleClient;
+pub use crate::stats::ServerStats;
+#[cfg(feature = "timings")]
+pub use crate::timings::OpTimings;
 pub use bb8::{ErrorSink, State};
 pub use connection::Connection;
-pub use parser::Status;
+pub use parser::{Status, StatusError, Value, ValueHeader};
 
 /// R2D2 connection pool
 pub type Pool = bb8::Pool<ConnectionManager>;
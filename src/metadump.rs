@@ -0,0 +1,119 @@
+use percent_encoding::percent_decode;
+use std::str::FromStr;
+
+use crate::ErrorKind;
+
+/// Item metadata as listed by "lru_crawler metadump"
+///
+/// Fields missing from the server line are zero.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct KeyMeta {
+    /// The key, percent-decoded
+    pub key: Vec<u8>,
+    /// Unix time the item expires at, -1 if it never expires
+    pub expiration: i64,
+    /// Unix time the item was last accessed at
+    pub last_access: u64,
+    /// CAS identifier
+    pub cas: u64,
+    /// Whether the item was fetched since it was stored
+    pub fetched: bool,
+    /// Slab class the item is stored in
+    pub slab_class: u32,
+    /// Item size in bytes, including its header
+    pub size: u32,
+}
+
+fn parse<T: FromStr + Default>(value: &[u8]) -> T {
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_default()
+}
+
+fn message(line: &[u8]) -> String {
+    String::from_utf8_lossy(line).trim().to_string()
+}
+
+/// Parse one line of a metadump, `None` for the final "END"
+///
+/// Dump lines end with "\n" alone, "END" and errors with "\r\n", either is accepted.
+pub(crate) fn parse_metadump_line(line: &[u8]) -> Result<Option<KeyMeta>, ErrorKind> {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+    if line == b"END" {
+        return Ok(None);
+    }
+    if line == b"ERROR" {
+        return Err(ErrorKind::NonexistentCommand);
+    }
+    if let Some(error) = line.strip_prefix(b"CLIENT_ERROR") {
+        return Err(ErrorKind::Client(message(error)));
+    }
+    for prefix in [&b"SERVER_ERROR"[..], b"ERROR", b"BUSY"] {
+        if let Some(error) = line.strip_prefix(prefix) {
+            return Err(ErrorKind::Server(message(error)));
+        }
+    }
+
+    let mut meta = KeyMeta::default();
+    let mut key = None;
+
+    for field in line.split(|&b| b == b' ') {
+        let (name, value) = match field.iter().position(|&b| b == b'=') {
+            Some(i) => (&field[..i], &field[i + 1..]),
+            None => continue,
+        };
+
+        match name {
+            b"key" => key = Some(percent_decode(value).collect()),
+            b"exp" => meta.expiration = parse(value),
+            b"la" => meta.last_access = parse(value),
+            b"cas" => meta.cas = parse(value),
+            b"fetch" => meta.fetched = value == b"yes",
+            b"cls" => meta.slab_class = parse(value),
+            b"size" => meta.size = parse(value),
+            _ => {}
+        }
+    }
+
+    meta.key = key.ok_or_else(|| {
+        ErrorKind::Protocol(Some(format!("unexpected metadump line: {}", message(line))))
+    })?;
+
+    Ok(Some(meta))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_metadump_line, KeyMeta};
+    use crate::ErrorKind;
+
+    #[test]
+    fn test_parse_metadump_line() {
+        let line = b"key=foo%20bar exp=-1 la=1600000000 cas=7 fetch=yes cls=1 size=63\n";
+
+        assert_eq!(
+            parse_metadump_line(line),
+            Ok(Some(KeyMeta {
+                key: b"foo bar".to_vec(),
+                expiration: -1,
+                last_access: 1_600_000_000,
+                cas: 7,
+                fetched: true,
+                slab_class: 1,
+                size: 63,
+            }))
+        );
+
+        assert_eq!(parse_metadump_line(b"END\r\n"), Ok(None));
+        assert_eq!(
+            parse_metadump_line(b"BUSY currently processing crawler request\r\n"),
+            Err(ErrorKind::Server(
+                "currently processing crawler request".to_string()
+            ))
+        );
+        assert!(parse_metadump_line(b"exp=-1\n").is_err());
+    }
+}
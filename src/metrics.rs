@@ -0,0 +1,191 @@
+#[cfg(feature = "metrics")]
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::MemcacheError;
+
+/// Snapshot of operation counters of a client and its clones, returned by `Client::metrics`
+///
+/// Keys are counted one by one, so a multi-key get of 10 keys adds 10 gets. A failed operation
+/// counts as one error however many keys it was given.
+///
+/// - gets: `get`, `get_into`, `get_raw`, `get_full`, `get_header`, `get_reader`, `get_timed`,
+///   `get_with_cas`, `exists`, `gets` and `gets_bytes`
+/// - sets: `set`, `set_with_opts`, `set_raw`, `set_reader`, `set_timed`, `set_encoded`,
+///   `set_verified`, `meta_set`, `add`, `replace`, `cas`, `set_many` and `add_many`
+/// - deletes: `delete` and `delete_many`
+/// - touches: `touch` and `touch_many`
+///
+/// Methods built on these, such as `get_or_set` or `try_lock`, count the calls they make.
+/// `increment`, `decrement`, `ttl` and server commands are not counted.
+#[cfg(feature = "metrics")]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ClientMetrics {
+    /// Keys read
+    pub gets: u64,
+    /// Keys read and found
+    pub hits: u64,
+    /// Keys read and not found
+    pub misses: u64,
+    /// Keys stored, whatever the status of the store
+    pub sets: u64,
+    /// Keys deleted, whatever the status of the deletion
+    pub deletes: u64,
+    /// Keys touched, whatever the status of the touch
+    pub touches: u64,
+    /// Failed gets, sets, deletes and touches
+    pub errors: u64,
+}
+
+#[cfg(feature = "metrics")]
+impl ClientMetrics {
+    /// Share of found keys among keys read without error, `None` before any was
+    pub fn hit_ratio(&self) -> Option<f64> {
+        let lookups = self.hits + self.misses;
+
+        if lookups == 0 {
+            None
+        } else {
+            Some(self.hits as f64 / lookups as f64)
+        }
+    }
+}
+
+/// Operation counters of a client and its clones
+#[cfg(feature = "metrics")]
+#[derive(Debug, Default)]
+pub(crate) struct Counters(AtomicCounters);
+
+#[cfg(feature = "metrics")]
+#[derive(Debug, Default)]
+struct AtomicCounters {
+    gets: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    sets: AtomicU64,
+    deletes: AtomicU64,
+    touches: AtomicU64,
+    errors: AtomicU64,
+}
+
+#[cfg(feature = "metrics")]
+impl Counters {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count a read of given number of keys, `found` counting those the result holds
+    pub(crate) fn get<T, F>(&self, keys: usize, result: &Result<T, MemcacheError>, found: F)
+    where
+        F: FnOnce(&T) -> usize,
+    {
+        let counters = &self.0;
+
+        let _ = counters.gets.fetch_add(keys as u64, Ordering::Relaxed);
+        if let Ok(output) = result {
+            let found = found(output);
+            let _ = counters.hits.fetch_add(found as u64, Ordering::Relaxed);
+            let _ = counters
+                .misses
+                .fetch_add(keys.saturating_sub(found) as u64, Ordering::Relaxed);
+        } else {
+            let _ = counters.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Count a store of given number of keys
+    pub(crate) fn set<T>(&self, keys: usize, result: &Result<T, MemcacheError>) {
+        let _ = self.0.sets.fetch_add(keys as u64, Ordering::Relaxed);
+        self.error(result);
+    }
+
+    /// Count a deletion of given number of keys
+    pub(crate) fn delete<T>(&self, keys: usize, result: &Result<T, MemcacheError>) {
+        let _ = self.0.deletes.fetch_add(keys as u64, Ordering::Relaxed);
+        self.error(result);
+    }
+
+    /// Count a touch of given number of keys
+    pub(crate) fn touch<T>(&self, keys: usize, result: &Result<T, MemcacheError>) {
+        let _ = self.0.touches.fetch_add(keys as u64, Ordering::Relaxed);
+        self.error(result);
+    }
+
+    fn error<T>(&self, result: &Result<T, MemcacheError>) {
+        if result.is_err() {
+            let _ = self.0.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> ClientMetrics {
+        let counters = &self.0;
+
+        ClientMetrics {
+            gets: counters.gets.load(Ordering::Relaxed),
+            hits: counters.hits.load(Ordering::Relaxed),
+            misses: counters.misses.load(Ordering::Relaxed),
+            sets: counters.sets.load(Ordering::Relaxed),
+            deletes: counters.deletes.load(Ordering::Relaxed),
+            touches: counters.touches.load(Ordering::Relaxed),
+            errors: counters.errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Operations are only counted with the "metrics" feature
+#[cfg(not(feature = "metrics"))]
+#[derive(Debug)]
+pub(crate) struct Counters;
+
+#[cfg(not(feature = "metrics"))]
+impl Counters {
+    pub(crate) fn new() -> Self {
+        Self
+    }
+
+    pub(crate) fn get<T, F>(&self, _keys: usize, _result: &Result<T, MemcacheError>, _found: F)
+    where
+        F: FnOnce(&T) -> usize,
+    {
+    }
+
+    pub(crate) fn set<T>(&self, _keys: usize, _result: &Result<T, MemcacheError>) {}
+
+    pub(crate) fn delete<T>(&self, _keys: usize, _result: &Result<T, MemcacheError>) {}
+
+    pub(crate) fn touch<T>(&self, _keys: usize, _result: &Result<T, MemcacheError>) {}
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use super::{ClientMetrics, Counters};
+    use crate::{ClientError, MemcacheError};
+
+    #[test]
+    fn test_counters() {
+        let counters = Counters::new();
+        let failed: Result<(), MemcacheError> = Err(ClientError::InvalidKey.into());
+        assert_eq!(counters.snapshot().hit_ratio(), None);
+
+        counters.get(1, &Ok(Some(42)), |value| value.iter().count());
+        counters.get(4, &Ok(vec![1, 2]), Vec::len);
+        counters.get(2, &failed, |_| 0);
+        counters.set(3, &Ok(()));
+        counters.delete(1, &failed);
+        counters.touch(2, &Ok(()));
+
+        let metrics = counters.snapshot();
+        assert_eq!(
+            metrics,
+            ClientMetrics {
+                gets: 7,
+                hits: 3,
+                misses: 2,
+                sets: 3,
+                deletes: 1,
+                touches: 2,
+                errors: 2,
+            }
+        );
+        assert_eq!(metrics.hit_ratio(), Some(0.6));
+    }
+}
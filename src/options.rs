@@ -0,0 +1,122 @@
+use crate::Expiration;
+
+/// Per-call storage options
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct StoreOpts {
+    /// Item expiration, never by default
+    pub expiration: Expiration,
+    /// Flags stored along with the item
+    ///
+    /// Bits 0 and 1 are reserved for the value format, see `Flags`, and rejected by methods
+    /// encoding the value. Raw values such as those of `Client::set_raw` may use all bits.
+    pub flags: u32,
+    /// Do not wait for the server reply, `Status::NoReply` is returned instead
+    ///
+    /// Only honored by the ascii protocol.
+    pub noreply: bool,
+}
+
+impl StoreOpts {
+    /// Constructs a new `StoreOpts`.
+    ///
+    /// Parameters are initialized with their default values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set item expiration
+    pub fn expiration<E: Into<Expiration>>(mut self, expiration: E) -> Self {
+        self.expiration = expiration.into();
+
+        self
+    }
+
+    /// Set item flags, given as `u32` or `Flags`
+    pub fn flags<F: Into<u32>>(mut self, flags: F) -> Self {
+        self.flags = flags.into();
+
+        self
+    }
+
+    /// Set whether to skip waiting for the server reply
+    pub fn noreply(mut self, noreply: bool) -> Self {
+        self.noreply = noreply;
+
+        self
+    }
+}
+
+/// Store mode of a meta set, matching the classic storage commands
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MetaSetMode {
+    /// Store the value, the default
+    #[default]
+    Set,
+    /// Store the value only if the key does not exist
+    Add,
+    /// Append the value to the existing one
+    Append,
+    /// Prepend the value to the existing one
+    Prepend,
+    /// Store the value only if the key exists
+    Replace,
+}
+
+/// Per-call options of `Client::meta_set`
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct MetaSetOpts {
+    /// Store mode, set by default
+    pub mode: MetaSetMode,
+    /// Store only if the item still has this CAS identifier
+    pub cas: Option<u64>,
+    /// Item expiration, never by default
+    pub expiration: Expiration,
+    /// Flags stored along with the item, bits 0 and 1 being reserved for the value format
+    pub flags: u32,
+    /// With `cas`, store even if the item was modified since, marking it as stale
+    pub invalidate: bool,
+}
+
+impl MetaSetOpts {
+    /// Constructs a new `MetaSetOpts`.
+    ///
+    /// Parameters are initialized with their default values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set store mode
+    pub fn mode(mut self, mode: MetaSetMode) -> Self {
+        self.mode = mode;
+
+        self
+    }
+
+    /// Set CAS identifier the item must still have
+    pub fn cas(mut self, cas: u64) -> Self {
+        self.cas = Some(cas);
+
+        self
+    }
+
+    /// Set item expiration
+    pub fn expiration<E: Into<Expiration>>(mut self, expiration: E) -> Self {
+        self.expiration = expiration.into();
+
+        self
+    }
+
+    /// Set item flags, given as `u32` or `Flags`
+    pub fn flags<F: Into<u32>>(mut self, flags: F) -> Self {
+        self.flags = flags.into();
+
+        self
+    }
+
+    /// Set whether a CAS mismatch stores the value anyway, marking the item as stale
+    pub fn invalidate(mut self, invalidate: bool) -> Self {
+        self.invalidate = invalidate;
+
+        self
+    }
+}
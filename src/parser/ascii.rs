@@ -8,8 +8,13 @@ use nom::{
     sequence::{preceded, terminated, tuple},
     IResult,
 };
+use std::collections::HashMap;
+use std::convert::TryFrom;
 
-use super::{ErrorKind, Response, Status, Value};
+use super::{ErrorKind, Response, Status, Value, ValueHeader};
+
+/// Marks a data block not followed by "\r\n", i.e. one whose declared length is wrong
+const LENGTH_MISMATCH: nom::error::ErrorKind = nom::error::ErrorKind::LengthValue;
 
 fn _parse_ascii_status(buf: &[u8]) -> IResult<&[u8], Response> {
     terminated(
@@ -25,34 +30,54 @@ fn _parse_ascii_status(buf: &[u8]) -> IResult<&[u8], Response> {
     )(buf)
 }
 
-pub(crate) fn parse_version(buf: &[u8]) -> IResult<&[u8], String> {
-    terminated(
+/// Version line, or the error reply of a server rejecting the command
+pub(crate) fn parse_version(buf: &[u8]) -> IResult<&[u8], Result<String, ErrorKind>> {
+    let version = terminated(
         map_res(preceded(tag(b"VERSION "), take_until("\r\n")), |s| {
             std::str::from_utf8(s).map(|s| s.to_string())
         }),
         crlf,
-    )(buf)
+    );
+
+    alt((map(version, Ok), map(parse_ascii_error_kind, Err)))(buf)
 }
 
 pub(crate) fn parse_ascii_status(buf: &[u8]) -> IResult<&[u8], Response> {
     alt((_parse_ascii_status, parse_ascii_error))(buf)
 }
 
+/// Server errors rejecting the command itself, as sent by mcrouter, are told apart from others
+fn server_error(message: &str) -> ErrorKind {
+    let lowercase = message.to_ascii_lowercase();
+
+    if lowercase.contains("not supported") || lowercase.contains("unsupported") {
+        ErrorKind::Unsupported(message.to_string())
+    } else {
+        ErrorKind::Server(message.to_string())
+    }
+}
+
 fn parse_ascii_error(buf: &[u8]) -> IResult<&[u8], Response> {
-    let parser = terminated(
+    map(parse_ascii_error_kind, Response::Error)(buf)
+}
+
+fn parse_ascii_error_kind(buf: &[u8]) -> IResult<&[u8], ErrorKind> {
+    terminated(
         alt((
             value(ErrorKind::NonexistentCommand, tag(b"ERROR")),
+            value(
+                ErrorKind::Unauthenticated,
+                tag(b"CLIENT_ERROR unauthenticated"),
+            ),
             map_res(preceded(tag(b"CLIENT_ERROR "), take_until("\r\n")), |s| {
                 std::str::from_utf8(s).map(|s| ErrorKind::Client(s.to_string()))
             }),
             map_res(preceded(tag(b"SERVER_ERROR "), take_until("\r\n")), |s| {
-                std::str::from_utf8(s).map(|s| ErrorKind::Server(s.to_string()))
+                std::str::from_utf8(s).map(server_error)
             }),
         )),
         crlf,
-    );
-
-    map(parser, Response::Error)(buf)
+    )(buf)
 }
 
 fn parse_ascii_u32(buf: &[u8]) -> IResult<&[u8], u32> {
@@ -63,26 +88,43 @@ fn parse_ascii_u64(buf: &[u8]) -> IResult<&[u8], u64> {
     map_res(take_while_m_n(1, 20, is_digit), btou)(buf)
 }
 
+/// Any byte but whitespace and control characters, as accepted by `check_key_len`
 fn is_key_char(chr: u8) -> bool {
-    chr > 32 && chr < 127
+    chr > 32 && chr != 127
 }
 
-fn parse_ascii_value(buf: &[u8]) -> IResult<&[u8], Value> {
+/// Key, flags, data block length and cas id of a value line
+type ValueLine<'a> = (&'a [u8], u32, u64, Option<u64>);
+
+/// VALUE key flags data_len [cas id]\r\n
+fn parse_ascii_value_line(buf: &[u8]) -> IResult<&[u8], ValueLine<'_>> {
     let kf = take_while1(is_key_char);
-    let (buf, (_, key, _, flags, _, len, _, cas, _)) = tuple((
-        // VALUE key flags data_len [cas id]\r\n
-        // data block\r\n
-        tag("VALUE "),
-        kf,
-        tag(" "),
-        parse_ascii_u32,
-        tag(" "),
-        parse_ascii_u64,
-        opt(tag(" ")),
-        opt(parse_ascii_u64),
-        crlf,
-    ))(buf)?;
-    let (buf, data) = terminated(take(len), crlf)(buf)?;
+    map(
+        tuple((
+            tag("VALUE "),
+            kf,
+            tag(" "),
+            parse_ascii_u32,
+            tag(" "),
+            parse_ascii_u64,
+            opt(tag(" ")),
+            opt(parse_ascii_u64),
+            crlf,
+        )),
+        |(_, key, _, flags, _, len, _, cas, _)| (key, flags, len, cas),
+    )(buf)
+}
+
+fn parse_ascii_value(buf: &[u8]) -> IResult<&[u8], Value> {
+    // VALUE key flags data_len [cas id]\r\n
+    // data block\r\n
+    let (buf, (key, flags, len, cas)) = parse_ascii_value_line(buf)?;
+    let (buf, data) = take(len)(buf)?;
+    // Failure rather than error, so that the mismatch is not taken for the end of values
+    let (buf, _) = crlf(buf).map_err(|e| match e {
+        nom::Err::Error(_) => nom::Err::Failure(nom::error::Error::new(buf, LENGTH_MISMATCH)),
+        e => e,
+    })?;
     Ok((
         buf,
         Value {
@@ -116,15 +158,283 @@ pub(crate) fn parse_ascii_response(buf: &[u8]) -> Result<Option<(usize, Response
             Ok(Some((n, response)))
         }
         Err(nom::Err::Incomplete(_)) => Ok(None),
+        Err(nom::Err::Failure(e)) if e.code == LENGTH_MISMATCH => Err(ErrorKind::Protocol(Some(
+            "value length mismatch".to_string(),
+        ))),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            Err(ErrorKind::Protocol(Some(format!("{:?}", e))))
+        }
+    }
+}
+
+/// Parse only the first line of a retrieval response, leaving data block unread.
+///
+/// Returns number of bytes read and header of the first value, or `None` header if the response
+/// is an empty "END".
+pub(crate) fn parse_ascii_value_header(
+    buf: &[u8],
+) -> Result<Option<(usize, Option<ValueHeader>)>, ErrorKind> {
+    let result = alt((
+        map(tag("END\r\n"), |_| Ok(None)),
+        map(parse_ascii_value_line, |(_, flags, len, cas)| {
+            let length = usize::try_from(len)
+                .map_err(|_| ErrorKind::Protocol(Some("data block too large".to_string())))?;
+
+            Ok(Some(ValueHeader { flags, length, cas }))
+        }),
+        map(parse_ascii_error, |response| match response {
+            Response::Error(e) => Err(e),
+            response => Err(ErrorKind::Protocol(Some(format!(
+                "unexpected response: {:?}",
+                response
+            )))),
+        }),
+    ))(buf);
+
+    match result {
+        Ok((left, Ok(len))) => Ok(Some((buf.len() - left.len(), len))),
+        Ok((_, Err(e))) => Err(e),
+        Err(nom::Err::Incomplete(_)) => Ok(None),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            Err(ErrorKind::Protocol(Some(format!("{:?}", e))))
+        }
+    }
+}
+
+/// Parse reply to "incr" or "decr".
+///
+/// Returns number of bytes read and the new value, or `None` value if the key was not found.
+pub(crate) fn parse_ascii_arithmetic(
+    buf: &[u8],
+) -> Result<Option<(usize, Option<u64>)>, ErrorKind> {
+    let result = alt((
+        map(terminated(parse_ascii_u64, crlf), |value| Ok(Some(value))),
+        map(tag("NOT_FOUND\r\n"), |_| Ok(None)),
+        map(parse_ascii_error, |response| match response {
+            Response::Error(e) => Err(e),
+            response => Err(ErrorKind::Protocol(Some(format!(
+                "unexpected response: {:?}",
+                response
+            )))),
+        }),
+    ))(buf);
+
+    match result {
+        Ok((left, Ok(value))) => Ok(Some((buf.len() - left.len(), value))),
+        Ok((_, Err(e))) => Err(e),
+        Err(nom::Err::Incomplete(_)) => Ok(None),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            Err(ErrorKind::Protocol(Some(format!("{:?}", e))))
+        }
+    }
+}
+
+/// Parse reply to "mg <key> t", a meta get of the remaining time to live.
+///
+/// Returns number of bytes read and the seconds left, -1 if the item never expires, or `None`
+/// if the key was not found.
+pub(crate) fn parse_ascii_meta_ttl(buf: &[u8]) -> Result<Option<(usize, Option<i64>)>, ErrorKind> {
+    let result = alt((
+        map(tag("EN\r\n"), |_| Ok(None)),
+        // Servers before 1.6.10 reply "OK" instead of "HD"
+        map(
+            preceded(
+                alt((tag("HD"), tag("OK"))),
+                terminated(take_until("\r\n"), crlf),
+            ),
+            |flags| meta_ttl(flags).map(Some),
+        ),
+        map(parse_ascii_error, |response| match response {
+            Response::Error(e) => Err(e),
+            response => Err(ErrorKind::Protocol(Some(format!(
+                "unexpected response: {:?}",
+                response
+            )))),
+        }),
+    ))(buf);
+
+    match result {
+        Ok((left, Ok(ttl))) => Ok(Some((buf.len() - left.len(), ttl))),
+        Ok((_, Err(e))) => Err(e),
+        Err(nom::Err::Incomplete(_)) => Ok(None),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            Err(ErrorKind::Protocol(Some(format!("{:?}", e))))
+        }
+    }
+}
+
+/// Parse reply to "mn", the meta no-op.
+///
+/// Returns number of bytes read and whether the server speaks the meta protocol, which servers
+/// and proxies without it tell by rejecting the command.
+pub(crate) fn parse_ascii_meta_noop(buf: &[u8]) -> Result<Option<(usize, bool)>, ErrorKind> {
+    let result = alt((
+        map(tag("MN\r\n"), |_| Ok(true)),
+        map(parse_ascii_error, |response| match response {
+            Response::Error(ErrorKind::NonexistentCommand | ErrorKind::Unsupported(_)) => Ok(false),
+            Response::Error(e) => Err(e),
+            response => Err(ErrorKind::Protocol(Some(format!(
+                "unexpected response: {:?}",
+                response
+            )))),
+        }),
+    ))(buf);
+
+    match result {
+        Ok((left, Ok(supported))) => Ok(Some((buf.len() - left.len(), supported))),
+        Ok((_, Err(e))) => Err(e),
+        Err(nom::Err::Incomplete(_)) => Ok(None),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            Err(ErrorKind::Protocol(Some(format!("{:?}", e))))
+        }
+    }
+}
+
+/// Status of a meta set, with the CAS identifier of stored items
+type MetaSetReply = (Status, Option<u64>);
+
+/// Parse reply to "ms <key> <datalen> c", a meta set returning the new CAS identifier.
+///
+/// Returns number of bytes read, the status and, if the item was stored, its CAS identifier.
+pub(crate) fn parse_ascii_meta_set(buf: &[u8]) -> Result<Option<(usize, MetaSetReply)>, ErrorKind> {
+    let result = alt((
+        // Servers before 1.6.10 reply "OK" instead of "HD"
+        map(preceded(alt((tag("HD"), tag("OK"))), meta_flags), |flags| {
+            Ok((Status::Stored, meta_cas(flags)))
+        }),
+        map(preceded(tag("NS"), meta_flags), |_| {
+            Ok((Status::NotStored, None))
+        }),
+        map(preceded(tag("EX"), meta_flags), |_| {
+            Ok((Status::Exists, None))
+        }),
+        map(preceded(tag("NF"), meta_flags), |_| {
+            Ok((Status::NotFound, None))
+        }),
+        map(parse_ascii_error, |response| match response {
+            Response::Error(e) => Err(e),
+            response => Err(ErrorKind::Protocol(Some(format!(
+                "unexpected response: {:?}",
+                response
+            )))),
+        }),
+    ))(buf);
+
+    match result {
+        Ok((left, Ok(reply))) => Ok(Some((buf.len() - left.len(), reply))),
+        Ok((_, Err(e))) => Err(e),
+        Err(nom::Err::Incomplete(_)) => Ok(None),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+            Err(ErrorKind::Protocol(Some(format!("{:?}", e))))
+        }
+    }
+}
+
+/// Flags of a meta response, up to the end of the line
+fn meta_flags(buf: &[u8]) -> IResult<&[u8], &[u8]> {
+    terminated(take_until("\r\n"), crlf)(buf)
+}
+
+/// Find "c<cas>" among the flags of a meta response
+fn meta_cas(flags: &[u8]) -> Option<u64> {
+    flags
+        .split(|&b| b == b' ')
+        .find_map(|flag| flag.strip_prefix(b"c"))
+        .and_then(|cas| btou(cas).ok())
+}
+
+/// Find "t<seconds>" among the flags of a meta response
+fn meta_ttl(flags: &[u8]) -> Result<i64, ErrorKind> {
+    flags
+        .split(|&b| b == b' ')
+        .find_map(|flag| flag.strip_prefix(b"t"))
+        .and_then(|ttl| std::str::from_utf8(ttl).ok()?.parse().ok())
+        .ok_or_else(|| ErrorKind::Protocol(Some("missing ttl in meta response".to_string())))
+}
+
+/// Parse "OK\r\n" reply of admin commands such as "verbosity".
+///
+/// Returns number of bytes read.
+pub(crate) fn parse_ascii_ok(buf: &[u8]) -> Result<Option<usize>, ErrorKind> {
+    let result = alt((
+        map(tag("OK\r\n"), |_| Ok(())),
+        map(parse_ascii_error, |response| match response {
+            Response::Error(e) => Err(e),
+            response => Err(ErrorKind::Protocol(Some(format!(
+                "unexpected response: {:?}",
+                response
+            )))),
+        }),
+    ))(buf);
+
+    match result {
+        Ok((left, Ok(()))) => Ok(Some(buf.len() - left.len())),
+        Ok((_, Err(e))) => Err(e),
+        Err(nom::Err::Incomplete(_)) => Ok(None),
         Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
             Err(ErrorKind::Protocol(Some(format!("{:?}", e))))
         }
     }
 }
 
+/// Parse "STAT <name> <value>\r\n" lines of a complete stats response, up to "END\r\n"
+pub(crate) fn parse_ascii_stats(buf: &[u8]) -> Result<HashMap<String, String>, ErrorKind> {
+    if let Ok((_, Response::Error(e))) = parse_ascii_error(buf) {
+        return Err(e);
+    }
+
+    let mut stats = HashMap::new();
+
+    for line in buf.split(|&b| b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+        if line == b"END" {
+            return Ok(stats);
+        }
+
+        let stat = line.strip_prefix(b"STAT ").ok_or_else(|| {
+            ErrorKind::Protocol(Some(format!(
+                "unexpected stats line: {}",
+                String::from_utf8_lossy(line)
+            )))
+        })?;
+        let stat = String::from_utf8_lossy(stat);
+        let (name, value) = stat.split_once(' ').unwrap_or((&stat, ""));
+
+        let _ = stats.insert(name.to_string(), value.to_string());
+    }
+
+    Err(ErrorKind::Protocol(Some(
+        "stats response is missing END".to_string(),
+    )))
+}
+
+/// Check if buffer ends with a line that terminates a raw command response:
+/// "END", "OK", "ERROR", "CLIENT_ERROR ..." or "SERVER_ERROR ...".
+pub(crate) fn is_raw_response_complete(buf: &[u8]) -> bool {
+    let body = match buf.strip_suffix(b"\r\n") {
+        Some(body) => body,
+        None => return false,
+    };
+
+    let last_line = match body.windows(2).rposition(|w| w == b"\r\n") {
+        Some(i) => &body[i + 2..],
+        None => body,
+    };
+
+    matches!(last_line, b"END" | b"OK" | b"ERROR")
+        || last_line.starts_with(b"CLIENT_ERROR ")
+        || last_line.starts_with(b"SERVER_ERROR ")
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{parse_ascii_response, ErrorKind, Response, Status, Value};
+    use super::{
+        is_raw_response_complete, parse_ascii_arithmetic, parse_ascii_meta_noop,
+        parse_ascii_meta_set, parse_ascii_meta_ttl, parse_ascii_ok, parse_ascii_response,
+        parse_ascii_stats, parse_ascii_value_header, parse_version, ErrorKind, Response, Status,
+        Value, ValueHeader,
+    };
     use lazy_static::lazy_static;
 
     static FOO_KEY: &[u8] = b"foo";
@@ -147,10 +457,14 @@ mod tests {
                 (b"ERROR\r\n", 7, Response::Error(ErrorKind::NonexistentCommand)),
                 (b"CLIENT_ERROR foo\r\n", 18, Response::Error(ErrorKind::Client(FOO_STR.to_string()))),
                 (b"SERVER_ERROR bar\r\n", 18, Response::Error(ErrorKind::Server(BAR_STR.to_string()))),
+                (b"CLIENT_ERROR unauthenticated\r\n", 30, Response::Error(ErrorKind::Unauthenticated)),
                 (b"END\r\n", 5, Response::Data(vec![])),
                 (b"VALUE foo 42 11\r\nhello world\r\nEND\r\n", 35, Response::Data(
                     vec![Value { key: FOO_KEY.to_vec(), flags: 42, cas: None, data: HELLO_WORLD_DATA.to_vec() }]
                 )),
+                (b"VALUE foo 42 0\r\n\r\nEND\r\n", 23, Response::Data(
+                    vec![Value { key: FOO_KEY.to_vec(), flags: 42, cas: None, data: vec![] }]
+                )),
                 (b"VALUE foo 42 11\r\nhello world\r\nVALUE bar 43 11 15\r\nhello world\r\nEND\r\n", 68,
                     Response::Data(
                         vec![
@@ -193,4 +507,210 @@ mod tests {
             assert_eq!(n, *data_read);
         }
     }
+
+    #[test]
+    fn test_value_length_mismatch() {
+        let mismatch = Err(ErrorKind::Protocol(Some(
+            "value length mismatch".to_string(),
+        )));
+
+        // Declared length shorter and longer than the data block
+        assert_eq!(
+            parse_ascii_response(b"VALUE foo 0 3\r\nhello\r\nEND\r\n"),
+            mismatch
+        );
+        assert_eq!(
+            parse_ascii_response(b"VALUE foo 0 7\r\nhello\r\nEND\r\n"),
+            mismatch
+        );
+    }
+
+    #[test]
+    fn test_unsupported_parsing() {
+        assert_eq!(
+            parse_ascii_ok(b"SERVER_ERROR Command not supported\r\n"),
+            Err(ErrorKind::Unsupported("Command not supported".to_string()))
+        );
+        assert_eq!(
+            parse_ascii_ok(b"SERVER_ERROR out of memory\r\n"),
+            Err(ErrorKind::Server("out of memory".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_version_parsing() {
+        assert_eq!(
+            parse_version(b"VERSION 1.6.9\r\n"),
+            Ok((&b""[..], Ok("1.6.9".to_string())))
+        );
+        assert_eq!(
+            parse_version(b"CLIENT_ERROR unauthenticated\r\n"),
+            Ok((&b""[..], Err(ErrorKind::Unauthenticated)))
+        );
+        assert!(parse_version(b"CLIENT_ERROR unauth")
+            .unwrap_err()
+            .is_incomplete());
+        assert!(parse_version(b"VERS").unwrap_err().is_incomplete());
+    }
+
+    #[test]
+    fn test_meta_ttl_parsing() {
+        assert_eq!(
+            parse_ascii_meta_ttl(b"HD t120\r\n"),
+            Ok(Some((9, Some(120))))
+        );
+        assert_eq!(parse_ascii_meta_ttl(b"HD t-1\r\n"), Ok(Some((8, Some(-1)))));
+        assert_eq!(parse_ascii_meta_ttl(b"OK t5\r\n"), Ok(Some((7, Some(5)))));
+        assert_eq!(parse_ascii_meta_ttl(b"EN\r\n"), Ok(Some((4, None))));
+        assert_eq!(parse_ascii_meta_ttl(b"HD t12"), Ok(None));
+        assert!(parse_ascii_meta_ttl(b"HD\r\n").is_err());
+        assert_eq!(
+            parse_ascii_meta_ttl(b"ERROR\r\n"),
+            Err(ErrorKind::NonexistentCommand)
+        );
+    }
+
+    #[test]
+    fn test_meta_noop_parsing() {
+        assert_eq!(parse_ascii_meta_noop(b"MN\r\n"), Ok(Some((4, true))));
+        assert_eq!(parse_ascii_meta_noop(b"ERROR\r\n"), Ok(Some((7, false))));
+        assert_eq!(
+            parse_ascii_meta_noop(b"SERVER_ERROR Command not supported\r\n"),
+            Ok(Some((36, false)))
+        );
+        assert_eq!(parse_ascii_meta_noop(b"MN"), Ok(None));
+        assert_eq!(
+            parse_ascii_meta_noop(b"SERVER_ERROR bar\r\n"),
+            Err(ErrorKind::Server(BAR_STR.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_meta_set_parsing() {
+        assert_eq!(
+            parse_ascii_meta_set(b"HD c42\r\n"),
+            Ok(Some((8, (Status::Stored, Some(42)))))
+        );
+        assert_eq!(
+            parse_ascii_meta_set(b"HD\r\n"),
+            Ok(Some((4, (Status::Stored, None))))
+        );
+        assert_eq!(
+            parse_ascii_meta_set(b"NS\r\n"),
+            Ok(Some((4, (Status::NotStored, None))))
+        );
+        assert_eq!(
+            parse_ascii_meta_set(b"EX\r\n"),
+            Ok(Some((4, (Status::Exists, None))))
+        );
+        assert_eq!(
+            parse_ascii_meta_set(b"NF\r\n"),
+            Ok(Some((4, (Status::NotFound, None))))
+        );
+        assert_eq!(parse_ascii_meta_set(b"HD c4"), Ok(None));
+        assert_eq!(
+            parse_ascii_meta_set(b"ERROR\r\n"),
+            Err(ErrorKind::NonexistentCommand)
+        );
+    }
+
+    #[test]
+    fn test_ok_parsing() {
+        assert_eq!(parse_ascii_ok(b"OK\r\n"), Ok(Some(4)));
+        assert_eq!(parse_ascii_ok(b"OK"), Ok(None));
+        assert_eq!(
+            parse_ascii_ok(b"CLIENT_ERROR bad command line format\r\n"),
+            Err(ErrorKind::Client("bad command line format".to_string()))
+        );
+        assert_eq!(
+            parse_ascii_ok(b"ERROR\r\n"),
+            Err(ErrorKind::NonexistentCommand)
+        );
+    }
+
+    #[test]
+    fn test_arithmetic_parsing() {
+        assert_eq!(parse_ascii_arithmetic(b"42\r\n"), Ok(Some((4, Some(42)))));
+        assert_eq!(
+            parse_ascii_arithmetic(b"NOT_FOUND\r\n"),
+            Ok(Some((11, None)))
+        );
+        assert_eq!(parse_ascii_arithmetic(b"42"), Ok(None));
+        assert_eq!(parse_ascii_arithmetic(b"NOT_FO"), Ok(None));
+        assert_eq!(
+            parse_ascii_arithmetic(b"CLIENT_ERROR bar\r\n"),
+            Err(ErrorKind::Client(BAR_STR.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_value_header_parsing() {
+        let header = |flags, length, cas| ValueHeader { flags, length, cas };
+
+        assert_eq!(parse_ascii_value_header(b"END\r\n"), Ok(Some((5, None))));
+        assert_eq!(
+            parse_ascii_value_header(b"VALUE foo 42 11\r\nhello"),
+            Ok(Some((17, Some(header(42, 11, None)))))
+        );
+        assert_eq!(
+            parse_ascii_value_header(b"VALUE foo 42 11 15\r\n"),
+            Ok(Some((20, Some(header(42, 11, Some(15))))))
+        );
+        assert_eq!(
+            parse_ascii_value_header(b"VALUE \xff\xfe 42 11\r\n"),
+            Ok(Some((16, Some(header(42, 11, None)))))
+        );
+        assert_eq!(
+            parse_ascii_value_header(b"VALUE foo 42 0\r\n\r\nEND\r\n"),
+            Ok(Some((16, Some(header(42, 0, None)))))
+        );
+        assert_eq!(parse_ascii_value_header(b"VALUE foo 4"), Ok(None));
+        assert_eq!(
+            parse_ascii_value_header(b"SERVER_ERROR bar\r\n"),
+            Err(ErrorKind::Server(BAR_STR.to_string()))
+        );
+    }
+
+    #[test]
+    fn test_stats_parsing() {
+        let stats = parse_ascii_stats(b"STAT pid 1\r\nSTAT version 1.6.9 x\r\nEND\r\n").unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(stats["pid"], "1");
+        assert_eq!(stats["version"], "1.6.9 x");
+
+        assert_eq!(parse_ascii_stats(b"END\r\n"), Ok(Default::default()));
+        assert_eq!(
+            parse_ascii_stats(b"ERROR\r\n"),
+            Err(ErrorKind::NonexistentCommand)
+        );
+        assert!(parse_ascii_stats(b"STAT pid 1\r\n").is_err());
+        assert!(parse_ascii_stats(b"VALUE foo 0 1\r\n").is_err());
+
+        // Single lines are not capped in length
+        let long_value = "x".repeat(4096);
+        let response = format!("STAT long {}\r\nEND\r\n", long_value);
+        assert_eq!(
+            parse_ascii_stats(response.as_bytes()).unwrap()["long"],
+            long_value
+        );
+    }
+
+    #[test]
+    fn test_raw_response_complete() {
+        assert!(is_raw_response_complete(b"OK\r\n"));
+        assert!(is_raw_response_complete(b"ERROR\r\n"));
+        assert!(is_raw_response_complete(
+            b"STAT pid 1\r\nSTAT uptime 2\r\nEND\r\n"
+        ));
+        assert!(is_raw_response_complete(
+            b"CLIENT_ERROR bad command line format\r\n"
+        ));
+        assert!(is_raw_response_complete(b"SERVER_ERROR out of memory\r\n"));
+
+        assert!(!is_raw_response_complete(b""));
+        assert!(!is_raw_response_complete(b"OK"));
+        assert!(!is_raw_response_complete(b"STAT pid 1\r\n"));
+        assert!(!is_raw_response_complete(b"STAT pid 1\r\nEN"));
+        assert!(!is_raw_response_complete(b"STAT BLOCKEND\r\n"));
+    }
 }
@@ -1,7 +1,11 @@
-use std::fmt;
+use std::{error, fmt};
 
 mod ascii;
-pub(crate) use ascii::{parse_ascii_response, parse_ascii_status, parse_version};
+pub(crate) use ascii::{
+    is_raw_response_complete, parse_ascii_arithmetic, parse_ascii_meta_noop, parse_ascii_meta_set,
+    parse_ascii_meta_ttl, parse_ascii_ok, parse_ascii_response, parse_ascii_stats,
+    parse_ascii_status, parse_ascii_value_header, parse_version,
+};
 
 use crate::ErrorKind;
 
@@ -20,6 +24,17 @@ pub struct Value {
     pub data: Vec<u8>,
 }
 
+/// First line of a value from memcached, read ahead of its data block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ValueHeader {
+    /// Flags for this key.
+    pub flags: u32,
+    /// Length of the data block in bytes.
+    pub length: usize,
+    /// CAS identifier, only sent in reply to "gets".
+    pub cas: Option<u64>,
+}
+
 /// Status of a memcached operation.
 #[derive(Clone, Debug, PartialEq)]
 pub enum Status {
@@ -35,8 +50,69 @@ pub enum Status {
     Exists,
     /// The key was not found.
     NotFound,
+    /// The command was sent with "noreply", so its outcome is unknown.
+    NoReply,
 }
 
+impl Status {
+    /// Whether the value was stored.
+    pub fn is_stored(&self) -> bool {
+        *self == Self::Stored
+    }
+
+    /// Whether the value was not stored.
+    pub fn is_not_stored(&self) -> bool {
+        *self == Self::NotStored
+    }
+
+    /// Whether the key was deleted.
+    pub fn is_deleted(&self) -> bool {
+        *self == Self::Deleted
+    }
+
+    /// Whether the key was touched.
+    pub fn is_touched(&self) -> bool {
+        *self == Self::Touched
+    }
+
+    /// Whether the key already exists.
+    pub fn is_exists(&self) -> bool {
+        *self == Self::Exists
+    }
+
+    /// Whether the key was not found.
+    pub fn is_not_found(&self) -> bool {
+        *self == Self::NotFound
+    }
+
+    /// Whether the command was sent with "noreply".
+    pub fn is_no_reply(&self) -> bool {
+        *self == Self::NoReply
+    }
+
+    /// Turn a status other than `Stored` into an error, for use with `?`.
+    ///
+    /// `NoReply` is taken as success too, as the caller chose not to know the outcome.
+    pub fn stored_ok(self) -> Result<(), StatusError> {
+        match self {
+            Self::Stored | Self::NoReply => Ok(()),
+            status => Err(StatusError(status)),
+        }
+    }
+}
+
+/// Unexpected status of a memcached operation, returned by `Status::stored_ok`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatusError(pub Status);
+
+impl fmt::Display for StatusError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unexpected status: {}", self.0)
+    }
+}
+
+impl error::Error for StatusError {}
+
 /// Response to a memcached operation.
 #[derive(Clone, Debug, PartialEq)]
 pub enum Response {
@@ -57,6 +133,7 @@ impl fmt::Display for Status {
             Self::Touched => "touched".fmt(f),
             Self::Exists => "exists".fmt(f),
             Self::NotFound => "not found".fmt(f),
+            Self::NoReply => "no reply".fmt(f),
         }
     }
 }
@@ -66,12 +143,38 @@ impl fmt::Display for ErrorKind {
         match self {
             Self::Generic(s) => write!(f, "generic: {}", s),
             Self::NonexistentCommand => write!(f, "command does not exist"),
+            Self::Unsupported(s) => write!(f, "unsupported: {}", s),
             Self::Protocol(s) => match s {
                 Some(s) => write!(f, "protocol: {}", s),
                 None => write!(f, "protocol"),
             },
             Self::Client(s) => write!(f, "client: {}", s),
             Self::Server(s) => write!(f, "server: {}", s),
+            Self::Unauthenticated => write!(f, "authentication required"),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Status, StatusError};
+
+    #[test]
+    fn test_status_helpers() {
+        assert!(Status::Stored.is_stored());
+        assert!(!Status::NotStored.is_stored());
+        assert!(Status::NotFound.is_not_found());
+        assert!(Status::Deleted.is_deleted());
+
+        assert_eq!(Status::Stored.stored_ok(), Ok(()));
+        assert_eq!(Status::NoReply.stored_ok(), Ok(()));
+        assert_eq!(
+            Status::NotStored.stored_ok(),
+            Err(StatusError(Status::NotStored))
+        );
+        assert_eq!(
+            Status::NotFound.stored_ok().unwrap_err().to_string(),
+            "unexpected status: not found"
+        );
+    }
+}
@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use crate::{ConnectionManager, Pool};
+
+/// Maximum number of connections, as in bb8
+const DEFAULT_MAX_SIZE: u32 = 10;
+/// Time to wait for a connection, as in bb8
+const DEFAULT_CONNECTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Connection pool configuration, which bb8 does not let read back from a built pool
+///
+/// Pools built by `Client::with_pool_config` keep it for `Client::pool_config`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PoolConfig {
+    /// Maximum number of connections
+    pub max_size: u32,
+    /// Number of idle connections kept open, none if `None`
+    pub min_idle: Option<u32>,
+    /// Time to wait for a connection before failing
+    pub connection_timeout: Duration,
+}
+
+impl PoolConfig {
+    /// Constructs a new `PoolConfig`.
+    ///
+    /// Parameters are initialized with the bb8 default values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set maximum number of connections
+    pub fn max_size(mut self, max_size: u32) -> Self {
+        self.max_size = max_size;
+
+        self
+    }
+
+    /// Set number of idle connections kept open
+    pub fn min_idle(mut self, min_idle: Option<u32>) -> Self {
+        self.min_idle = min_idle;
+
+        self
+    }
+
+    /// Set time to wait for a connection before failing
+    pub fn connection_timeout(mut self, connection_timeout: Duration) -> Self {
+        self.connection_timeout = connection_timeout;
+
+        self
+    }
+
+    /// Pool builder with this configuration applied, for options not covered by it
+    pub fn builder(&self) -> bb8::Builder<ConnectionManager> {
+        Pool::builder()
+            .max_size(self.max_size)
+            .min_idle(self.min_idle)
+            .connection_timeout(self.connection_timeout)
+    }
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_size: DEFAULT_MAX_SIZE,
+            min_idle: None,
+            connection_timeout: DEFAULT_CONNECTION_TIMEOUT,
+        }
+    }
+}
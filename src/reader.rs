@@ -0,0 +1,134 @@
+use bytes::{Buf, BytesMut};
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use tokio::io::{AsyncRead, ReadBuf};
+
+use crate::PoolConnection;
+
+/// Closing lines following the data block of a single value
+const VALUE_TRAILER: &[u8] = b"\r\nEND\r\n";
+
+/// Reader over the data block of a single value, streamed from the connection
+///
+/// Holds the pooled connection until the reader is exhausted. The connection goes back to the
+/// pool only once the reader has returned end of file, a reader dropped earlier leaves unread
+/// response bytes behind and its connection is discarded.
+#[derive(Debug)]
+pub struct ValueReader<'a> {
+    conn: PoolConnection<'a>,
+    /// Bytes read from the connection but not handed out yet
+    buffer: BytesMut,
+    /// Data block length
+    length: usize,
+    /// Data block bytes not handed out yet
+    data_left: usize,
+    /// Whether the closing END line was read
+    finished: bool,
+}
+
+impl<'a> ValueReader<'a> {
+    /// Wrap connection positioned within the data block, with given bytes already read ahead
+    pub(crate) fn new(conn: PoolConnection<'a>, buffer: BytesMut, length: usize) -> Self {
+        Self {
+            conn,
+            buffer,
+            length,
+            data_left: length,
+            finished: false,
+        }
+    }
+
+    /// Data block length, as announced by the server
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    fn fail<T>(&mut self, error: io::Error) -> Poll<io::Result<T>> {
+        self.conn.poison();
+
+        Poll::Ready(Err(error))
+    }
+
+    fn poll_data(&mut self, cx: &mut Context, buf: &mut ReadBuf) -> Poll<io::Result<()>> {
+        if !self.buffer.is_empty() {
+            let n = self.data_left.min(self.buffer.len()).min(buf.remaining());
+            buf.put_slice(&self.buffer[..n]);
+            self.buffer.advance(n);
+            self.data_left -= n;
+
+            return Poll::Ready(Ok(()));
+        }
+
+        let start = buf.filled().len();
+        if let Err(e) = ready!(Pin::new(&mut *self.conn).poll_read(cx, buf)) {
+            return self.fail(e);
+        }
+
+        let n = buf.filled().len() - start;
+        if n == 0 {
+            return self.fail(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        // Keep bytes past the data block for the trailer check
+        if n > self.data_left {
+            self.buffer
+                .extend_from_slice(&buf.filled()[start + self.data_left..]);
+            buf.set_filled(start + self.data_left);
+        }
+        self.data_left -= n.min(self.data_left);
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_trailer(&mut self, cx: &mut Context) -> Poll<io::Result<()>> {
+        while self.buffer.len() < VALUE_TRAILER.len() {
+            // Read no further than the trailer, nothing follows it
+            let mut chunk = [0u8; VALUE_TRAILER.len()];
+            let mut chunk = ReadBuf::new(&mut chunk[..VALUE_TRAILER.len() - self.buffer.len()]);
+
+            if let Err(e) = ready!(Pin::new(&mut *self.conn).poll_read(cx, &mut chunk)) {
+                return self.fail(e);
+            }
+            if chunk.filled().is_empty() {
+                return self.fail(io::ErrorKind::UnexpectedEof.into());
+            }
+
+            self.buffer.extend_from_slice(chunk.filled());
+        }
+
+        if self.buffer[..] != *VALUE_TRAILER {
+            return self.fail(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "expected END after value",
+            ));
+        }
+
+        self.buffer.clear();
+        self.finished = true;
+        self.conn.finish_exchange();
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncRead for ValueReader<'_> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context,
+        buf: &mut ReadBuf,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+
+        if this.finished || buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+
+        if this.data_left > 0 {
+            this.poll_data(cx, buf)
+        } else {
+            // Reached end of file once the closing END line is read
+            this.poll_trailer(cx)
+        }
+    }
+}
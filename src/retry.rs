@@ -0,0 +1,177 @@
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use crate::MemcacheError;
+
+/// Retries of operations failing with retriable errors, see `MemcacheError::is_retriable`
+///
+/// Backoff doubles with every attempt, starting at `base_backoff` and capped at `max_backoff`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetryPolicy {
+    /// Number of retries after the first attempt
+    pub max_retries: u32,
+    /// Backoff before the first retry
+    pub base_backoff: Duration,
+    /// Upper bound of the backoff
+    pub max_backoff: Duration,
+    /// Randomize backoffs between half and all of their length, so that clients failing at the
+    /// same time do not retry at the same time
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            base_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Constructs a new `RetryPolicy`.
+    ///
+    /// Parameters are initialized with their default values.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set number of retries after the first attempt
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+
+        self
+    }
+
+    /// Set backoff before the first retry
+    pub fn base_backoff(mut self, base_backoff: Duration) -> Self {
+        self.base_backoff = base_backoff;
+
+        self
+    }
+
+    /// Set upper bound of the backoff
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+
+        self
+    }
+
+    /// Set whether backoffs are randomized
+    pub fn jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+
+        self
+    }
+
+    /// Backoff before given retry, counted from 0
+    fn backoff(&self, retry: u32) -> Duration {
+        let backoff = self
+            .base_backoff
+            .checked_mul(1 << retry.min(31))
+            .map_or(self.max_backoff, |backoff| backoff.min(self.max_backoff));
+
+        if self.jitter {
+            // Hashers of a new RandomState are randomly keyed, which is random enough here
+            let random = RandomState::new().build_hasher().finish();
+            let half = backoff / 2;
+
+            half + half.mul_f64(random as f64 / u64::MAX as f64)
+        } else {
+            backoff
+        }
+    }
+
+    /// Run operation until it succeeds, fails with an error that is not retriable, or runs out
+    /// of retries
+    ///
+    /// Every attempt runs a new future, so operations getting a pooled connection get a fresh
+    /// one, broken connections being discarded by the pool.
+    pub async fn run<F, Fut, T>(&self, mut operation: F) -> Result<T, MemcacheError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, MemcacheError>>,
+    {
+        let mut retry = 0;
+
+        loop {
+            match operation().await {
+                Err(e) if e.is_retriable() && retry < self.max_retries => {
+                    tokio::time::sleep(self.backoff(retry)).await;
+                    retry += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetryPolicy;
+    use crate::{ErrorKind, MemcacheError};
+    use std::io;
+    use std::time::Duration;
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryPolicy::new()
+            .base_backoff(Duration::from_millis(10))
+            .max_backoff(Duration::from_millis(50))
+            .jitter(false);
+
+        assert_eq!(policy.backoff(0), Duration::from_millis(10));
+        assert_eq!(policy.backoff(2), Duration::from_millis(40));
+        assert_eq!(policy.backoff(3), Duration::from_millis(50));
+        assert_eq!(policy.backoff(100), Duration::from_millis(50));
+
+        let backoff = policy.jitter(true).backoff(1);
+        assert!(backoff >= Duration::from_millis(10) && backoff <= Duration::from_millis(20));
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let policy = RetryPolicy::new()
+            .max_retries(2)
+            .base_backoff(Duration::from_millis(1));
+
+        let mut attempts = 0;
+        let result: Result<(), MemcacheError> = policy
+            .run(|| {
+                attempts += 1;
+                async { Err(io::Error::from(io::ErrorKind::ConnectionReset).into()) }
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(attempts, 3);
+
+        let mut attempts = 0;
+        let result: Result<(), MemcacheError> = policy
+            .run(|| {
+                attempts += 1;
+                async { Err(ErrorKind::Client("bad data chunk".to_string()).into()) }
+            })
+            .await;
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+
+        let mut attempts = 0;
+        let result = policy
+            .run(|| {
+                attempts += 1;
+                let attempt = attempts;
+                async move {
+                    match attempt {
+                        1 => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                        _ => Ok(attempt),
+                    }
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), 2);
+    }
+}
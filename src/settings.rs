@@ -1,10 +1,129 @@
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::RetryPolicy;
+
 const DEFAULT_BUFFER_SIZE: usize = 128;
+const DEFAULT_RESERVE_SIZE: usize = 1024;
+const DEFAULT_MAX_PIPELINE_BYTES: usize = 64 * 1024;
+const DEFAULT_MAX_MULTIGET_KEYS: usize = 256;
+const DEFAULT_MAX_KEY_LENGTH: usize = 250;
+const DEFAULT_CAS_CACHE_CAPACITY: usize = 10_000;
+
+/// Overrides for resolver options read from the system configuration
+///
+/// Options left as `None` keep their configured value.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ResolverSettings {
+    /// Timeout of a single DNS request
+    pub timeout: Option<Duration>,
+    /// Number of attempts before a lookup fails
+    pub attempts: Option<usize>,
+    /// Number of dots before a name is looked up as is, ahead of search domains
+    pub ndots: Option<usize>,
+    /// Whether the hosts file is consulted
+    pub use_hosts_file: Option<bool>,
+    /// Number of lookup retries after transient failures, 1 if `None`
+    pub lookup_retries: Option<usize>,
+}
+
+impl ResolverSettings {
+    /// Constructs a new `ResolverSettings` overriding nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set timeout of a single DNS request
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+
+        self
+    }
+
+    /// Set number of attempts before a lookup fails
+    pub fn attempts(mut self, attempts: usize) -> Self {
+        self.attempts = Some(attempts);
+
+        self
+    }
+
+    /// Set number of dots before a name is looked up as is
+    ///
+    /// Container resolv.conf files often set `ndots:5`, sending every lookup of a short service
+    /// name through all search domains first.
+    pub fn ndots(mut self, ndots: usize) -> Self {
+        self.ndots = Some(ndots);
+
+        self
+    }
+
+    /// Set whether the hosts file is consulted
+    pub fn use_hosts_file(mut self, use_hosts_file: bool) -> Self {
+        self.use_hosts_file = Some(use_hosts_file);
+
+        self
+    }
+
+    /// Set number of lookup retries after transient failures
+    ///
+    /// Lookups timing out or answered with SERVFAIL are retried right away, NXDOMAIN answers are
+    /// authoritative and fail the connect. Retries come on top of `attempts` made by the resolver
+    /// itself.
+    pub fn lookup_retries(mut self, lookup_retries: usize) -> Self {
+        self.lookup_retries = Some(lookup_retries);
+
+        self
+    }
+}
+
+/// Closure deciding whether a serialized value is worth compressing, see
+/// `Settings::compress_filter`
+pub type CompressFilter = Arc<dyn Fn(&[u8]) -> bool + Send + Sync>;
 
 /// Client settings
-#[derive(Clone, Debug)]
+#[derive(Clone)]
 pub struct Settings {
     /// Response buffer size
     pub buffer_size: usize,
+    /// Number of bytes reserved each time the response buffer needs to grow
+    pub reserve_size: usize,
+    /// Number of request bytes batch operations buffer before flushing
+    pub max_pipeline_bytes: usize,
+    /// Idle time before TCP keepalive probes are sent on new connections, system default if `None`
+    pub tcp_keepalive: Option<Duration>,
+    /// Maximum number of keys sent in a single multi-get request
+    pub max_multiget_keys: usize,
+    /// Maximum key length in bytes, longer keys are rejected before being sent
+    pub max_key_length: usize,
+    /// Overrides for options of the resolver looking up server addresses
+    pub resolver: ResolverSettings,
+    /// Time a successful connection check stays valid, checked on every checkout if `None`
+    pub validation_interval: Option<Duration>,
+    /// Time allowed to connect to each server address, no limit other than the OS one if `None`
+    pub connect_timeout: Option<Duration>,
+    /// Size of the socket receive buffer (SO_RCVBUF) of new connections, system default if `None`
+    pub socket_recv_buffer: Option<u32>,
+    /// Size of the socket send buffer (SO_SNDBUF) of new connections, system default if `None`
+    pub socket_send_buffer: Option<u32>,
+    /// Capacity of the read and write buffers wrapping new connections, 8 KiB if `None`
+    pub stream_buffer_capacity: Option<usize>,
+    /// Retries of `Client::get_retry` and `Client::set_retry`
+    pub retry_policy: RetryPolicy,
+    /// Duration above which operations are logged as slow, none are if `None`
+    pub slow_log_threshold: Option<Duration>,
+    /// Whether `Client::get` reads with "gets", remembering CAS identifiers for `Client::last_cas`
+    pub always_request_cas: bool,
+    /// Number of keys whose CAS identifiers are remembered with `always_request_cas`
+    pub cas_cache_capacity: usize,
+    /// Size in bytes below which serialized values are stored without trying to compress them
+    pub compress_min_size: usize,
+    /// Decides whether a serialized value is worth compressing, all are tried if `None`
+    pub compress_filter: Option<CompressFilter>,
+    /// Whether `Client::set` sends "noreply" rather than waiting for the server reply
+    pub set_noreply: bool,
+    /// Whether new connections without credentials are probed for required authentication
+    pub auth_probe: bool,
 }
 
 impl Settings {
@@ -21,12 +140,260 @@ impl Settings {
 
         self
     }
+
+    /// Set response buffer growth step
+    ///
+    /// Small responses fit into the initial `buffer_size`, while large multi-gets
+    /// benefit from reserving more at once.
+    pub fn reserve_size(mut self, reserve_size: usize) -> Self {
+        self.reserve_size = reserve_size;
+
+        self
+    }
+
+    /// Set number of request bytes batch operations buffer before flushing
+    ///
+    /// Batches are written in chunks of whole commands once this limit is reached, and replies
+    /// to each chunk are read before the next one is sent.
+    pub fn max_pipeline_bytes(mut self, max_pipeline_bytes: usize) -> Self {
+        self.max_pipeline_bytes = max_pipeline_bytes;
+
+        self
+    }
+
+    /// Enable TCP keepalive on new connections, probing after given idle time
+    ///
+    /// Keeps idle pooled connections alive behind NATs and firewalls that silently drop them.
+    /// Applied by `ConnectionManager` configured through `ConnectionManager::with_settings`.
+    pub fn tcp_keepalive(mut self, tcp_keepalive: Option<Duration>) -> Self {
+        self.tcp_keepalive = tcp_keepalive;
+
+        self
+    }
+
+    /// Set maximum number of keys sent in a single multi-get request
+    ///
+    /// Larger key lists are split into several requests, keeping request lines within the
+    /// limits of proxies such as mcrouter.
+    pub fn max_multiget_keys(mut self, max_multiget_keys: usize) -> Self {
+        self.max_multiget_keys = max_multiget_keys;
+
+        self
+    }
+
+    /// Set maximum key length in bytes, 250 by default as in memcached
+    ///
+    /// Raise it only for proxies or memcached builds accepting longer keys, which otherwise
+    /// reply with an error. Binary protocol keys are capped at 65535 bytes whatever the setting.
+    pub fn max_key_length(mut self, max_key_length: usize) -> Self {
+        self.max_key_length = max_key_length;
+
+        self
+    }
+
+    /// Override options of the resolver looking up server addresses
+    ///
+    /// Applied by `ConnectionManager` configured through `ConnectionManager::with_settings`,
+    /// unless it was given a ready resolver with `ConnectionManager::new`.
+    pub fn resolver(mut self, resolver: ResolverSettings) -> Self {
+        self.resolver = resolver;
+
+        self
+    }
+
+    /// Skip checking pooled connections that passed a check within given interval
+    ///
+    /// bb8 checks connections on every checkout, which for a hot pool mostly repeats checks
+    /// that just passed. Applied by `ConnectionManager` configured through
+    /// `ConnectionManager::with_settings`.
+    pub fn validation_interval(mut self, validation_interval: Option<Duration>) -> Self {
+        self.validation_interval = validation_interval;
+
+        self
+    }
+
+    /// Set time allowed to connect to each server address
+    ///
+    /// Every address a name resolves to gets the full timeout, so a dead address does not
+    /// exhaust the pool's `connection_timeout` before the others are tried. Applied by
+    /// `ConnectionManager` configured through `ConnectionManager::with_settings`, which then
+    /// needs a Tokio runtime with time enabled.
+    pub fn connect_timeout(mut self, connect_timeout: Option<Duration>) -> Self {
+        self.connect_timeout = connect_timeout;
+
+        self
+    }
+
+    /// Set size of the socket receive buffer of new connections
+    ///
+    /// Applied by `ConnectionManager` configured through `ConnectionManager::with_settings`.
+    pub fn socket_recv_buffer(mut self, socket_recv_buffer: Option<u32>) -> Self {
+        self.socket_recv_buffer = socket_recv_buffer;
+
+        self
+    }
+
+    /// Set size of the socket send buffer of new connections
+    ///
+    /// Applied by `ConnectionManager` configured through `ConnectionManager::with_settings`.
+    pub fn socket_send_buffer(mut self, socket_send_buffer: Option<u32>) -> Self {
+        self.socket_send_buffer = socket_send_buffer;
+
+        self
+    }
+
+    /// Set capacity of the read and write buffers wrapping new connections
+    ///
+    /// A capacity above the typical value size saves bulk loads of large values from many small
+    /// reads and writes. Applied by `ConnectionManager` configured through
+    /// `ConnectionManager::with_settings`.
+    pub fn stream_buffer_capacity(mut self, stream_buffer_capacity: Option<usize>) -> Self {
+        self.stream_buffer_capacity = stream_buffer_capacity;
+
+        self
+    }
+
+    /// Set retries of `Client::get_retry` and `Client::set_retry`
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+
+        self
+    }
+
+    /// Warn about operations taking longer than given duration
+    ///
+    /// Measured from connection checkout to the parsed reply and logged with `tracing` at warn
+    /// level, with the command and key length. Batches log the total length of their keys,
+    /// commands without a key a length of 0. Streams such as `Client::key_dump` are not timed.
+    /// Needs the "slow-log" feature.
+    pub fn slow_log_threshold(mut self, slow_log_threshold: Option<Duration>) -> Self {
+        self.slow_log_threshold = slow_log_threshold;
+
+        self
+    }
+
+    /// Read single keys with "gets" instead of "get", remembering CAS identifiers of all reads
+    ///
+    /// The identifier of a key read by `Client::get`, `Client::gets` or `Client::gets_bytes` is
+    /// then returned by `Client::last_cas`, saving a separate "gets" round trip before a "cas".
+    /// Identifiers are kept by the client and its clones until the key is read again and found
+    /// missing, up to `Settings::cas_cache_capacity` keys.
+    pub fn always_request_cas(mut self, always_request_cas: bool) -> Self {
+        self.always_request_cas = always_request_cas;
+
+        self
+    }
+
+    /// Set how many keys CAS identifiers are remembered for with `always_request_cas`, 10000 by
+    /// default
+    ///
+    /// Identifiers of the keys read longest ago are forgotten first.
+    pub fn cas_cache_capacity(mut self, cas_cache_capacity: usize) -> Self {
+        self.cas_cache_capacity = cas_cache_capacity;
+
+        self
+    }
+
+    /// Store serialized values smaller than given size without trying to compress them
+    ///
+    /// Saves the CPU time brotli spends on small values it barely shrinks. Needs the "compress"
+    /// feature.
+    pub fn compress_min_size(mut self, compress_min_size: usize) -> Self {
+        self.compress_min_size = compress_min_size;
+
+        self
+    }
+
+    /// Decide with given closure whether a serialized value is worth compressing
+    ///
+    /// Called with the serialized bytes of values of at least `compress_min_size`, which are
+    /// stored uncompressed if it returns `false`. `looks_compressible` estimates it from the
+    /// entropy of a sample, closures may capture their own state such as a threshold. Values are
+    /// still only stored compressed if that makes them smaller, and the flags of every value tell
+    /// whether it was. Needs the "compress" feature.
+    pub fn compress_filter<F>(mut self, compress_filter: F) -> Self
+    where
+        F: Fn(&[u8]) -> bool + Send + Sync + 'static,
+    {
+        self.compress_filter = Some(Arc::new(compress_filter));
+
+        self
+    }
+
+    /// Send `Client::set` with "noreply" instead of waiting for the server reply
+    ///
+    /// Trades confirmation for throughput: `Status::NoReply` is returned and failures to store go
+    /// unnoticed. Disabled by default, `Client::set_with_opts` always follows its `StoreOpts`.
+    /// Only honored by the ascii protocol.
+    pub fn set_noreply(mut self, set_noreply: bool) -> Self {
+        self.set_noreply = set_noreply;
+
+        self
+    }
+
+    /// Probe new connections without credentials with "version", failing the connect with
+    /// `ErrorKind::Unauthenticated` if the server requires authentication
+    ///
+    /// Enabled by default, so that missing credentials fail early rather than every command.
+    /// Disable it to save the round trip, or for proxies not answering "version".
+    pub fn auth_probe(mut self, auth_probe: bool) -> Self {
+        self.auth_probe = auth_probe;
+
+        self
+    }
+}
+
+impl fmt::Debug for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Settings")
+            .field("buffer_size", &self.buffer_size)
+            .field("reserve_size", &self.reserve_size)
+            .field("max_pipeline_bytes", &self.max_pipeline_bytes)
+            .field("tcp_keepalive", &self.tcp_keepalive)
+            .field("max_multiget_keys", &self.max_multiget_keys)
+            .field("max_key_length", &self.max_key_length)
+            .field("resolver", &self.resolver)
+            .field("validation_interval", &self.validation_interval)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("socket_recv_buffer", &self.socket_recv_buffer)
+            .field("socket_send_buffer", &self.socket_send_buffer)
+            .field("stream_buffer_capacity", &self.stream_buffer_capacity)
+            .field("retry_policy", &self.retry_policy)
+            .field("slow_log_threshold", &self.slow_log_threshold)
+            .field("always_request_cas", &self.always_request_cas)
+            .field("cas_cache_capacity", &self.cas_cache_capacity)
+            .field("compress_min_size", &self.compress_min_size)
+            // Closures have no useful debug output, only whether one is set
+            .field("compress_filter", &self.compress_filter.is_some())
+            .field("set_noreply", &self.set_noreply)
+            .field("auth_probe", &self.auth_probe)
+            .finish()
+    }
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Self {
             buffer_size: DEFAULT_BUFFER_SIZE,
+            reserve_size: DEFAULT_RESERVE_SIZE,
+            max_pipeline_bytes: DEFAULT_MAX_PIPELINE_BYTES,
+            tcp_keepalive: None,
+            max_multiget_keys: DEFAULT_MAX_MULTIGET_KEYS,
+            max_key_length: DEFAULT_MAX_KEY_LENGTH,
+            resolver: ResolverSettings::default(),
+            validation_interval: None,
+            connect_timeout: None,
+            socket_recv_buffer: None,
+            socket_send_buffer: None,
+            stream_buffer_capacity: None,
+            retry_policy: RetryPolicy::default(),
+            slow_log_threshold: None,
+            always_request_cas: false,
+            cas_cache_capacity: DEFAULT_CAS_CACHE_CAPACITY,
+            compress_min_size: 0,
+            compress_filter: None,
+            set_noreply: false,
+            auth_probe: true,
         }
     }
 }
@@ -0,0 +1,245 @@
+use bb8::ManageConnection;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::client::{check_flags, check_key_len, decode};
+use crate::driver::{RetrievalCommand, StorageCommand};
+use crate::parser::{Response, Status};
+use crate::slow_log::observe;
+use crate::{
+    codec, driver, Connection, ConnectionManager, Expiration, MemcacheError, Settings, StoreOpts,
+};
+
+/// Status of a single key command reply
+fn status(response: Response) -> Result<Status, MemcacheError> {
+    match response {
+        Response::Status(s) => Ok(s),
+        Response::Error(e) => Err(e.into()),
+        _ => unreachable!(),
+    }
+}
+
+/// Client owning one connection instead of a pool, for CLI tools and migrations
+///
+/// Created with `Client::connect_single` or `SingleClient::connect`. Commands are sent one at a
+/// time, so methods take `&mut self`. A connection broken by a failed exchange is replaced by a
+/// new one from the same manager before the next command.
+#[derive(Debug)]
+pub struct SingleClient {
+    manager: ConnectionManager,
+    conn: Connection,
+    settings: Settings,
+}
+
+impl SingleClient {
+    /// Open a connection with given manager, using given settings for command exchanges
+    pub async fn connect(
+        manager: ConnectionManager,
+        settings: Settings,
+    ) -> Result<Self, MemcacheError> {
+        let conn = manager.connect().await?;
+
+        Ok(Self {
+            manager,
+            conn,
+            settings,
+        })
+    }
+
+    /// Get a reference to the settings.
+    pub fn get_settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    /// Reconnect if the last exchange left the connection broken
+    async fn reconnect_if_broken(&mut self) -> Result<(), MemcacheError> {
+        if self.conn.has_broken() {
+            self.conn = self.manager.connect().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the server version.
+    pub async fn version(&mut self) -> Result<String, MemcacheError> {
+        self.reconnect_if_broken().await?;
+
+        let exchange = driver::version(&mut self.conn, &self.settings);
+        observe(&self.settings, b"version", 0, exchange).await
+    }
+
+    /// Get a key from memcached server, see `Client::get`.
+    pub async fn get<K: AsRef<[u8]>, V: DeserializeOwned>(
+        &mut self,
+        key: K,
+    ) -> Result<Option<V>, MemcacheError> {
+        check_key_len(&key, self.settings.max_key_length, self.conn.protocol())?;
+        self.reconnect_if_broken().await?;
+
+        let keys = &[key];
+        let exchange =
+            driver::retrieve(&mut self.conn, RetrievalCommand::Get, keys, &self.settings);
+        let response = observe(&self.settings, b"get", keys[0].as_ref().len(), exchange).await?;
+
+        match response {
+            Some(mut values) => {
+                let value = values.swap_remove(0);
+                decode(&value.key, &value.data, value.flags)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Set a key with associate value into memcached server with expiration seconds.
+    pub async fn set<K: AsRef<[u8]>, T: Serialize, E>(
+        &mut self,
+        key: K,
+        value: T,
+        expiration: E,
+    ) -> Result<Status, MemcacheError>
+    where
+        E: Into<Expiration>,
+    {
+        let opts = StoreOpts::new()
+            .expiration(expiration)
+            .noreply(self.settings.set_noreply);
+
+        self.set_with_opts(key, value, opts).await
+    }
+
+    /// Set a key with expiration, flags and noreply given in `opts`, see `Client::set_with_opts`.
+    pub async fn set_with_opts<K: AsRef<[u8]>, T: Serialize>(
+        &mut self,
+        key: K,
+        value: T,
+        opts: StoreOpts,
+    ) -> Result<Status, MemcacheError> {
+        self.store(StorageCommand::Set, key, value, opts).await
+    }
+
+    /// Store the value only if the server doesn't already hold data for this key.
+    pub async fn add<K: AsRef<[u8]>, T: Serialize, E>(
+        &mut self,
+        key: K,
+        value: T,
+        expiration: E,
+    ) -> Result<Status, MemcacheError>
+    where
+        E: Into<Expiration>,
+    {
+        let opts = StoreOpts::new().expiration(expiration);
+
+        self.store(StorageCommand::Add, key, value, opts).await
+    }
+
+    /// Store the value only if the server already holds data for this key.
+    pub async fn replace<K: AsRef<[u8]>, T: Serialize, E>(
+        &mut self,
+        key: K,
+        value: T,
+        expiration: E,
+    ) -> Result<Status, MemcacheError>
+    where
+        E: Into<Expiration>,
+    {
+        let opts = StoreOpts::new().expiration(expiration);
+
+        self.store(StorageCommand::Replace, key, value, opts).await
+    }
+
+    async fn store<K: AsRef<[u8]>, T: Serialize>(
+        &mut self,
+        cmd: StorageCommand,
+        key: K,
+        value: T,
+        opts: StoreOpts,
+    ) -> Result<Status, MemcacheError> {
+        check_key_len(&key, self.settings.max_key_length, self.conn.protocol())?;
+        check_flags(opts.flags)?;
+        self.reconnect_if_broken().await?;
+
+        let encoded = codec::encode(value, &self.settings)?;
+
+        let key_length = key.as_ref().len();
+        let exchange = driver::storage(
+            &mut self.conn,
+            cmd,
+            key,
+            opts.flags | encoded.flags.bits(),
+            opts.expiration,
+            &encoded.data,
+            opts.noreply,
+            &self.settings,
+        );
+        let response = observe(&self.settings, cmd.into(), key_length, exchange).await?;
+
+        status(response)
+    }
+
+    /// Delete a key with associate value into memcached server
+    pub async fn delete<K: AsRef<[u8]>>(&mut self, key: K) -> Result<Status, MemcacheError> {
+        check_key_len(&key, self.settings.max_key_length, self.conn.protocol())?;
+        self.reconnect_if_broken().await?;
+
+        let key_length = key.as_ref().len();
+        let exchange = driver::delete(&mut self.conn, key, false, &self.settings);
+        let response = observe(&self.settings, b"delete", key_length, exchange).await?;
+
+        status(response)
+    }
+
+    /// Set a new expiration time for an existing key
+    pub async fn touch<K: AsRef<[u8]>, E>(
+        &mut self,
+        key: K,
+        expiration: E,
+    ) -> Result<Status, MemcacheError>
+    where
+        E: Into<Expiration>,
+    {
+        check_key_len(&key, self.settings.max_key_length, self.conn.protocol())?;
+        self.reconnect_if_broken().await?;
+
+        let key_length = key.as_ref().len();
+        let exchange = driver::touch(&mut self.conn, key, expiration, false, &self.settings);
+        let response = observe(&self.settings, b"touch", key_length, exchange).await?;
+
+        status(response)
+    }
+}
+
+#[cfg(all(test, feature = "test-util"))]
+mod tests {
+    use super::SingleClient;
+    use crate::parser::Status;
+    use crate::testing::MockServer;
+    use crate::{ConnectionManager, Settings};
+
+    #[tokio::test]
+    async fn test_single_client() {
+        let manager = ConnectionManager::mock(MockServer::new()).unwrap();
+        let mut client = SingleClient::connect(manager, Settings::new())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            client.set("foo", "bar", None).await.unwrap(),
+            Status::Stored
+        );
+        assert_eq!(
+            client.add("foo", "baz", None).await.unwrap(),
+            Status::NotStored
+        );
+        assert_eq!(
+            client.get::<_, String>("foo").await.unwrap().as_deref(),
+            Some("bar")
+        );
+        assert_eq!(client.delete("foo").await.unwrap(), Status::Deleted);
+        assert_eq!(client.get::<_, String>("foo").await.unwrap(), None);
+
+        // Connections poisoned by a failed exchange are replaced before the next command
+        let _ = client.set("number", 42, None).await.unwrap();
+        client.conn.poison();
+        assert_eq!(client.get::<_, u32>("number").await.unwrap(), Some(42));
+    }
+}
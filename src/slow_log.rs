@@ -0,0 +1,69 @@
+use std::future::Future;
+#[cfg(feature = "slow-log")]
+use std::time::Instant;
+
+use crate::Settings;
+
+/// Await a command exchange on a checked out connection, warning with `tracing` if it took longer
+/// than `Settings::slow_log_threshold`
+#[cfg(feature = "slow-log")]
+pub(crate) async fn observe<F: Future>(
+    settings: &Settings,
+    command: &[u8],
+    key_length: usize,
+    exchange: F,
+) -> F::Output {
+    let threshold = match settings.slow_log_threshold {
+        Some(threshold) => threshold,
+        None => return exchange.await,
+    };
+
+    let started = Instant::now();
+    let output = exchange.await;
+    let elapsed = started.elapsed();
+
+    if elapsed > threshold {
+        tracing::warn!(
+            target: "vmemcached::slow_log",
+            command = %command.trim_ascii().escape_ascii(),
+            key_length,
+            ?elapsed,
+            "slow operation"
+        );
+    }
+
+    output
+}
+
+/// Await a command exchange, slow operations are only logged with the "slow-log" feature
+#[cfg(not(feature = "slow-log"))]
+pub(crate) async fn observe<F: Future>(
+    _settings: &Settings,
+    _command: &[u8],
+    _key_length: usize,
+    exchange: F,
+) -> F::Output {
+    exchange.await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::observe;
+    use crate::Settings;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_observe() {
+        let settings = Settings::new().slow_log_threshold(Some(Duration::ZERO));
+
+        let output = observe(&settings, b"get", 3, async {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+            42
+        })
+        .await;
+        assert_eq!(output, 42);
+
+        let output = observe(&Settings::new(), b"get", 3, async { 42 }).await;
+        assert_eq!(output, 42);
+    }
+}
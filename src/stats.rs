@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Server statistics with well-known fields parsed
+///
+/// Fields missing from the server reply are zero.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ServerStats {
+    /// Number of items currently stored
+    pub curr_items: u64,
+    /// Number of bytes currently used to store items
+    pub bytes: u64,
+    /// Number of keys that have been requested and found present
+    pub get_hits: u64,
+    /// Number of items that have been requested and not found
+    pub get_misses: u64,
+    /// Number of valid items removed from cache to free memory for new items
+    pub evictions: u64,
+    /// Time since the server started
+    pub uptime: Duration,
+    /// All other statistics, and well-known ones that failed to parse
+    pub raw: HashMap<String, String>,
+}
+
+/// Remove and parse statistic, leaving it in place if its value does not parse
+fn take<T: FromStr>(raw: &mut HashMap<String, String>, name: &str) -> Option<T> {
+    let value = raw.get(name)?.parse().ok()?;
+    let _ = raw.remove(name);
+
+    Some(value)
+}
+
+impl From<HashMap<String, String>> for ServerStats {
+    fn from(mut raw: HashMap<String, String>) -> Self {
+        Self {
+            curr_items: take(&mut raw, "curr_items").unwrap_or_default(),
+            bytes: take(&mut raw, "bytes").unwrap_or_default(),
+            get_hits: take(&mut raw, "get_hits").unwrap_or_default(),
+            get_misses: take(&mut raw, "get_misses").unwrap_or_default(),
+            evictions: take(&mut raw, "evictions").unwrap_or_default(),
+            uptime: take(&mut raw, "uptime")
+                .map(Duration::from_secs)
+                .unwrap_or_default(),
+            raw,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ServerStats;
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    #[test]
+    fn test_from_raw() {
+        let raw: HashMap<String, String> = [
+            ("curr_items", "3"),
+            ("bytes", "not a number"),
+            ("get_hits", "10"),
+            ("uptime", "60"),
+            ("version", "1.6.9"),
+        ]
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+
+        let stats = ServerStats::from(raw);
+
+        assert_eq!(stats.curr_items, 3);
+        assert_eq!(stats.bytes, 0);
+        assert_eq!(stats.get_hits, 10);
+        assert_eq!(stats.get_misses, 0);
+        assert_eq!(stats.uptime, Duration::from_secs(60));
+        assert_eq!(stats.raw.len(), 2);
+        assert_eq!(stats.raw["bytes"], "not a number");
+        assert_eq!(stats.raw["version"], "1.6.9");
+    }
+}
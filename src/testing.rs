@@ -0,0 +1,623 @@
+//! In-memory mock server for testing code built on [`Client`] without a memcached server.
+//!
+//! The mock speaks enough of the ascii protocol for get, gets, set, add, replace, append, prepend,
+//! cas, delete, touch, incr, decr, verbosity, cache_memlimit, version, meta get of the ttl, meta
+//! set, flush_all and lru_crawler metadump. Items never expire, except that an absolute exptime in
+//! the past removes the item right away, but their ttl is reported as if they did. Likewise
+//! flush_all removes all items right away, whatever the delay.
+//!
+//! ```no_run
+//! # async fn example() -> Result<(), vmemcached::MemcacheError> {
+//! use vmemcached::testing::MockServer;
+//!
+//! let server = MockServer::new();
+//! let client = server.client().await?;
+//!
+//! client.set("foo", "bar", None).await?;
+//!
+//! // Next command fails with given reply
+//! server.fail_next("SERVER_ERROR out of memory");
+//! assert!(client.set("foo", "bar", None).await.is_err());
+//! # Ok(())
+//! # }
+//! ```
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::sync::{Arc, Mutex, PoisonError};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufStream, DuplexStream};
+
+use crate::expiration::{unix_time, MAX_RELATIVE_EXPTIME};
+use crate::manager::Connect;
+use crate::{Client, Connection, ConnectionManager, MemcacheError, Pool, Settings};
+
+/// Capacity of the in-memory pipe between client and mock server
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+/// Stored item
+#[derive(Debug)]
+struct Item {
+    flags: u32,
+    data: Vec<u8>,
+    cas: u64,
+    /// Unix time the item is meant to expire at, only reported by meta get
+    deadline: Option<u64>,
+}
+
+/// State shared by all connections to a mock server
+#[derive(Debug, Default)]
+struct State {
+    items: HashMap<Vec<u8>, Item>,
+    errors: VecDeque<String>,
+    last_cas: u64,
+}
+
+/// In-memory memcached server speaking the ascii protocol
+///
+/// Clones share stored items and queued errors.
+#[derive(Clone, Debug, Default)]
+pub struct MockServer(Arc<Mutex<State>>);
+
+impl MockServer {
+    /// Create an empty mock server
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build a client whose connections are served by this mock server
+    pub async fn client(&self) -> Result<Client, MemcacheError> {
+        let pool = Pool::builder()
+            .build(ConnectionManager::mock(self.clone())?)
+            .await?;
+
+        Ok(Client::with_pool(pool, Settings::default()))
+    }
+
+    /// Open a connection served by this mock server
+    ///
+    /// Must be called within a Tokio runtime, the server side runs as a spawned task.
+    pub fn connect(&self) -> Connection {
+        let (client, server) = tokio::io::duplex(PIPE_CAPACITY);
+
+        drop(tokio::spawn(serve(self.clone(), server)));
+
+        Connection::from_duplex(client)
+    }
+
+    /// Reply to the next command with given line instead of executing it, e.g.
+    /// `"SERVER_ERROR out of memory"`
+    ///
+    /// Queued replies are used in order, one per command.
+    pub fn fail_next(&self, reply: &str) {
+        self.state().errors.push_back(reply.to_string());
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[async_trait::async_trait]
+impl Connect for MockServer {
+    async fn connect(&self) -> Result<Connection, MemcacheError> {
+        Ok(MockServer::connect(self))
+    }
+}
+
+/// Serve one connection until the client goes away
+async fn serve(server: MockServer, stream: DuplexStream) -> io::Result<()> {
+    let mut stream = BufStream::new(stream);
+    let mut line = Vec::new();
+
+    loop {
+        line.clear();
+        if stream.read_until(b'\n', &mut line).await? == 0 {
+            return Ok(());
+        }
+
+        let command = line.strip_suffix(b"\r\n").unwrap_or(&line);
+        let parts: Vec<&[u8]> = command
+            .split(|&b| b == b' ')
+            .filter(|part| !part.is_empty())
+            .collect();
+
+        let (name, args) = match parts.split_first() {
+            Some((name, args)) => (*name, args),
+            None => {
+                stream.write_all(b"ERROR\r\n").await?;
+                stream.flush().await?;
+                continue;
+            }
+        };
+
+        // Storage commands carry a data block, which is read even if the command fails
+        let length = match name {
+            b"set" | b"add" | b"replace" | b"append" | b"prepend" | b"cas" => Some(args.get(3)),
+            b"ms" => Some(args.get(1)),
+            _ => None,
+        };
+        let data = match length {
+            Some(length) => match length.and_then(|length| parse::<usize>(length)) {
+                Some(length) => {
+                    let mut data = vec![0; length + 2];
+                    let _ = stream.read_exact(&mut data).await?;
+                    data.truncate(length);
+                    Some(data)
+                }
+                None => {
+                    stream
+                        .write_all(b"CLIENT_ERROR bad command line format\r\n")
+                        .await?;
+                    stream.flush().await?;
+                    continue;
+                }
+            },
+            None => None,
+        };
+
+        let noreply = args.last() == Some(&&b"noreply"[..]);
+
+        let reply = {
+            let mut state = server.state();
+
+            match state.errors.pop_front() {
+                Some(error) => format!("{}\r\n", error).into_bytes(),
+                None => state.execute(name, args, data),
+            }
+        };
+
+        if !noreply {
+            stream.write_all(&reply).await?;
+            stream.flush().await?;
+        }
+    }
+}
+
+/// Unix time an item stored with given exptime expires at
+fn deadline(exptime: u64) -> Option<u64> {
+    match exptime {
+        0 => None,
+        exptime if exptime <= MAX_RELATIVE_EXPTIME => Some(unix_time() + exptime),
+        exptime => Some(exptime),
+    }
+}
+
+fn parse<T: std::str::FromStr>(arg: &[u8]) -> Option<T> {
+    std::str::from_utf8(arg).ok()?.parse().ok()
+}
+
+impl State {
+    fn execute(&mut self, name: &[u8], args: &[&[u8]], data: Option<Vec<u8>>) -> Vec<u8> {
+        match (name, data) {
+            (b"get", None) => self.retrieve(args, false),
+            (b"gets", None) => self.retrieve(args, true),
+            (b"ms", Some(data)) => self
+                .meta_set(args, data)
+                .unwrap_or_else(|| b"CLIENT_ERROR bad command line format\r\n".to_vec()),
+            (command, Some(data)) => self
+                .store(command, args, data)
+                .unwrap_or_else(|| b"CLIENT_ERROR bad command line format\r\n".to_vec()),
+            (b"delete", None) => match args.first().and_then(|key| self.items.remove(*key)) {
+                Some(_) => b"DELETED\r\n".to_vec(),
+                None => b"NOT_FOUND\r\n".to_vec(),
+            },
+            (b"touch", None) => {
+                let exptime = args.get(1).and_then(|arg| parse(arg)).unwrap_or_default();
+
+                match args.first().and_then(|key| self.items.get_mut(*key)) {
+                    Some(item) => {
+                        item.deadline = deadline(exptime);
+                        b"TOUCHED\r\n".to_vec()
+                    }
+                    None => b"NOT_FOUND\r\n".to_vec(),
+                }
+            }
+            (b"mg", None) if args.len() == 2 && args[1] == b"t" => {
+                match self.items.get(args[0]).map(|item| item.deadline) {
+                    Some(Some(deadline)) => {
+                        format!("HD t{}\r\n", deadline.saturating_sub(unix_time())).into_bytes()
+                    }
+                    Some(None) => b"HD t-1\r\n".to_vec(),
+                    None => b"EN\r\n".to_vec(),
+                }
+            }
+            (b"incr", None) => self.arithmetic(args, u64::wrapping_add),
+            (b"decr", None) => self.arithmetic(args, u64::saturating_sub),
+            (b"verbosity", None) | (b"cache_memlimit", None) => {
+                match args.first().and_then(|arg| parse::<u32>(arg)) {
+                    Some(_) => b"OK\r\n".to_vec(),
+                    None => b"ERROR\r\n".to_vec(),
+                }
+            }
+            (b"flush_all", None) => {
+                self.items.clear();
+                b"OK\r\n".to_vec()
+            }
+            (b"lru_crawler", None) if args == [&b"metadump"[..], b"all"] => self.metadump(),
+            (b"version", None) => b"VERSION mock\r\n".to_vec(),
+            (b"mn", None) => b"MN\r\n".to_vec(),
+            _ => b"ERROR\r\n".to_vec(),
+        }
+    }
+
+    /// <command name> <key> <value>
+    fn arithmetic(&mut self, args: &[&[u8]], apply: fn(u64, u64) -> u64) -> Vec<u8> {
+        let (key, amount) = match (args.first(), args.get(1).and_then(|arg| parse(arg))) {
+            (Some(key), Some(amount)) => (*key, amount),
+            _ => return b"CLIENT_ERROR bad command line format\r\n".to_vec(),
+        };
+
+        let item = match self.items.get_mut(key) {
+            Some(item) => item,
+            None => return b"NOT_FOUND\r\n".to_vec(),
+        };
+
+        match parse::<u64>(&item.data) {
+            Some(current) => {
+                let value = apply(current, amount);
+
+                self.last_cas += 1;
+                item.cas = self.last_cas;
+                item.data = value.to_string().into_bytes();
+
+                format!("{}\r\n", value).into_bytes()
+            }
+            None => b"CLIENT_ERROR cannot increment or decrement non-numeric value\r\n".to_vec(),
+        }
+    }
+
+    fn metadump(&self) -> Vec<u8> {
+        let mut reply = Vec::new();
+
+        for (key, item) in &self.items {
+            let key = percent_encoding::percent_encode(key, percent_encoding::NON_ALPHANUMERIC);
+            let exp = item.deadline.map_or(-1, |deadline| deadline as i64);
+            let line = format!(
+                "key={} exp={} la={} cas={} fetch=no cls=1 size={}\n",
+                key,
+                exp,
+                unix_time(),
+                item.cas,
+                item.data.len()
+            );
+            reply.extend_from_slice(line.as_bytes());
+        }
+        reply.extend_from_slice(b"END\r\n");
+
+        reply
+    }
+
+    fn retrieve(&self, keys: &[&[u8]], with_cas: bool) -> Vec<u8> {
+        let mut reply = Vec::new();
+
+        for key in keys {
+            if let Some(item) = self.items.get(*key) {
+                reply.extend_from_slice(b"VALUE ");
+                reply.extend_from_slice(key);
+                reply.extend_from_slice(format!(" {} {}", item.flags, item.data.len()).as_bytes());
+                if with_cas {
+                    reply.extend_from_slice(format!(" {}", item.cas).as_bytes());
+                }
+                reply.extend_from_slice(b"\r\n");
+                reply.extend_from_slice(&item.data);
+                reply.extend_from_slice(b"\r\n");
+            }
+        }
+        reply.extend_from_slice(b"END\r\n");
+
+        reply
+    }
+
+    /// ms <key> <datalen> <flags>*, run as the classic storage command of its mode
+    fn meta_set(&mut self, args: &[&[u8]], data: Vec<u8>) -> Option<Vec<u8>> {
+        let key = *args.first()?;
+        let length = data.len().to_string();
+        let mut flags = "0".to_string();
+        let mut exptime = "0".to_string();
+        let mut command: &[u8] = b"set";
+        let mut cas = None;
+
+        for flag in &args[2..] {
+            let (token, value) = flag.split_at(1);
+            let value = std::str::from_utf8(value).ok()?;
+
+            match token {
+                b"c" | b"I" => {}
+                b"F" => flags = value.to_string(),
+                b"T" => exptime = value.to_string(),
+                b"C" => cas = Some(parse::<u64>(value.as_bytes())?),
+                b"M" => {
+                    command = match value {
+                        "S" => b"set",
+                        "E" => b"add",
+                        "A" => b"append",
+                        "P" => b"prepend",
+                        "R" => b"replace",
+                        _ => return None,
+                    }
+                }
+                _ => return None,
+            }
+        }
+
+        if let Some(cas) = cas {
+            match self.items.get(key) {
+                None => return Some(b"NF\r\n".to_vec()),
+                Some(item) if item.cas != cas => return Some(b"EX\r\n".to_vec()),
+                Some(_) => {}
+            }
+        }
+
+        let storage_args = [key, flags.as_bytes(), exptime.as_bytes(), length.as_bytes()];
+        let reply = match &self.store(command, &storage_args, data)?[..] {
+            b"STORED\r\n" => match self.items.get(key) {
+                Some(item) => format!("HD c{}\r\n", item.cas).into_bytes(),
+                None => b"HD\r\n".to_vec(),
+            },
+            b"NOT_STORED\r\n" => b"NS\r\n".to_vec(),
+            _ => return None,
+        };
+
+        Some(reply)
+    }
+
+    /// <command name> <key> <flags> <exptime> <bytes> [<cas unique>] [noreply]
+    fn store(&mut self, command: &[u8], args: &[&[u8]], data: Vec<u8>) -> Option<Vec<u8>> {
+        let key = args.first()?.to_vec();
+        let flags = parse(args.get(1)?)?;
+        let exptime: u64 = parse(args.get(2)?)?;
+        let existing = self.items.get_mut(&key);
+
+        let data = match (command, existing) {
+            (b"add", Some(_)) | (b"replace", None) => return Some(b"NOT_STORED\r\n".to_vec()),
+            (b"append", None) | (b"prepend", None) => return Some(b"NOT_STORED\r\n".to_vec()),
+            (b"cas", None) => return Some(b"NOT_FOUND\r\n".to_vec()),
+            (b"cas", Some(item)) if Some(item.cas) != parse(args.get(4)?) => {
+                return Some(b"EXISTS\r\n".to_vec())
+            }
+            (b"append", Some(item)) => [&item.data[..], &data].concat(),
+            (b"prepend", Some(item)) => [&data[..], &item.data].concat(),
+            _ => data,
+        };
+
+        if exptime > MAX_RELATIVE_EXPTIME && exptime <= unix_time() {
+            let _ = self.items.remove(&key);
+
+            return Some(b"STORED\r\n".to_vec());
+        }
+
+        self.last_cas += 1;
+        let _ = self.items.insert(
+            key,
+            Item {
+                flags,
+                data,
+                cas: self.last_cas,
+                deadline: deadline(exptime),
+            },
+        );
+
+        Some(b"STORED\r\n".to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MockServer;
+    use crate::{ErrorKind, MemcacheError, Status};
+
+    #[tokio::test]
+    async fn test_client_commands() {
+        let server = MockServer::new();
+        let client = server.client().await.unwrap();
+
+        assert_eq!(client.version().await.unwrap(), "mock");
+
+        let value: Option<String> = client.get("foo").await.unwrap();
+        assert_eq!(value, None);
+
+        assert_eq!(
+            client.set("foo", "bar", None).await.unwrap(),
+            Status::Stored
+        );
+        assert_eq!(
+            client.add("foo", "baz", None).await.unwrap(),
+            Status::NotStored
+        );
+        let value: Option<String> = client.get("foo").await.unwrap();
+        assert_eq!(value.as_deref(), Some("bar"));
+
+        let (value, cas): (String, u64) = client.get_with_cas("foo").await.unwrap().unwrap();
+        assert_eq!(value, "bar");
+        assert_eq!(
+            client.cas("foo", "qux", None, cas).await.unwrap(),
+            Status::Stored
+        );
+        assert_eq!(
+            client.cas("foo", "qux", None, cas).await.unwrap(),
+            Status::Exists
+        );
+
+        assert_eq!(client.ttl("foo").await.unwrap(), Some(-1));
+        assert_eq!(
+            client
+                .touch("foo", std::time::Duration::from_secs(60))
+                .await
+                .unwrap(),
+            Status::Touched
+        );
+        assert!(matches!(client.ttl("foo").await.unwrap(), Some(59..=60)));
+        assert_eq!(client.delete("foo").await.unwrap(), Status::Deleted);
+        assert_eq!(client.delete("foo").await.unwrap(), Status::NotFound);
+        assert_eq!(client.touch("foo", None).await.unwrap(), Status::NotFound);
+        assert_eq!(client.ttl("foo").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_meta_set() {
+        use crate::{MetaSetMode, MetaSetOpts};
+
+        let client = MockServer::new().client().await.unwrap();
+
+        let (status, cas) = client
+            .meta_set("foo", "bar", MetaSetOpts::new().mode(MetaSetMode::Add))
+            .await
+            .unwrap();
+        assert_eq!(status, Status::Stored);
+        let cas = cas.unwrap();
+
+        let (status, _) = client
+            .meta_set("foo", "baz", MetaSetOpts::new().mode(MetaSetMode::Add))
+            .await
+            .unwrap();
+        assert_eq!(status, Status::NotStored);
+
+        let opts = MetaSetOpts::new().cas(cas + 1);
+        let (status, _) = client.meta_set("foo", "baz", opts).await.unwrap();
+        assert_eq!(status, Status::Exists);
+
+        let (status, new_cas) = client
+            .meta_set("foo", "baz", MetaSetOpts::new().cas(cas))
+            .await
+            .unwrap();
+        assert_eq!(status, Status::Stored);
+        assert_ne!(new_cas, Some(cas));
+
+        let opts = MetaSetOpts::new().cas(cas);
+        let (status, _) = client.meta_set("missing", "baz", opts).await.unwrap();
+        assert_eq!(status, Status::NotFound);
+
+        let opts = MetaSetOpts::new().mode(MetaSetMode::Replace);
+        let (status, _) = client.meta_set("missing", "baz", opts).await.unwrap();
+        assert_eq!(status, Status::NotStored);
+
+        let value: Option<String> = client.get("foo").await.unwrap();
+        assert_eq!(value.as_deref(), Some("baz"));
+    }
+
+    #[tokio::test]
+    async fn test_flush_all_nodes() {
+        use crate::{Client, ConnectionManager, Pool, Settings};
+
+        let server = MockServer::new();
+        let manager = ConnectionManager::mock(server.clone()).unwrap();
+        let pool = Pool::builder().max_size(3).build(manager).await.unwrap();
+        let client = Client::with_pool(pool, Settings::default());
+
+        let _ = client.set("foo", "bar", None).await.unwrap();
+        client.flush_all(0).await.unwrap();
+        assert_eq!(client.get::<_, String>("foo").await.unwrap(), None);
+
+        let _ = client.set("foo", "bar", None).await.unwrap();
+        let results = client.flush_all_nodes(0).await;
+        assert_eq!(results.len(), client.state().connections as usize);
+        assert!(results.iter().all(Result::is_ok));
+        assert_eq!(client.get::<_, String>("foo").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_admin_commands() {
+        let server = MockServer::new();
+        let client = server.client().await.unwrap();
+
+        client.set_verbosity(1).await.unwrap();
+        client.set_cache_memlimit(64).await.unwrap();
+
+        server.fail_next("ERROR");
+        match client.set_verbosity(1).await {
+            Err(MemcacheError::Memcache(ErrorKind::NonexistentCommand)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // As replied by mcrouter
+        server.fail_next("SERVER_ERROR Command not supported");
+        assert!(client.set_verbosity(1).await.unwrap_err().is_unsupported());
+
+        server.fail_next("SERVER_ERROR out of memory");
+        assert!(!client.set_verbosity(1).await.unwrap_err().is_unsupported());
+    }
+
+    #[cfg(feature = "metadump")]
+    #[tokio::test]
+    async fn test_key_dump() {
+        use futures_util::TryStreamExt;
+
+        let server = MockServer::new();
+        let client = server.client().await.unwrap();
+
+        for key in &["a", "b", "c"] {
+            let _ = client.set(key, "value", None).await.unwrap();
+        }
+
+        let mut keys: Vec<Vec<u8>> = client
+            .key_dump()
+            .map_ok(|meta| meta.key)
+            .try_collect()
+            .await
+            .unwrap();
+        keys.sort();
+        assert_eq!(keys, vec![b"a".to_vec(), b"b".to_vec(), b"c".to_vec()]);
+
+        // Connection is back in sync after the dump
+        assert_eq!(client.version().await.unwrap(), "mock");
+    }
+
+    #[tokio::test]
+    async fn test_unknown_command_discards_connection() {
+        use crate::{ConnectionManager, Pool, Settings};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let server = MockServer::new();
+        let connects = Arc::new(AtomicUsize::new(0));
+        let connector = {
+            let server = server.clone();
+            let connects = connects.clone();
+
+            move || {
+                let _ = connects.fetch_add(1, Ordering::SeqCst);
+                let conn = server.connect();
+                async move { Ok(conn) }
+            }
+        };
+        let manager = ConnectionManager::mock(server.clone())
+            .unwrap()
+            .with_connector(connector);
+        let pool = Pool::builder().max_size(1).build(manager).await.unwrap();
+        let client = crate::Client::with_pool(pool, Settings::default());
+
+        server.fail_next("ERROR");
+        match client.set("foo", "bar", None).await {
+            Err(MemcacheError::Memcache(ErrorKind::NonexistentCommand)) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+        let initial = connects.load(Ordering::SeqCst);
+
+        // Next operation runs on a fresh connection
+        assert_eq!(
+            client.set("foo", "bar", None).await.unwrap(),
+            Status::Stored
+        );
+        assert_eq!(connects.load(Ordering::SeqCst), initial + 1);
+    }
+
+    #[tokio::test]
+    async fn test_fail_next() {
+        let server = MockServer::new();
+        let client = server.client().await.unwrap();
+
+        server.fail_next("SERVER_ERROR out of memory");
+
+        match client.set("foo", "bar", None).await {
+            Err(MemcacheError::Memcache(ErrorKind::Server(message))) => {
+                assert_eq!(message, "out of memory")
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        // Error is used up and the data block was consumed, so the connection stays in sync
+        assert_eq!(
+            client.set("foo", "bar", None).await.unwrap(),
+            Status::Stored
+        );
+    }
+}
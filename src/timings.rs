@@ -0,0 +1,51 @@
+use std::time::{Duration, Instant};
+
+/// Time spent by an operation waiting for a pooled connection and exchanging with the server
+///
+/// Long acquire times call for a larger pool, long round trips for more servers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OpTimings {
+    /// Time waiting for a connection from the pool
+    pub acquire: Duration,
+    /// Time from sending the request to parsing the reply
+    pub round_trip: Duration,
+}
+
+impl OpTimings {
+    /// Timings of an operation started at `started` that got its connection at `acquired`
+    ///
+    /// Also logged with `tracing` at debug level, target `vmemcached::timings`.
+    pub(crate) fn finish(command: &str, started: Instant, acquired: Instant) -> Self {
+        let timings = Self {
+            acquire: acquired.duration_since(started),
+            round_trip: acquired.elapsed(),
+        };
+
+        tracing::debug!(
+            target: "vmemcached::timings",
+            command,
+            acquire = ?timings.acquire,
+            round_trip = ?timings.round_trip,
+            "operation timings"
+        );
+
+        timings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OpTimings;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_finish() {
+        let started = Instant::now();
+        let acquired = started + Duration::from_millis(5);
+        std::thread::sleep(Duration::from_millis(10));
+
+        let timings = OpTimings::finish("get", started, acquired);
+        assert_eq!(timings.acquire, Duration::from_millis(5));
+        assert!(timings.round_trip >= Duration::from_millis(5));
+    }
+}
@@ -1,3 +1,5 @@
+#![allow(dead_code)]
+
 use std::convert::TryFrom;
 use std::time::Duration;
 use vmemcached::{Client, ConnectionManager, MemcacheError, Pool, Settings};
@@ -31,7 +33,7 @@ pub async fn connect_with_custom_settings(
         .max_lifetime(Some(Duration::from_secs(60 * 30)))
         .idle_timeout(Some(Duration::from_secs(60 * 10)))
         .connection_timeout(Duration::from_millis(40))
-        .build(ConnectionManager::try_from(target)?)
+        .build(ConnectionManager::try_from(target)?.with_settings(settings.clone()))
         .await?;
 
     Ok(Client::with_pool(pool, settings))
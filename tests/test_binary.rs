@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::time;
+
+use vmemcached::Status;
+
+mod helpers;
+
+#[tokio::test]
+async fn test_binary() {
+    // Testing memcached, mcrouter speaks ascii only
+    let client = helpers::connect("memcache://localhost:11211?protocol=binary")
+        .await
+        .unwrap();
+
+    let version = client.version().await.unwrap();
+    assert_eq!(version, "1.6.9");
+
+    let got = client
+        .set("binary_foo", "bar", time::Duration::from_secs(1))
+        .await
+        .unwrap();
+
+    assert_eq!(got, Status::Stored);
+
+    let got: Option<String> = client.get("binary_foo").await.unwrap();
+    assert_eq!(got.unwrap(), "bar");
+
+    let got: Option<String> = client.get("binary_foo_none").await.unwrap();
+    assert!(got.is_none());
+
+    let got = client
+        .add("binary_foo", "baz", time::Duration::from_secs(1))
+        .await
+        .unwrap();
+    assert_eq!(got, Status::NotStored);
+
+    let got = client
+        .replace("binary_foo_none", "baz", time::Duration::from_secs(1))
+        .await
+        .unwrap();
+    assert_eq!(got, Status::NotStored);
+
+    let value: HashMap<String, String> = client
+        .gets(&["binary_foo", "binary_foo_none"])
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(value.len(), 1);
+    assert_eq!(value["binary_foo"], "bar".to_string());
+
+    let got = client
+        .touch("binary_foo", time::Duration::from_secs(1000))
+        .await
+        .unwrap();
+    assert_eq!(got, Status::Touched);
+
+    let got = client.delete("binary_foo").await.unwrap();
+    assert_eq!(got, Status::Deleted);
+
+    let got = client.delete("binary_foo").await.unwrap();
+    assert_eq!(got, Status::NotFound);
+
+    let got = client
+        .touch("binary_foo_none", time::Duration::from_secs(1000))
+        .await
+        .unwrap();
+    assert_eq!(got, Status::NotFound);
+    // Keys are sent apart from the command, so whitespace is allowed
+    let got = client
+        .set("binary foo", "bar", time::Duration::from_secs(1))
+        .await
+        .unwrap();
+    assert_eq!(got, Status::Stored);
+
+    let got: Option<String> = client.get("binary foo").await.unwrap();
+    assert_eq!(got.unwrap(), "bar");
+}
@@ -1,4 +1,5 @@
-use vmemcached::Settings;
+use std::time::Duration;
+use vmemcached::{ResolverSettings, RetryPolicy, Settings};
 
 mod helpers;
 
@@ -12,11 +13,38 @@ async fn test_default_settings() {
     let expected = Settings::default();
 
     assert_eq!(got.buffer_size, expected.buffer_size);
+    assert_eq!(got.reserve_size, expected.reserve_size);
+    assert_eq!(got.max_pipeline_bytes, expected.max_pipeline_bytes);
+    assert_eq!(got.tcp_keepalive, None);
+    assert_eq!(got.max_multiget_keys, expected.max_multiget_keys);
+    assert_eq!(got.max_key_length, 250);
+    assert_eq!(got.resolver, ResolverSettings::default());
+    assert_eq!(got.validation_interval, None);
+    assert_eq!(got.connect_timeout, None);
+    assert_eq!(got.socket_recv_buffer, None);
+    assert_eq!(got.socket_send_buffer, None);
+    assert_eq!(got.stream_buffer_capacity, None);
+    assert_eq!(got.retry_policy, RetryPolicy::default());
+    assert_eq!(got.slow_log_threshold, None);
 }
 
 #[tokio::test]
 async fn test_custom_settings() {
-    let settings = Settings::new().buffer_size(256);
+    let settings = Settings::new()
+        .buffer_size(256)
+        .reserve_size(4096)
+        .max_pipeline_bytes(1024)
+        .tcp_keepalive(Some(Duration::from_secs(60)))
+        .max_multiget_keys(16)
+        .max_key_length(1024)
+        .resolver(ResolverSettings::new().ndots(1).lookup_retries(3))
+        .validation_interval(Some(Duration::from_secs(5)))
+        .connect_timeout(Some(Duration::from_secs(1)))
+        .socket_recv_buffer(Some(1 << 20))
+        .socket_send_buffer(Some(1 << 20))
+        .stream_buffer_capacity(Some(64 * 1024))
+        .retry_policy(RetryPolicy::new().max_retries(5))
+        .slow_log_threshold(Some(Duration::from_millis(50)));
     let client = helpers::connect_with_custom_settings("memcache://localhost:11311", settings)
         .await
         .unwrap();
@@ -25,4 +53,18 @@ async fn test_custom_settings() {
     let expected_buffer_size = 256;
 
     assert_eq!(got.buffer_size, expected_buffer_size);
+    assert_eq!(got.reserve_size, 4096);
+    assert_eq!(got.max_pipeline_bytes, 1024);
+    assert_eq!(got.tcp_keepalive, Some(Duration::from_secs(60)));
+    assert_eq!(got.max_multiget_keys, 16);
+    assert_eq!(got.max_key_length, 1024);
+    assert_eq!(got.resolver.ndots, Some(1));
+    assert_eq!(got.resolver.lookup_retries, Some(3));
+    assert_eq!(got.validation_interval, Some(Duration::from_secs(5)));
+    assert_eq!(got.connect_timeout, Some(Duration::from_secs(1)));
+    assert_eq!(got.socket_recv_buffer, Some(1 << 20));
+    assert_eq!(got.socket_send_buffer, Some(1 << 20));
+    assert_eq!(got.stream_buffer_capacity, Some(64 * 1024));
+    assert_eq!(got.retry_policy.max_retries, 5);
+    assert_eq!(got.slow_log_threshold, Some(Duration::from_millis(50)));
 }
@@ -17,7 +17,7 @@ async fn test_haproxy() {
     let mut i = 0;
     while i < 20 {
         let client_clone = client.clone();
-        let _ = tokio::spawn(async move {
+        tokio::spawn(async move {
             let key = "haproxy_fun";
 
             let got = client_clone
@@ -41,7 +41,7 @@ async fn test_haproxy() {
         });
 
         let client_clone = client.clone();
-        let _ = tokio::spawn(async move {
+        tokio::spawn(async move {
             let key = "haproxy_fun2";
 
             client_clone
@@ -3,7 +3,7 @@ use rand::{thread_rng, Rng};
 use std::iter;
 use std::time;
 
-use vmemcached::Status;
+use vmemcached::{MetaSetMode, MetaSetOpts, Status};
 
 mod helpers;
 
@@ -127,3 +127,384 @@ async fn tcp_test() {
         }
     }
 }
+
+#[tokio::test]
+async fn test_shutdown() {
+    let client = helpers::connect("memcache://localhost:11311")
+        .await
+        .unwrap();
+
+    client.version().await.unwrap();
+    client.shutdown().await.unwrap();
+
+    // Pool reconnects on demand after shutdown
+    client.version().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_raw_command() {
+    let client = helpers::connect("memcache://localhost:11211")
+        .await
+        .unwrap();
+
+    let response = client.command("stats").await.unwrap();
+    assert!(response.starts_with(b"STAT pid "));
+    assert!(response.ends_with(b"END\r\n"));
+
+    let response = client.command("verbosity 1").await.unwrap();
+    assert_eq!(response, b"OK\r\n");
+
+    let response = client.command("no_such_command").await.unwrap();
+    assert_eq!(response, b"ERROR\r\n");
+}
+
+#[tokio::test]
+async fn test_cas() {
+    let client = helpers::connect("memcache://localhost:11211")
+        .await
+        .unwrap();
+
+    let key = "test_cas";
+    client.delete(key).await.unwrap();
+
+    let result: Option<(u64, u64)> = client.get_with_cas(key).await.unwrap();
+    assert_eq!(result, None);
+
+    client.set(key, 1u64, None).await.unwrap();
+    let (value, cas): (u64, u64) = client.get_with_cas(key).await.unwrap().unwrap();
+    assert_eq!(value, 1);
+
+    let status = client.cas(key, value + 1, None, cas).await.unwrap();
+    assert_eq!(status, Status::Stored);
+
+    // Token is stale after the successful update
+    let status = client.cas(key, value + 2, None, cas).await.unwrap();
+    assert_eq!(status, Status::Exists);
+
+    let result: Option<u64> = client.get(key).await.unwrap();
+    assert_eq!(result, Some(2));
+
+    client.delete(key).await.unwrap();
+    let status = client.cas(key, value, None, cas).await.unwrap();
+    assert_eq!(status, Status::NotFound);
+}
+
+#[tokio::test]
+async fn test_get_or_set() {
+    let client = helpers::connect("memcache://localhost:11211")
+        .await
+        .unwrap();
+
+    let key = "test_get_or_set";
+    client.delete(key).await.unwrap();
+
+    let value: String = client
+        .get_or_set(key, None, || async { "computed".to_string() })
+        .await
+        .unwrap();
+    assert_eq!(value, "computed");
+
+    // Hit does not call the closure
+    let value: String = client
+        .get_or_set(key, None, || async { unreachable!() })
+        .await
+        .unwrap();
+    assert_eq!(value, "computed");
+}
+
+#[tokio::test]
+async fn test_get_or_set_single_flight() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    let client = helpers::connect("memcache://localhost:11211")
+        .await
+        .unwrap();
+
+    let key = "test_get_or_set_single_flight";
+    client.delete(key).await.unwrap();
+
+    let computations = Arc::new(AtomicUsize::new(0));
+    let tasks: Vec<_> = (0..10)
+        .map(|_| {
+            let client = client.clone();
+            let computations = computations.clone();
+
+            tokio::spawn(async move {
+                client
+                    .get_or_set_single_flight(key, None, || async move {
+                        let _ = computations.fetch_add(1, Ordering::SeqCst);
+                        tokio::task::yield_now().await;
+                        "computed".to_string()
+                    })
+                    .await
+                    .unwrap()
+            })
+        })
+        .collect();
+
+    for task in tasks {
+        assert_eq!(task.await.unwrap(), "computed");
+    }
+    assert!(computations.load(Ordering::SeqCst) < 10);
+}
+
+#[tokio::test]
+async fn test_exists() {
+    let client = helpers::connect("memcache://localhost:11211")
+        .await
+        .unwrap();
+
+    let key = "test_exists";
+    client.delete(key).await.unwrap();
+    assert!(!client.exists(key).await.unwrap());
+
+    client.set(key, "value", None).await.unwrap();
+    assert!(client.exists(key).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_add_or_get() {
+    let client = helpers::connect("memcache://localhost:11211")
+        .await
+        .unwrap();
+
+    let key = "test_add_or_get";
+    client.delete(key).await.unwrap();
+
+    let value = client.add_or_get(key, 1u64, None).await.unwrap();
+    assert_eq!(value, 1);
+
+    // First writer wins
+    let value = client.add_or_get(key, 2u64, None).await.unwrap();
+    assert_eq!(value, 1);
+}
+
+#[tokio::test]
+async fn test_set_reader() {
+    let client = helpers::connect("memcache://localhost:11211")
+        .await
+        .unwrap();
+
+    // Stored bytes are read back by get when they hold an encoded value
+    let value = br#""streamed value""#;
+    let status = client
+        .set_reader("test_set_reader", &value[..], value.len(), None)
+        .await
+        .unwrap();
+    assert_eq!(status, Status::Stored);
+
+    let result: Option<String> = client.get("test_set_reader").await.unwrap();
+    assert_eq!(result.as_deref(), Some("streamed value"));
+}
+
+#[tokio::test]
+async fn test_increment_with_default() {
+    let client = helpers::connect("memcache://localhost:11211")
+        .await
+        .unwrap();
+
+    let key = "test_increment_with_default";
+    client.delete(key).await.unwrap();
+
+    assert_eq!(client.increment(key, 1).await.unwrap(), None);
+    assert_eq!(
+        client
+            .increment_with_default(key, 1, 10, None)
+            .await
+            .unwrap(),
+        10
+    );
+    assert_eq!(
+        client
+            .increment_with_default(key, 1, 10, None)
+            .await
+            .unwrap(),
+        11
+    );
+    assert_eq!(client.decrement(key, 5).await.unwrap(), Some(6));
+    assert_eq!(client.counter_value(key).await.unwrap(), Some(6));
+}
+
+#[tokio::test]
+async fn test_try_lock_unlock() {
+    let client = helpers::connect("memcache://localhost:11211")
+        .await
+        .unwrap();
+
+    let key = "test_try_lock_unlock";
+    client.delete(key).await.unwrap();
+
+    let ttl = time::Duration::from_secs(10);
+    assert!(client.try_lock(key, "a", ttl).await.unwrap());
+    assert!(!client.try_lock(key, "b", ttl).await.unwrap());
+
+    assert!(!client.unlock(key, "b").await.unwrap());
+    assert!(client.unlock(key, "a").await.unwrap());
+
+    assert!(client.try_lock(key, "b", ttl).await.unwrap());
+}
+
+#[tokio::test]
+async fn test_set_delete_many() {
+    let settings = vmemcached::Settings::new().max_pipeline_bytes(64);
+    let client = helpers::connect_with_custom_settings("memcache://localhost:11211", settings)
+        .await
+        .unwrap();
+
+    let items: Vec<(String, u64)> = (0..100).map(|i| (format!("test_many_{}", i), i)).collect();
+
+    let result = client.set_many(&items, None).await.unwrap();
+    assert_eq!(result.stored(), items.len());
+    assert_eq!(result.errors().count(), 0);
+
+    let value: Option<u64> = client.get("test_many_42").await.unwrap();
+    assert_eq!(value, Some(42));
+
+    let keys: Vec<&str> = items.iter().map(|(key, _)| key.as_str()).collect();
+    let statuses = client
+        .touch_many(&keys, time::Duration::from_secs(60))
+        .await
+        .unwrap();
+    assert_eq!(statuses, vec![Status::Touched; items.len()]);
+
+    let statuses = client.delete_many(&keys).await.unwrap();
+    assert_eq!(statuses, vec![Status::Deleted; items.len()]);
+}
+
+#[tokio::test]
+async fn test_set_with_opts() {
+    let client = helpers::connect("memcache://localhost:11211")
+        .await
+        .unwrap();
+
+    let key = "test_set_with_opts";
+
+    let opts = vmemcached::StoreOpts::new()
+        .expiration(time::Duration::from_secs(60))
+        .noreply(true);
+    let status = client.set_with_opts(key, "value", opts).await.unwrap();
+    assert_eq!(status, Status::NoReply);
+
+    let value: Option<String> = client.get(key).await.unwrap();
+    assert_eq!(value.as_deref(), Some("value"));
+}
+
+#[tokio::test]
+async fn test_stats() {
+    let client = helpers::connect("memcache://localhost:11211")
+        .await
+        .unwrap();
+
+    let stats = client.stats().await.unwrap();
+    assert!(stats.contains_key("pid"));
+
+    let stats = client.stats_typed().await.unwrap();
+    assert!(stats.raw.contains_key("pid"));
+    assert!(!stats.raw.contains_key("uptime"));
+}
+
+#[tokio::test]
+async fn test_stats_with_arg() {
+    let client = helpers::connect("memcache://localhost:11211")
+        .await
+        .unwrap();
+
+    let stats = client.stats_with_arg("slabs").await.unwrap();
+    assert!(stats.contains_key("active_slabs"));
+}
+
+#[tokio::test]
+async fn test_raw_round_trip() {
+    let client = helpers::connect("memcache://localhost:11211")
+        .await
+        .unwrap();
+
+    let data = b"\x00\xffraw bytes".to_vec();
+    let opts = vmemcached::StoreOpts::new().flags(42u32);
+    let status = client
+        .set_raw("test_raw_round_trip", data.clone(), opts)
+        .await
+        .unwrap();
+    assert_eq!(status, Status::Stored);
+
+    let value = client
+        .get_raw("test_raw_round_trip")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(value.data, data);
+    assert_eq!(value.flags, 42);
+}
+
+#[tokio::test]
+async fn test_admin_commands() {
+    let client = helpers::connect("memcache://localhost:11211?protocol=ascii")
+        .await
+        .unwrap();
+
+    client.set_verbosity(1).await.unwrap();
+    client.set_verbosity(0).await.unwrap();
+
+    let stats = client.stats().await.unwrap();
+    // Keep the current limit
+    let limit: u64 = stats["limit_maxbytes"].parse().unwrap();
+    let limit_mb = (limit / (1024 * 1024)) as u32;
+    client.set_cache_memlimit(limit_mb).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_ttl() {
+    let client = helpers::connect("memcache://localhost:11211?protocol=ascii")
+        .await
+        .unwrap();
+
+    let key = "test_ttl";
+    client.delete(key).await.unwrap();
+    assert_eq!(client.ttl(key).await.unwrap(), None);
+
+    client.set(key, "value", None).await.unwrap();
+    assert_eq!(client.ttl(key).await.unwrap(), Some(-1));
+
+    client
+        .touch(key, time::Duration::from_secs(60))
+        .await
+        .unwrap();
+    assert!(client.ttl(key).await.unwrap().unwrap() > 0);
+}
+
+#[tokio::test]
+async fn test_meta_set() {
+    let client = helpers::connect("memcache://localhost:11211")
+        .await
+        .unwrap();
+
+    let key = "test_meta_set";
+    client.delete(key).await.unwrap();
+
+    let opts = MetaSetOpts::new().mode(MetaSetMode::Add);
+    let (status, cas) = client.meta_set(key, "value", opts).await.unwrap();
+    assert_eq!(status, Status::Stored);
+
+    let (status, _) = client.meta_set(key, "value", opts).await.unwrap();
+    assert_eq!(status, Status::NotStored);
+
+    let opts = MetaSetOpts::new().cas(cas.unwrap() + 1);
+    let (status, _) = client.meta_set(key, "value", opts).await.unwrap();
+    assert_eq!(status, Status::Exists);
+}
+
+#[tokio::test]
+async fn test_set_verified() {
+    let client = helpers::connect("memcache://localhost:11211")
+        .await
+        .unwrap();
+
+    client
+        .set_verified("test_set_verified", "value", None)
+        .await
+        .unwrap();
+
+    let value: Option<String> = client.get("test_set_verified").await.unwrap();
+    assert_eq!(value.as_deref(), Some("value"));
+}